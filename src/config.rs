@@ -0,0 +1,112 @@
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use vulkano::format::Format;
+
+use crate::{
+    vulkan::{swapchain::PresentMode, PowerPreference},
+    wayland::settings::SIZE,
+    FORMAT,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct RendererConfig {
+    pub size: (u32, u32),
+    pub clear_color: [f32; 4],
+    pub device_name: Option<String>,
+    pub power_preference: PowerPreference,
+    pub msaa_samples: u32,
+    // Renders at this multiple of `size` and scales the result to `size`.
+    pub render_scale: f32,
+    pub software_fallback: bool,
+    // Present windowed frames through a Vulkan swapchain instead of copying them into shm buffers.
+    pub vulkan_presentation: bool,
+    // Only used with `vulkan_presentation`, shm buffers are always paced by the compositor's frame
+    // callbacks.
+    pub present_mode: PresentMode,
+    // Caps the windowed frame rate below the display's, `None` for no cap.
+    pub max_fps: Option<u32>,
+    // The format rendered to and read back, see `swizzle::is_readable`. Not saved, as vulkano's
+    // formats don't implement serde, so a loaded config always has the default.
+    #[serde(skip)]
+    pub format: Format,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        RendererConfig {
+            size: SIZE,
+            clear_color: [0.2, 0.2, 0.2, 1.0],
+            device_name: None,
+            power_preference: PowerPreference::HighPerformance,
+            msaa_samples: 1,
+            render_scale: 1.0,
+            software_fallback: false,
+            vulkan_presentation: false,
+            present_mode: PresentMode::Vsync,
+            max_fps: None,
+            format: FORMAT,
+        }
+    }
+}
+
+impl RendererConfig {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+
+        return serde_json::from_str(&contents)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error));
+    }
+
+    #[allow(dead_code)]
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+        return fs::write(path, contents);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_round_trips_through_a_file() {
+        let config = RendererConfig {
+            size: (640, 480),
+            clear_color: [0.1, 0.2, 0.3, 0.5],
+            device_name: Some("llvmpipe".to_owned()),
+            power_preference: PowerPreference::LowPower,
+            msaa_samples: 4,
+            render_scale: 0.5,
+            software_fallback: true,
+            vulkan_presentation: true,
+            present_mode: PresentMode::Mailbox,
+            max_fps: Some(30),
+            format: FORMAT,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        config.save(&path).unwrap();
+
+        assert_eq!(RendererConfig::load(&path).unwrap(), config);
+    }
+
+    #[test]
+    fn missing_fields_take_their_defaults() {
+        let config: RendererConfig =
+            serde_json::from_str(r#"{ "msaa_samples": 4, "present_mode": "Immediate" }"#).unwrap();
+
+        assert_eq!(
+            config,
+            RendererConfig {
+                msaa_samples: 4,
+                present_mode: PresentMode::Immediate,
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -1,28 +1,55 @@
 use std::{
-    fs::File,
-    io::{BufWriter, Write},
+    cell::Cell,
+    env,
+    fs::{create_dir_all, File},
+    io::BufWriter,
+    mem,
+    path::{Path, PathBuf},
+    process,
     sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use bytemuck::AnyBitPattern;
-use vulkan::VulkanProcessor;
+use config::RendererConfig;
+use memmap2::MmapOptions;
+use shader_watcher::ShaderWatcher;
+use swizzle::{swizzle_to_wayland, to_rgba8};
+use vulkan::{
+    error::{PipelineError, ShaderLoadError},
+    frames::FRAMES_IN_FLIGHT,
+    pipeline::{AttachmentOps, BlendMode, DepthStencilConfig, RasterizationConfig},
+    swapchain::PresentMode,
+    PowerPreference, TimestampQuery, VulkanProcessor,
+};
 use vulkano::{
     buffer::{BufferUsage, Subbuffer},
     command_buffer::{
-        CommandBufferUsage, CopyImageToBufferInfo, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
-        SubpassBeginInfo, SubpassContents, SubpassEndInfo,
+        AutoCommandBufferBuilder, BlitImageInfo, CommandBufferUsage, CopyImageToBufferInfo,
+        PrimaryAutoCommandBuffer,
+    },
+    format::{ClearValue, Format},
+    image::{
+        sampler::Filter, view::ImageView, Image, ImageAspects, ImageSubresourceLayers, ImageType,
+        ImageUsage, SampleCount,
     },
-    format::Format,
-    image::{view::ImageView, ImageType, ImageUsage},
     memory::allocator::MemoryTypeFilter,
-    pipeline::graphics::{
-        vertex_input::{Vertex, VertexDefinition},
-        viewport::Viewport,
+    pipeline::{
+        graphics::{input_assembly::PrimitiveTopology, vertex_input::Vertex},
+        GraphicsPipeline,
     },
-    render_pass::{Framebuffer, FramebufferCreateInfo, Subpass},
+    render_pass::{Framebuffer, RenderPass, Subpass},
+    shader::ShaderModule,
+    swapchain::ColorSpace,
 };
-use wayland::{settings::SIZE, WaylandClient};
+use wayland::WaylandClient;
+use xkbcommon::xkb::{self, Keysym};
 
+mod config;
+mod obj;
+mod rasterizer;
+mod shader_watcher;
+mod swizzle;
 mod vulkan;
 mod wayland;
 
@@ -33,9 +60,10 @@ mod vs {
             #version 460
 
             layout(location = 0) in vec2 position;
+            layout(location = 1) in vec2 offset;
 
             void main() {
-                gl_Position = vec4(position, 0.0, 1.0);
+                gl_Position = vec4(position + offset, 0.0, 1.0);
             }
         ",
     }
@@ -58,9 +86,40 @@ mod fs {
 
 struct GraphicsProcessor<'a> {
     processor: &'a VulkanProcessor,
+    // As created from, see `config` for the current settings.
+    config: RendererConfig,
     size: (u32, u32),
+    // The scene is rendered at `size` times this and scaled to `size` with a linear blit, below
+    // 1.0 for speed or above it for supersampling.
+    render_scale: f32,
+    clear_color: [f32; 4],
+    // Of the rendered image and the readback buffer, converted to 8 bits on readback.
+    format: Format,
+    render_pass: Arc<RenderPass>,
+    shader_modules: Vec<Arc<ShaderModule>>,
+    shader_reload: Option<(ShaderFiles, ShaderWatcher)>,
+    pipeline: Arc<GraphicsPipeline>,
+    mesh: Mesh,
+    instance_buffer: Subbuffer<[MyInstance]>,
     data_buffer: Subbuffer<[u8]>,
     command_buffer: Arc<PrimaryAutoCommandBuffer>,
+    // Brackets the render in `command_buffer`, `None` without timestamp support.
+    timestamp_query: Option<TimestampQuery>,
+    // Spent recording since the last `execute`, reported in its `FrameStats`.
+    record_time: Duration,
+}
+
+// Where the time of one `execute` went, in milliseconds.
+#[derive(Clone, Copy, Debug, Default)]
+struct FrameStats {
+    // Zero unless the frame had to be re-recorded, e.g. after a resize, as the command buffer is
+    // otherwise reused.
+    cpu_record_ms: f64,
+    // Measured with GPU timestamps where supported, otherwise the wall time from submission until
+    // the fence signals.
+    gpu_execute_ms: f64,
+    // Converting the pixels and copying them into the Wayland buffer.
+    readback_ms: f64,
 }
 
 #[derive(Vertex, AnyBitPattern, Clone, Copy)]
@@ -70,93 +129,485 @@ struct MyVertex {
     position: [f32; 2],
 }
 
+// Per-instance data, read from vertex buffer binding 1.
+#[derive(Vertex, AnyBitPattern, Clone, Copy)]
+#[repr(C)]
+struct MyInstance {
+    #[format(R32G32_SFLOAT)]
+    offset: [f32; 2],
+}
+
+struct Mesh {
+    vertex_buffer: Subbuffer<[MyVertex]>,
+    index_buffer: Subbuffer<[u32]>,
+}
+
+struct ShaderFiles {
+    vertex: PathBuf,
+    fragment: PathBuf,
+}
+
+impl ShaderFiles {
+    fn load(&self, processor: &VulkanProcessor) -> Result<Vec<Arc<ShaderModule>>, ShaderLoadError> {
+        return Ok(vec![
+            processor.load_shader_from_path(&self.vertex)?,
+            processor.load_shader_from_path(&self.fragment)?,
+        ]);
+    }
+}
+
+// The default render format, see `RendererConfig::format`. Matches the byte order of Wayland's
+// little-endian `Argb8888`, so frames are copied into the shm buffer as they are. Other 8-bit RGBA
+// orders are swizzled and float formats quantized, see `swizzle`. Compositors treat shm pixels as
+// sRGB-encoded, so being `UNORM`, shader outputs are taken to be encoded already.
+const FORMAT: Format = Format::B8G8R8A8_UNORM;
+
+// Per channel, for rounding differences between the GPU and `rasterizer`.
+const REFERENCE_TOLERANCE: u8 = 1;
+
 impl<'a> GraphicsProcessor<'a> {
-    fn new(processor: &'a VulkanProcessor, size: (u32, u32)) -> Self {
-        const FORMAT: Format = Format::R8G8B8A8_UNORM;
+    #[allow(dead_code)]
+    fn new(processor: &'a VulkanProcessor, size: (u32, u32), format: Format) -> Self {
+        Self::from_config(
+            processor,
+            &RendererConfig {
+                size,
+                format,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn from_config(processor: &'a VulkanProcessor, config: &RendererConfig) -> Self {
+        return Self::with_vertices(processor, config, Self::triangle(), vec![0, 1, 2]);
+    }
+
+    fn with_vertices(
+        processor: &'a VulkanProcessor,
+        config: &RendererConfig,
+        vertices: Vec<MyVertex>,
+        indices: Vec<u32>,
+    ) -> Self {
+        let shader_modules = processor.load_shader_modules(vec![vs::load, fs::load]);
+        let mesh = Self::create_mesh(processor, vertices, indices);
+
+        return Self::from_parts(processor, config, shader_modules, mesh);
+    }
+
+    // Uses SPIR-V shaders from disk instead of the embedded ones. With `watch`, they are reloaded
+    // by `reload_shaders_if_changed` whenever the files change.
+    fn with_shader_files(
+        processor: &'a VulkanProcessor,
+        config: &RendererConfig,
+        shader_files: ShaderFiles,
+        watch: bool,
+    ) -> Self {
+        let shader_modules = shader_files
+            .load(processor)
+            .unwrap_or_else(|error| panic!("Failed to load shaders: {}", error));
+
+        let mesh = Self::create_mesh(processor, Self::triangle(), vec![0, 1, 2]);
+        let mut graphics_processor = Self::from_parts(processor, config, shader_modules, mesh);
 
+        if watch {
+            let shader_watcher = ShaderWatcher::new(&[
+                shader_files.vertex.as_path(),
+                shader_files.fragment.as_path(),
+            ])
+            .expect("Failed to watch shader files.");
+            graphics_processor.shader_reload = Some((shader_files, shader_watcher));
+        }
+
+        return graphics_processor;
+    }
+
+    fn from_parts(
+        processor: &'a VulkanProcessor,
+        config: &RendererConfig,
+        shader_modules: Vec<Arc<ShaderModule>>,
+        mesh: Mesh,
+    ) -> Self {
+        let size = config.size;
+        let render_scale = config.render_scale;
+        Self::validate_size(processor, size);
+        Self::validate_size(processor, Self::scaled_size(size, render_scale));
+        let clear_color = config.clear_color;
+        let format = config.format;
+        assert!(
+            swizzle::is_readable(format),
+            "Can't read back frames rendered to {:?}.",
+            format
+        );
+
+        let samples = SampleCount::try_from(config.msaa_samples)
+            .expect("MSAA sample count must be a power of two between 1 and 64.");
+        let render_pass = processor.create_render_pass(format, samples, AttachmentOps::default());
+
+        let pipeline = Self::create_pipeline(processor, &render_pass, &shader_modules)
+            .unwrap_or_else(|error| panic!("{}", error));
+
+        let instance_buffer =
+            Self::create_instance_buffer(processor, vec![MyInstance { offset: [0.0, 0.0] }]);
+
+        let record_start = Instant::now();
+        let (data_buffer, command_buffer, timestamp_query) = Self::record_frame_target(
+            processor,
+            size,
+            render_scale,
+            clear_color,
+            format,
+            &render_pass,
+            &pipeline,
+            &mesh,
+            &instance_buffer,
+        );
+
+        return GraphicsProcessor {
+            processor,
+            config: config.clone(),
+            size,
+            render_scale,
+            clear_color,
+            format,
+            render_pass,
+            shader_modules,
+            shader_reload: None,
+            pipeline,
+            mesh,
+            instance_buffer,
+            data_buffer,
+            command_buffer,
+            timestamp_query,
+            record_time: record_start.elapsed(),
+        };
+    }
+
+    fn triangle() -> Vec<MyVertex> {
+        vec![
+            MyVertex {
+                position: [-0.1, 0.1],
+            },
+            MyVertex {
+                position: [0.1, 0.1],
+            },
+            MyVertex {
+                position: [0.0, -0.141421356],
+            },
+        ]
+    }
+
+    // PREFER_DEVICE | HOST_SEQUENTIAL_WRITE picks memory the host can map, device-local where the
+    // device exposes such a heap (e.g. resizable BAR) and system memory otherwise. That keeps
+    // `update_vertices` a plain `write()`, but the contents should only ever be written
+    // sequentially and never read back from the host, since the mapping may be uncached.
+    fn create_mesh(
+        processor: &VulkanProcessor,
+        vertices: Vec<MyVertex>,
+        indices: Vec<u32>,
+    ) -> Mesh {
         let vertex_buffer = processor.create_iter_buffer(
-            vec![
-                MyVertex {
-                    position: [-0.1, 0.1],
-                },
-                MyVertex {
-                    position: [0.1, 0.1],
-                },
-                MyVertex {
-                    position: [0.0, -0.141421356],
-                },
-            ],
+            vertices,
             BufferUsage::VERTEX_BUFFER,
             MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
         );
 
-        let render_pass = processor.create_render_pass(FORMAT);
+        return Mesh {
+            vertex_buffer,
+            index_buffer: processor.create_index_buffer(indices),
+        };
+    }
 
-        let image = processor.create_image(
+    // Overwrites the vertices in place, so the recorded command buffer stays valid. Must be called
+    // between frames, as `write()` fails while the GPU is still reading the buffer.
+    #[allow(dead_code)]
+    fn update_vertices(&self, vertices: &[MyVertex]) {
+        assert_eq!(
+            vertices.len() as u64,
+            self.mesh.vertex_buffer.len(),
+            "update_vertices can't change the vertex count, use set_mesh instead."
+        );
+
+        self.mesh
+            .vertex_buffer
+            .write()
+            .expect("Vertex buffer is in use by the GPU.")
+            .copy_from_slice(vertices);
+    }
+
+    // Replaces the vertex and index buffers, which re-records the frame.
+    fn set_mesh(&mut self, vertices: Vec<MyVertex>, indices: Vec<u32>) {
+        self.mesh = Self::create_mesh(self.processor, vertices, indices);
+        self.rebuild_frame_target();
+    }
+
+    fn create_instance_buffer(
+        processor: &VulkanProcessor,
+        instances: Vec<MyInstance>,
+    ) -> Subbuffer<[MyInstance]> {
+        return processor.create_iter_buffer(
+            instances,
+            BufferUsage::VERTEX_BUFFER,
+            MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+        );
+    }
+
+    // The mesh is drawn once per instance in a single draw call.
+    #[allow(dead_code)]
+    fn set_instances(&mut self, instances: Vec<MyInstance>) {
+        self.instance_buffer = Self::create_instance_buffer(self.processor, instances);
+        self.rebuild_frame_target();
+    }
+
+    // The viewport is dynamic, set from the framebuffer in `record_scene`, so the pipeline
+    // doesn't depend on the size.
+    fn create_pipeline(
+        processor: &VulkanProcessor,
+        render_pass: &Arc<RenderPass>,
+        shader_modules: &[Arc<ShaderModule>],
+    ) -> Result<Arc<GraphicsPipeline>, PipelineError> {
+        let stages_layout = processor.create_pipeline_stages_layout_from_modules(shader_modules)?;
+
+        let vertex_input_state = VulkanProcessor::create_instanced_vertex_input_state::<
+            MyVertex,
+            MyInstance,
+        >(&stages_layout.0)?;
+
+        let subpass = Subpass::from(render_pass.clone(), 0).unwrap();
+
+        return processor.create_graphics_pipeline(
+            stages_layout,
+            vertex_input_state,
+            None,
+            subpass,
+            RasterizationConfig::default(),
+            DepthStencilConfig::default(),
+            BlendMode::Opaque,
+            PrimitiveTopology::TriangleList,
+        );
+    }
+
+    fn validate_size(processor: &VulkanProcessor, size: (u32, u32)) {
+        let max_dimension = processor.max_image_dimension_2d();
+
+        assert!(
+            size.0 > 0 && size.1 > 0,
+            "Render size must be non-zero, got {}x{}.",
+            size.0,
+            size.1
+        );
+        assert!(
+            size.0 <= max_dimension && size.1 <= max_dimension,
+            "Render size {}x{} exceeds the device's maximum image dimension of {}.",
+            size.0,
+            size.1,
+            max_dimension
+        );
+    }
+
+    // Only the frame target is rebuilt, the pipeline's viewport is dynamic.
+    fn resize(&mut self, size: (u32, u32)) {
+        Self::validate_size(self.processor, size);
+        Self::validate_size(self.processor, Self::scaled_size(size, self.render_scale));
+        self.size = size;
+        self.rebuild_frame_target();
+    }
+
+    #[allow(dead_code)]
+    fn set_render_scale(&mut self, render_scale: f32) {
+        assert!(
+            render_scale > 0.0 && render_scale.is_finite(),
+            "Render scale must be positive, got {}.",
+            render_scale
+        );
+        Self::validate_size(self.processor, Self::scaled_size(self.size, render_scale));
+
+        self.render_scale = render_scale;
+        self.rebuild_frame_target();
+    }
+
+    fn render_size(&self) -> (u32, u32) {
+        Self::scaled_size(self.size, self.render_scale)
+    }
+
+    // Rounded, and at least 1x1 however small the scale.
+    fn scaled_size(size: (u32, u32), render_scale: f32) -> (u32, u32) {
+        let scale = |dimension: u32| ((dimension as f32 * render_scale).round() as u32).max(1);
+
+        return (scale(size.0), scale(size.1));
+    }
+
+    // Scaled rendering draws into an image of its own, which is then blitted to the output size.
+    fn create_scaled_image(
+        processor: &VulkanProcessor,
+        render_size: (u32, u32),
+        format: Format,
+    ) -> Arc<Image> {
+        processor.create_image_or_panic(
             ImageType::Dim2d,
-            FORMAT,
-            [size.0, size.1, 1],
+            format,
+            [render_size.0, render_size.1, 1],
             ImageUsage::TRANSFER_SRC | ImageUsage::COLOR_ATTACHMENT,
             MemoryTypeFilter::PREFER_DEVICE,
-        );
+        )
+    }
 
-        let view = ImageView::new_default(image.clone()).expect("Failed to create image view.");
+    // Keeps the current pipeline when the new shaders fail to load or to build a pipeline, so a
+    // mistake in a shader doesn't take the window down.
+    fn reload_shaders_if_changed(&mut self) {
+        let Some((shader_files, shader_watcher)) = &self.shader_reload else {
+            return;
+        };
 
-        let framebuffer = Framebuffer::new(
-            render_pass.clone(),
-            FramebufferCreateInfo {
-                attachments: vec![view],
-                ..Default::default()
-            },
+        if !shader_watcher.poll_changed() {
+            return;
+        }
+
+        let shader_modules = match shader_files.load(self.processor) {
+            Ok(shader_modules) => shader_modules,
+            Err(error) => {
+                println!(
+                    "Failed to reload shaders, keeping the previous pipeline: {}",
+                    error
+                );
+                return;
+            }
+        };
+
+        // E.g. a vertex shader whose inputs no longer match `MyVertex` and `MyInstance`.
+        let pipeline =
+            match Self::create_pipeline(self.processor, &self.render_pass, &shader_modules) {
+                Ok(pipeline) => pipeline,
+                Err(error) => {
+                    println!(
+                        "Failed to rebuild the pipeline, keeping the previous one: {}",
+                        error
+                    );
+                    return;
+                }
+            };
+
+        self.shader_modules = shader_modules;
+        self.pipeline = pipeline;
+        self.rebuild_frame_target();
+        println!("Reloaded shaders.");
+    }
+
+    // The clear value is recorded into the command buffer, so changing it re-records the frame.
+    // Alpha is kept through readback, so an alpha below 1.0 gives a translucent window. The color
+    // is straight alpha, it's premultiplied when recorded to match the Wayland buffer.
+    #[allow(dead_code)]
+    fn set_clear_color(&mut self, clear_color: [f32; 4]) {
+        self.clear_color = clear_color;
+        self.rebuild_frame_target();
+    }
+
+    fn rebuild_frame_target(&mut self) {
+        let record_start = Instant::now();
+        let (data_buffer, command_buffer, timestamp_query) = self.create_frame_target();
+        self.data_buffer = data_buffer;
+        self.command_buffer = command_buffer;
+        self.timestamp_query = timestamp_query;
+        self.record_time += record_start.elapsed();
+    }
+
+    fn create_frame_target(
+        &self,
+    ) -> (
+        Subbuffer<[u8]>,
+        Arc<PrimaryAutoCommandBuffer>,
+        Option<TimestampQuery>,
+    ) {
+        Self::record_frame_target(
+            self.processor,
+            self.size,
+            self.render_scale,
+            self.clear_color,
+            self.format,
+            &self.render_pass,
+            &self.pipeline,
+            &self.mesh,
+            &self.instance_buffer,
         )
-        .unwrap();
+    }
 
-        let data_buffer = processor.create_iter_buffer(
-            (0..size.0 * size.1 * 4).map(|_| 0u8).collect(),
-            BufferUsage::TRANSFER_DST,
-            MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+    // Records a render of the scene into its own image, copied out to its own host-visible buffer,
+    // so several of these can be in flight on the GPU at once. The render is timed with its own
+    // timestamp query for the same reason.
+    #[allow(clippy::too_many_arguments)]
+    fn record_frame_target(
+        processor: &VulkanProcessor,
+        size: (u32, u32),
+        render_scale: f32,
+        clear_color: [f32; 4],
+        format: Format,
+        render_pass: &Arc<RenderPass>,
+        pipeline: &Arc<GraphicsPipeline>,
+        mesh: &Mesh,
+        instance_buffer: &Subbuffer<[MyInstance]>,
+    ) -> (
+        Subbuffer<[u8]>,
+        Arc<PrimaryAutoCommandBuffer>,
+        Option<TimestampQuery>,
+    ) {
+        let image = processor.create_image_or_panic(
+            ImageType::Dim2d,
+            format,
+            [size.0, size.1, 1],
+            ImageUsage::TRANSFER_SRC | ImageUsage::TRANSFER_DST | ImageUsage::COLOR_ATTACHMENT,
+            MemoryTypeFilter::PREFER_DEVICE,
         );
 
-        let viewport = Viewport {
-            offset: [0.0, 0.0],
-            extent: [size.0 as f32, size.1 as f32],
-            depth_range: 0.0..=1.0,
+        let render_size = Self::scaled_size(size, render_scale);
+        let render_image = if render_size == size {
+            image.clone()
+        } else {
+            Self::create_scaled_image(processor, render_size, format)
         };
 
-        let pipeline = {
-            let stages_layout = processor.create_pipeline_stages_layout(vec![vs::load, fs::load]);
-
-            let vertex_input_state = MyVertex::per_vertex()
-                .definition(&stages_layout.0[0].entry_point.info().input_interface)
-                .unwrap();
+        let view =
+            ImageView::new_default(render_image.clone()).expect("Failed to create image view.");
+        let (framebuffer, clear_values) =
+            Self::create_framebuffer(processor, render_size, clear_color, render_pass, view);
 
-            let subpass = Subpass::from(render_pass.clone(), 0).unwrap();
+        let data_buffer = processor.create_iter_buffer(
+            (0..(size.0 * size.1) as u64 * format.block_size())
+                .map(|_| 0u8)
+                .collect(),
+            BufferUsage::TRANSFER_DST,
+            MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+        );
 
-            processor.create_graphics_pipeline(stages_layout, vertex_input_state, viewport, subpass)
-        };
+        let timestamp_query = processor.create_timestamp_query();
 
         let command_buffer = processor.create_command_buffer(
             |builder| {
+                if let Some(ref timestamp_query) = timestamp_query {
+                    processor.begin_timestamp(builder, timestamp_query);
+                }
+                Self::record_scene(
+                    processor,
+                    builder,
+                    framebuffer,
+                    clear_values,
+                    pipeline,
+                    mesh,
+                    instance_buffer,
+                );
+
+                if render_size != size {
+                    builder
+                        .blit_image(BlitImageInfo {
+                            filter: Filter::Linear,
+                            ..BlitImageInfo::images(render_image, image.clone())
+                        })
+                        .unwrap();
+                }
+
+                if let Some(ref timestamp_query) = timestamp_query {
+                    processor.end_timestamp(builder, timestamp_query);
+                }
+
                 builder
-                    .begin_render_pass(
-                        RenderPassBeginInfo {
-                            clear_values: vec![Some([0.2, 0.2, 0.2, 1.0].into())],
-                            ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
-                        },
-                        SubpassBeginInfo {
-                            contents: SubpassContents::Inline,
-                            ..Default::default()
-                        },
-                    )
-                    .unwrap()
-                    .bind_pipeline_graphics(pipeline.clone())
-                    .unwrap()
-                    .bind_vertex_buffers(0, vertex_buffer.clone())
-                    .unwrap()
-                    .draw(3, 1, 0, 0)
-                    .unwrap()
-                    .end_render_pass(SubpassEndInfo::default())
-                    .unwrap()
                     .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
                         image,
                         data_buffer.clone(),
@@ -166,42 +617,1414 @@ impl<'a> GraphicsProcessor<'a> {
             CommandBufferUsage::MultipleSubmit,
         );
 
-        return GraphicsProcessor {
-            processor,
-            size,
-            data_buffer,
-            command_buffer,
+        return (data_buffer, command_buffer, timestamp_query);
+    }
+
+    // Renders the scene straight into `image`, e.g. a swapchain image, with no readback. The image
+    // must be in the processor's format and the current size, and with a render scale other than
+    // 1.0 also needs `TRANSFER_DST` usage.
+    fn record_to_image(&self, image: Arc<Image>) -> Arc<PrimaryAutoCommandBuffer> {
+        let view = ImageView::new_default(image).expect("Failed to create image view.");
+
+        return self.record_to_view(view);
+    }
+
+    // As `record_to_image`, for a view of part of an image, e.g. one layer of an image array from
+    // `create_layer_view`.
+    fn record_to_view(&self, view: Arc<ImageView>) -> Arc<PrimaryAutoCommandBuffer> {
+        return self.processor.create_command_buffer(
+            |builder| self.record_scene_to_view(builder, view),
+            CommandBufferUsage::OneTimeSubmit,
+        );
+    }
+
+    fn record_scene_to_view(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        view: Arc<ImageView>,
+    ) {
+        let render_size = self.render_size();
+        let scaled_image = if render_size == self.size {
+            None
+        } else {
+            Some(Self::create_scaled_image(
+                self.processor,
+                render_size,
+                self.format,
+            ))
+        };
+
+        let render_view = match scaled_image {
+            Some(ref scaled_image) => {
+                ImageView::new_default(scaled_image.clone()).expect("Failed to create image view.")
+            }
+            None => view.clone(),
+        };
+
+        let (framebuffer, clear_values) = Self::create_framebuffer(
+            self.processor,
+            render_size,
+            self.clear_color,
+            &self.render_pass,
+            render_view,
+        );
+
+        Self::record_scene(
+            self.processor,
+            builder,
+            framebuffer,
+            clear_values,
+            &self.pipeline,
+            &self.mesh,
+            &self.instance_buffer,
+        );
+
+        // Only into the part of the image the view covers.
+        if let Some(scaled_image) = scaled_image {
+            let subresource_range = view.subresource_range();
+            let mut blit_info = BlitImageInfo {
+                filter: Filter::Linear,
+                ..BlitImageInfo::images(scaled_image, view.image().clone())
+            };
+            blit_info.regions[0].dst_subresource = ImageSubresourceLayers {
+                aspects: ImageAspects::COLOR,
+                mip_level: subresource_range.mip_levels.start,
+                array_layers: subresource_range.array_layers.clone(),
+            };
+            blit_info.regions[0].src_subresource.array_layers =
+                0..subresource_range.array_layers.len() as u32;
+            blit_info.regions[0].dst_offsets = [[0, 0, 0], [self.size.0, self.size.1, 1]];
+
+            builder.blit_image(blit_info).unwrap();
+        }
+    }
+
+    // A multisampled render pass draws into an extra image and resolves into `view`, which has no
+    // clear value of its own.
+    fn create_framebuffer(
+        processor: &VulkanProcessor,
+        size: (u32, u32),
+        clear_color: [f32; 4],
+        render_pass: &Arc<RenderPass>,
+        view: Arc<ImageView>,
+    ) -> (Arc<Framebuffer>, Vec<Option<ClearValue>>) {
+        let [red, green, blue, alpha] = clear_color;
+        let clear_color = [red * alpha, green * alpha, blue * alpha, alpha];
+
+        let samples = render_pass.attachments()[0].samples;
+        let (attachments, clear_values) = if samples == SampleCount::Sample1 {
+            (vec![view], vec![Some(clear_color.into())])
+        } else {
+            let multisampled_image =
+                processor.create_multisampled_image(view.format(), [size.0, size.1], samples);
+            let multisampled_view =
+                ImageView::new_default(multisampled_image).expect("Failed to create image view.");
+
+            (
+                vec![multisampled_view, view],
+                vec![Some(clear_color.into()), None],
+            )
+        };
+
+        let framebuffer = processor
+            .create_framebuffer(render_pass, attachments, [size.0, size.1])
+            .unwrap_or_else(|error| panic!("{}", error));
+
+        return (framebuffer, clear_values);
+    }
+
+    fn record_scene(
+        processor: &VulkanProcessor,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        framebuffer: Arc<Framebuffer>,
+        clear_values: Vec<Option<ClearValue>>,
+        pipeline: &Arc<GraphicsPipeline>,
+        mesh: &Mesh,
+        instance_buffer: &Subbuffer<[MyInstance]>,
+    ) {
+        let extent = framebuffer.extent();
+
+        processor.record_render_pass(builder, framebuffer, clear_values, |builder| {
+            builder.bind_pipeline_graphics(pipeline.clone()).unwrap();
+            processor.set_viewport_and_scissor(builder, extent);
+            builder
+                .bind_vertex_buffers(0, (mesh.vertex_buffer.clone(), instance_buffer.clone()))
+                .unwrap()
+                .bind_index_buffer(mesh.index_buffer.clone())
+                .unwrap()
+                .draw_indexed(
+                    mesh.index_buffer.len() as u32,
+                    instance_buffer.len() as u32,
+                    0,
+                    0,
+                    0,
+                )
+                .unwrap();
+        });
+    }
+
+    // The config this was created from, with whatever has been changed since, e.g. the size
+    // after a resize, so saving it restores the renderer as it is now.
+    #[allow(dead_code)]
+    fn config(&self) -> RendererConfig {
+        RendererConfig {
+            size: self.size,
+            clear_color: self.clear_color,
+            render_scale: self.render_scale,
+            ..self.config.clone()
+        }
+    }
+
+    // Renders a frame into the start of `buffer_file`, an shm buffer at least the size of the
+    // frame. The pixels are converted to Wayland's byte order in a mapping of the file, so they
+    // aren't copied again on the way in.
+    fn execute(&mut self, buffer_file: &mut File) -> FrameStats {
+        let execute_start = Instant::now();
+        self.processor
+            .execute_then_wait(self.command_buffer.clone());
+        let execute_time = execute_start.elapsed();
+
+        let readback_start = Instant::now();
+        let frame_length = self.size.0 as usize * self.size.1 as usize * 4;
+        assert!(
+            buffer_file.metadata().unwrap().len() >= frame_length as u64,
+            "The buffer file is smaller than the {}x{} frame.",
+            self.size.0,
+            self.size.1
+        );
+
+        // Safety: the file isn't resized while it's mapped, and the compositor doesn't read the
+        // buffer until it's attached after this returns.
+        let mut mapping = unsafe { MmapOptions::new().len(frame_length).map_mut(&*buffer_file) }
+            .expect("Failed to map the buffer file.");
+
+        if swizzle::is_8_bit(self.format) {
+            mapping.copy_from_slice(&self.data_buffer.read().unwrap());
+            swizzle_to_wayland(self.format, &mut mapping);
+        } else {
+            let pixels = self.data_buffer.read().unwrap().to_vec();
+            mapping.copy_from_slice(&to_rgba8(self.format, pixels));
+            swizzle_to_wayland(Format::R8G8B8A8_UNORM, &mut mapping);
+        }
+        drop(mapping);
+        let readback_time = readback_start.elapsed();
+
+        let gpu_execute_ms = match self.gpu_time_nanos() {
+            Some(gpu_time) => gpu_time as f64 / 1e6,
+            None => execute_time.as_secs_f64() * 1e3,
+        };
+
+        return FrameStats {
+            cpu_record_ms: mem::take(&mut self.record_time).as_secs_f64() * 1e3,
+            gpu_execute_ms,
+            readback_ms: readback_time.as_secs_f64() * 1e3,
         };
     }
 
-    fn execute(&self, buffer_file: &File) {
+    // Of the render in the last executed frame, see `VulkanProcessor::gpu_time_nanos`.
+    fn gpu_time_nanos(&self) -> Option<u64> {
+        let timestamp_query = self.timestamp_query.as_ref()?;
+
+        return self.processor.gpu_time_nanos(timestamp_query);
+    }
+
+    // Renders `frames` frames into `out_dir` as PNGs, keeping up to `FRAMES_IN_FLIGHT` frames in
+    // flight so encoding frame N overlaps the GPU rendering the frames after it. `update` returns
+    // the instances of each frame, as many as there are now. Every slot of the processor's frame
+    // ring has an instance buffer of its own, which is only written once the slot's previous frame
+    // is done. The mesh is shared by all of them, so it must not change while this runs.
+    fn render_sequence_pipelined<F: FnMut(usize) -> Vec<MyInstance>>(
+        &self,
+        frames: usize,
+        out_dir: &Path,
+        mut update: F,
+    ) {
+        let instance_count = self.instance_buffer.len() as usize;
+        let targets: Vec<_> = (0..FRAMES_IN_FLIGHT)
+            .map(|_| {
+                let instance_buffer = Self::create_instance_buffer(
+                    self.processor,
+                    vec![MyInstance { offset: [0.0, 0.0] }; instance_count],
+                );
+                let (data_buffer, command_buffer, _) = Self::record_frame_target(
+                    self.processor,
+                    self.size,
+                    self.render_scale,
+                    self.clear_color,
+                    self.format,
+                    &self.render_pass,
+                    &self.pipeline,
+                    &self.mesh,
+                    &instance_buffer,
+                );
+
+                (instance_buffer, data_buffer, command_buffer)
+            })
+            .collect();
+
+        create_dir_all(out_dir).expect("Failed to create output directory.");
+
+        // The frame each slot was last submitted with, until its PNG is written.
+        let mut unwritten: Vec<Option<usize>> = vec![None; FRAMES_IN_FLIGHT];
+
+        for frame in 0..frames {
+            // Waits for the frame that last used the slot.
+            let slot = self.processor.begin_frame();
+            let (instance_buffer, data_buffer, command_buffer) = &targets[slot];
+
+            if let Some(finished_frame) = unwritten[slot].take() {
+                self.write_frame_png(data_buffer, finished_frame, out_dir);
+            }
+
+            let instances = update(frame);
+            assert_eq!(
+                instances.len(),
+                instance_count,
+                "render_sequence_pipelined can't change the instance count."
+            );
+            instance_buffer
+                .write()
+                .expect("Instance buffer is in use by the GPU.")
+                .copy_from_slice(&instances);
+
+            self.processor.end_frame(slot, command_buffer.clone());
+            unwritten[slot] = Some(frame);
+        }
+
+        self.processor.wait_for_frames();
+        let mut unwritten: Vec<(usize, usize)> = unwritten
+            .into_iter()
+            .enumerate()
+            .filter_map(|(slot, frame)| Some((frame?, slot)))
+            .collect();
+        unwritten.sort();
+        for (frame, slot) in unwritten {
+            self.write_frame_png(&targets[slot].1, frame, out_dir);
+        }
+    }
+
+    fn write_frame_png(&self, data_buffer: &Subbuffer<[u8]>, frame: usize, out_dir: &Path) {
+        self.write_png(
+            data_buffer,
+            &out_dir.join(format!("frame_{:05}.png", frame)),
+        );
+    }
+
+    // The RGBA color at (x, y) of the last executed frame, read straight from the mapped readback
+    // buffer without copying the rest of it.
+    #[allow(dead_code)]
+    fn read_pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        assert!(
+            x < self.size.0 && y < self.size.1,
+            "Pixel ({}, {}) is outside the {}x{} frame.",
+            x,
+            y,
+            self.size.0,
+            self.size.1
+        );
+
+        let texel_size = self.format.block_size() as usize;
+        let offset = texel_offset(self.size, x, y, texel_size);
+        let texel = self.data_buffer.read().unwrap()[offset..offset + texel_size].to_vec();
+
+        return to_rgba8(self.format, texel).try_into().unwrap();
+    }
+
+    // The whole last executed frame as RGBA, as `read_pixel` reads a single pixel.
+    fn read_frame(&self) -> Vec<u8> {
+        let pixels = self.processor.download_buffer(&self.data_buffer);
+
+        return to_rgba8(self.format, pixels);
+    }
+
+    // Renders a frame but only reads back the `width` x `height` rectangle at (x, y), as RGBA rows,
+    // e.g. for a tile that changed or a thumbnail. The frame's own readback buffer is untouched.
+    #[allow(dead_code)]
+    fn execute_region(&self, x: u32, y: u32, width: u32, height: u32) -> Vec<u8> {
+        let image = self.processor.create_image_or_panic(
+            ImageType::Dim2d,
+            self.format,
+            [self.size.0, self.size.1, 1],
+            ImageUsage::TRANSFER_SRC | ImageUsage::TRANSFER_DST | ImageUsage::COLOR_ATTACHMENT,
+            MemoryTypeFilter::PREFER_DEVICE,
+        );
+        let view = ImageView::new_default(image.clone()).expect("Failed to create image view.");
+
+        let region_buffer = self.processor.create_iter_buffer(
+            (0..(width * height) as u64 * self.format.block_size())
+                .map(|_| 0u8)
+                .collect(),
+            BufferUsage::TRANSFER_DST,
+            MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+        );
+
+        let command_buffer = self.processor.create_command_buffer(
+            |builder| {
+                self.record_scene_to_view(builder, view);
+                self.processor.record_copy_image_region_to_buffer(
+                    builder,
+                    image,
+                    region_buffer.clone(),
+                    [x, y],
+                    [width, height],
+                );
+            },
+            CommandBufferUsage::OneTimeSubmit,
+        );
+        self.processor.execute_then_wait(command_buffer);
+
+        return to_rgba8(self.format, region_buffer.read().unwrap().to_vec());
+    }
+
+    // Renders a frame and waits for it, leaving the pixels in the readback buffer untouched.
+    fn execute_without_readback(&mut self) {
+        self.processor
+            .execute_then_wait(self.command_buffer.clone());
+    }
+
+    // The Wayland path keeps using `execute`, which writes raw BGRA into the shm file.
+    fn execute_to_png(&mut self, path: &Path) {
         self.processor
             .execute_then_wait(self.command_buffer.clone());
 
-        let result = self.data_buffer.read().unwrap();
-        let mut writer = BufWriter::new(buffer_file);
-        let mut index = 0;
-        for _x in 0..self.size.0 {
-            for _y in 0..self.size.1 {
-                let r: u8 = result[index];
-                let g: u8 = result[index + 1];
-                let b: u8 = result[index + 2];
-                let a: u8 = result[index + 3];
-                index += 4;
+        self.write_png(&self.data_buffer, path);
+    }
+
+    fn write_png(&self, data_buffer: &Subbuffer<[u8]>, path: &Path) {
+        let pixels = to_rgba8(self.format, self.processor.download_buffer(data_buffer));
+
+        encode_png(path, self.size, &pixels);
+    }
+
+    // Saves the last executed frame, which `execute` leaves in the readback buffer, so the Wayland
+    // path can capture what it just drew without rendering again.
+    fn save_frame_png(&self, path: &Path) {
+        encode_png(path, self.size, &self.read_frame());
+    }
+}
+
+// Of pixel (x, y) in a tightly packed frame of `size`, stored row by row from the top.
+fn texel_offset(size: (u32, u32), x: u32, y: u32, texel_size: usize) -> usize {
+    (y as usize * size.0 as usize + x as usize) * texel_size
+}
+
+fn encode_png(path: &Path, size: (u32, u32), pixels: &[u8]) {
+    let file = File::create(path).expect("Failed to create PNG file.");
 
-                writer.write_all(&[b, g, r, a]).unwrap();
+    let mut encoder = png::Encoder::new(BufWriter::new(file), size.0, size.1);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().unwrap();
+    writer
+        .write_image_data(pixels)
+        .expect("Failed to encode PNG.");
+}
+
+struct Arguments {
+    config: Option<PathBuf>,
+    render_once: Option<PathBuf>,
+    size: Option<(u32, u32)>,
+    shader_files: Option<ShaderFiles>,
+    watch_shaders: bool,
+    software_fallback: bool,
+    vulkan_presentation: bool,
+    max_fps: Option<u32>,
+    present_mode: Option<PresentMode>,
+    render_scale: Option<f32>,
+    low_power: bool,
+    verify: bool,
+    benchmark_frames: Option<u32>,
+    screenshot_key: Keysym,
+    mesh: Option<PathBuf>,
+    render_sequence: Option<(PathBuf, usize)>,
+}
+
+const USAGE: &str =
+    "Usage: vase [--config <config.json>] [--render-once | --headless <output.png>] \
+     [--size <width>x<height>] [--shaders <vertex.spv> <fragment.spv>] [--watch-shaders] \
+     [--software-fallback] [--vulkan-present] [--present-mode <vsync|mailbox|immediate>] \
+     [--max-fps <fps>] [--render-scale <scale>] [--low-power] [--verify] \
+     [--benchmark <frames>] [--screenshot-key <keysym>] [--mesh <model.obj>] \
+     [--render-sequence <directory> <frames>]";
+
+fn parse_arguments() -> Arguments {
+    let mut arguments = Arguments {
+        config: None,
+        render_once: None,
+        size: None,
+        shader_files: None,
+        watch_shaders: false,
+        software_fallback: false,
+        vulkan_presentation: false,
+        max_fps: None,
+        present_mode: None,
+        render_scale: None,
+        low_power: false,
+        verify: false,
+        benchmark_frames: None,
+        screenshot_key: Keysym::Print,
+        mesh: None,
+        render_sequence: None,
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => {
+                arguments.config = Some(
+                    args.next()
+                        .map(PathBuf::from)
+                        .unwrap_or_else(|| exit_with_usage("--config requires a path.")),
+                );
+            }
+            "--render-once" | "--headless" => {
+                arguments.render_once =
+                    Some(args.next().map(PathBuf::from).unwrap_or_else(|| {
+                        exit_with_usage("--render-once requires an output path.")
+                    }));
+            }
+            "--size" => {
+                let size = args
+                    .next()
+                    .unwrap_or_else(|| exit_with_usage("--size requires a value."));
+                arguments.size = Some(
+                    parse_size(&size)
+                        .unwrap_or_else(|| exit_with_usage(&format!("Invalid size '{}'.", size))),
+                );
+            }
+            "--shaders" => {
+                let mut next_path = || {
+                    args.next().map(PathBuf::from).unwrap_or_else(|| {
+                        exit_with_usage("--shaders requires a vertex and a fragment shader path.")
+                    })
+                };
+                arguments.shader_files = Some(ShaderFiles {
+                    vertex: next_path(),
+                    fragment: next_path(),
+                });
+            }
+            "--watch-shaders" => arguments.watch_shaders = true,
+            "--software-fallback" => arguments.software_fallback = true,
+            "--vulkan-present" => arguments.vulkan_presentation = true,
+            "--low-power" => arguments.low_power = true,
+            "--verify" => arguments.verify = true,
+            "--max-fps" => {
+                let max_fps = args
+                    .next()
+                    .unwrap_or_else(|| exit_with_usage("--max-fps requires a value."));
+                arguments.max_fps = Some(
+                    max_fps
+                        .parse()
+                        .ok()
+                        .filter(|&max_fps| max_fps > 0)
+                        .unwrap_or_else(|| {
+                            exit_with_usage(&format!("Invalid frame rate cap '{}'.", max_fps))
+                        }),
+                );
             }
+            "--present-mode" => {
+                let present_mode = args
+                    .next()
+                    .unwrap_or_else(|| exit_with_usage("--present-mode requires a mode."));
+                arguments.present_mode = Some(match present_mode.as_str() {
+                    "vsync" => PresentMode::Vsync,
+                    "mailbox" => PresentMode::Mailbox,
+                    "immediate" => PresentMode::Immediate,
+                    _ => exit_with_usage(&format!("Unknown present mode '{}'.", present_mode)),
+                });
+            }
+            "--benchmark" => {
+                let frames = args
+                    .next()
+                    .unwrap_or_else(|| exit_with_usage("--benchmark requires a frame count."));
+                arguments.benchmark_frames = Some(
+                    frames
+                        .parse()
+                        .ok()
+                        .filter(|&frames| frames > 0)
+                        .unwrap_or_else(|| {
+                            exit_with_usage(&format!("Invalid frame count '{}'.", frames))
+                        }),
+                );
+            }
+            "--screenshot-key" => {
+                let name = args
+                    .next()
+                    .unwrap_or_else(|| exit_with_usage("--screenshot-key requires a key name."));
+                // Names as in xkbcommon-keysyms.h without the `XKB_KEY_` prefix, e.g. `Print` or
+                // `s`.
+                let keysym = xkb::keysym_from_name(&name, xkb::KEYSYM_NO_FLAGS);
+                if keysym == Keysym::NoSymbol {
+                    exit_with_usage(&format!("Unknown key '{}'.", name));
+                }
+                arguments.screenshot_key = keysym;
+            }
+            "--mesh" => {
+                arguments.mesh = Some(
+                    args.next()
+                        .map(PathBuf::from)
+                        .unwrap_or_else(|| exit_with_usage("--mesh requires an OBJ file path.")),
+                );
+            }
+            "--render-sequence" => {
+                let out_dir = args.next().map(PathBuf::from).unwrap_or_else(|| {
+                    exit_with_usage("--render-sequence requires a directory and a frame count.")
+                });
+                let frames = args.next().unwrap_or_else(|| {
+                    exit_with_usage("--render-sequence requires a directory and a frame count.")
+                });
+                let frames = frames
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&frames| frames > 0)
+                    .unwrap_or_else(|| {
+                        exit_with_usage(&format!("Invalid frame count '{}'.", frames))
+                    });
+                arguments.render_sequence = Some((out_dir, frames));
+            }
+            "--render-scale" => {
+                let render_scale = args
+                    .next()
+                    .unwrap_or_else(|| exit_with_usage("--render-scale requires a value."));
+                arguments.render_scale = Some(
+                    render_scale
+                        .parse()
+                        .ok()
+                        .filter(|&render_scale: &f32| {
+                            render_scale > 0.0 && render_scale.is_finite()
+                        })
+                        .unwrap_or_else(|| {
+                            exit_with_usage(&format!("Invalid render scale '{}'.", render_scale))
+                        }),
+                );
+            }
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => exit_with_usage(&format!("Unknown argument '{}'.", arg)),
         }
-        writer.flush().unwrap();
     }
+
+    if arguments.watch_shaders && arguments.shader_files.is_none() {
+        exit_with_usage("--watch-shaders requires --shaders.");
+    }
+
+    if arguments.verify
+        && (arguments.render_once.is_none()
+            || arguments.shader_files.is_some()
+            || arguments.mesh.is_some())
+    {
+        exit_with_usage("--verify requires --render-once and the built-in shaders and mesh.");
+    }
+
+    return arguments;
+}
+
+fn parse_size(size: &str) -> Option<(u32, u32)> {
+    let (width, height) = size.split_once('x')?;
+    let size = (width.parse().ok()?, height.parse().ok()?);
+
+    if size.0 == 0 || size.1 == 0 {
+        return None;
+    }
+
+    return Some(size);
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2);
+}
+
+// The embedded shaders and triangle unless `shader_files` or `mesh` replace them.
+fn create_graphics_processor<'a>(
+    processor: &'a VulkanProcessor,
+    config: &RendererConfig,
+    shader_files: Option<ShaderFiles>,
+    watch_shaders: bool,
+    mesh: Option<(Vec<MyVertex>, Vec<u32>)>,
+) -> GraphicsProcessor<'a> {
+    return match (shader_files, mesh) {
+        (Some(shader_files), mesh) => {
+            let mut graphics_processor = GraphicsProcessor::with_shader_files(
+                processor,
+                config,
+                shader_files,
+                watch_shaders,
+            );
+            if let Some((vertices, indices)) = mesh {
+                graphics_processor.set_mesh(vertices, indices);
+            }
+            graphics_processor
+        }
+        (None, Some((vertices, indices))) => {
+            GraphicsProcessor::with_vertices(processor, config, vertices, indices)
+        }
+        (None, None) => GraphicsProcessor::from_config(processor, config),
+    };
+}
+
+// Renders a single frame straight to a PNG. Nothing on this path connects to Wayland, so it works
+// without a compositor, e.g. in CI.
+fn run_headless(
+    config: &RendererConfig,
+    shader_files: Option<ShaderFiles>,
+    mesh: Option<(Vec<MyVertex>, Vec<u32>)>,
+    output_path: &Path,
+    verify: bool,
+) {
+    let processor = VulkanProcessor::from_config(config);
+    let mut graphics_processor =
+        create_graphics_processor(&processor, config, shader_files, false, mesh);
+
+    graphics_processor.execute_to_png(output_path);
+
+    println!("Rendered to {}.", output_path.display());
+
+    if let Some(gpu_time) = graphics_processor.gpu_time_nanos() {
+        println!(
+            "Render pass took {:.3} ms on the GPU.",
+            gpu_time as f64 / 1e6
+        );
+    }
+
+    if verify {
+        verify_against_reference(config, &graphics_processor);
+    }
+}
+
+// Compares the frame with the CPU rasterizer's render of the default scene. Pixels on the
+// triangle's edges may legitimately differ between drivers, and more of them with MSAA or a
+// render scale, so up to one frame's perimeter of them is accepted.
+fn verify_against_reference(config: &RendererConfig, graphics_processor: &GraphicsProcessor) {
+    let positions: Vec<[f32; 2]> = GraphicsProcessor::triangle()
+        .iter()
+        .map(|vertex| vertex.position)
+        .collect();
+    let expected = rasterizer::rasterize(
+        config.size,
+        config.clear_color,
+        &positions,
+        &[0, 1, 2],
+        &[[0.0, 0.0]],
+        [1.0; 4],
+    );
+
+    let mismatched = rasterizer::count_mismatched_pixels(
+        &expected,
+        &graphics_processor.read_frame(),
+        REFERENCE_TOLERANCE,
+    );
+    let allowed = (config.size.0 + config.size.1) as usize * 2;
+
+    if mismatched > allowed {
+        println!(
+            "Verification failed: {} pixels differ from the reference, at most {} may.",
+            mismatched, allowed
+        );
+        process::exit(1);
+    }
+
+    println!(
+        "Verified against the reference rasterizer, {} edge pixels differ.",
+        mismatched
+    );
+}
+
+// Renders `frames` PNGs of the scene going once around a circle, e.g. to turn into a video.
+fn run_sequence(
+    config: &RendererConfig,
+    shader_files: Option<ShaderFiles>,
+    mesh: Option<(Vec<MyVertex>, Vec<u32>)>,
+    out_dir: &Path,
+    frames: usize,
+) {
+    let processor = VulkanProcessor::from_config(config);
+    let graphics_processor =
+        create_graphics_processor(&processor, config, shader_files, false, mesh);
+
+    let sequence_start = Instant::now();
+    graphics_processor.render_sequence_pipelined(frames, out_dir, |frame| {
+        let angle = frame as f32 / frames as f32 * std::f32::consts::TAU;
+        vec![MyInstance {
+            offset: [0.5 * angle.cos(), 0.5 * angle.sin()],
+        }]
+    });
+
+    println!(
+        "Rendered {} frames to {} in {:.3} s.",
+        frames,
+        out_dir.display(),
+        sequence_start.elapsed().as_secs_f64()
+    );
+}
+
+// Submits the recorded frame `frames` times, waiting on each, without converting or copying the
+// pixels anywhere, so the times are submission plus GPU work.
+fn run_benchmark(
+    config: &RendererConfig,
+    shader_files: Option<ShaderFiles>,
+    mesh: Option<(Vec<MyVertex>, Vec<u32>)>,
+    frames: u32,
+) {
+    let processor = VulkanProcessor::from_config(config);
+    let mut graphics_processor =
+        create_graphics_processor(&processor, config, shader_files, false, mesh);
+
+    // Untimed, so first-use costs in the driver don't end up in the maximum.
+    graphics_processor.execute_without_readback();
+
+    let mut frame_times = Vec::with_capacity(frames as usize);
+    let benchmark_start = Instant::now();
+
+    for _ in 0..frames {
+        let frame_start = Instant::now();
+        graphics_processor.execute_without_readback();
+        frame_times.push(frame_start.elapsed());
+    }
+
+    let total_time = benchmark_start.elapsed();
+    let min_time = frame_times.iter().min().unwrap();
+    let max_time = frame_times.iter().max().unwrap();
+    let average_time = total_time / frames;
+
+    println!(
+        "Rendered {} frames at {}x{} in {:.3} s, {:.1} fps.",
+        frames,
+        config.size.0,
+        config.size.1,
+        total_time.as_secs_f64(),
+        frames as f64 / total_time.as_secs_f64()
+    );
+    println!(
+        "Frame time: {:.3} ms min, {:.3} ms max, {:.3} ms average.",
+        min_time.as_secs_f64() * 1e3,
+        max_time.as_secs_f64() * 1e3,
+        average_time.as_secs_f64() * 1e3
+    );
+}
+
+fn run_windowed(
+    config: &RendererConfig,
+    shader_files: Option<ShaderFiles>,
+    mesh: Option<(Vec<MyVertex>, Vec<u32>)>,
+    watch_shaders: bool,
+    screenshot_key: Keysym,
+) {
+    let processor = VulkanProcessor::from_config(config);
+    let mut graphics_processor =
+        create_graphics_processor(&processor, config, shader_files, watch_shaders, mesh);
+
+    if config.vulkan_presentation {
+        run_presenting(&processor, graphics_processor, config, screenshot_key);
+        return;
+    }
+
+    let opaque = config.clear_color[3] >= 1.0;
+    let last_frame_stats = Cell::new(FrameStats::default());
+    let screenshot_requested = Cell::new(false);
+
+    let mut wayland_client = WaylandClient::new(config.size, |buffer_file, size, _frame| {
+        if size != graphics_processor.size {
+            graphics_processor.resize(size);
+        }
+
+        graphics_processor.reload_shaders_if_changed();
+
+        last_frame_stats.set(graphics_processor.execute(buffer_file));
+
+        // The frame just drawn is still in the readback buffer.
+        if screenshot_requested.take() {
+            save_screenshot(|path| graphics_processor.save_frame_png(path));
+        }
+
+        // The whole scene is re-rendered every frame.
+        Vec::new()
+    });
+    wayland_client.set_opaque(opaque);
+    wayland_client.set_max_fps(config.max_fps);
+    wayland_client.set_key_handler(|key_event| {
+        if key_event.pressed && key_event.keysym == screenshot_key {
+            screenshot_requested.set(true);
+        }
+    });
+
+    if let Err(error) = wayland_client.run() {
+        println!("Lost the connection to the compositor: {}", error);
+    }
+
+    println!("Last measured frame rate: {:.1} fps.", wayland_client.fps());
+
+    let stats = last_frame_stats.get();
+    println!(
+        "Last frame: {:.3} ms recording, {:.3} ms on the GPU, {:.3} ms readback.",
+        stats.cpu_record_ms, stats.gpu_execute_ms, stats.readback_ms
+    );
+}
+
+// Writes a screenshot named after the current time to the working directory.
+fn save_screenshot<F: FnOnce(&Path)>(save: F) {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let path = PathBuf::from(format!(
+        "screenshot_{}_{:03}.png",
+        timestamp.as_secs(),
+        timestamp.subsec_millis()
+    ));
+
+    save(&path);
+    println!("Saved a screenshot to {}.", path.display());
+}
+
+// Renders straight into the images of a swapchain on the window's surface, skipping the readback
+// and the copy into shm buffers.
+fn run_presenting(
+    processor: &VulkanProcessor,
+    mut graphics_processor: GraphicsProcessor,
+    config: &RendererConfig,
+    screenshot_key: Keysym,
+) {
+    let screenshot_requested = Cell::new(false);
+    let screenshot_requested = &screenshot_requested;
+
+    let mut wayland_client = WaylandClient::new_presenting(graphics_processor.size);
+    wayland_client.set_max_fps(config.max_fps);
+    wayland_client.set_key_handler(move |key_event| {
+        if key_event.pressed && key_event.keysym == screenshot_key {
+            screenshot_requested.set(true);
+        }
+    });
+
+    let (display, surface) = wayland_client.raw_handles();
+    // Safety: the handles stay valid while `wayland_client` lives, and the presenter is owned by
+    // its present handler, which the client drops before closing its connection.
+    let surface = unsafe { processor.create_wayland_surface(display, surface) };
+    let mut presenter = processor.create_presenter(
+        surface,
+        graphics_processor.size,
+        graphics_processor.format,
+        ColorSpace::SrgbNonLinear,
+        config.present_mode,
+    );
+
+    wayland_client.set_present_handler(move |size, _frame| {
+        if size != graphics_processor.size {
+            // The frames still in flight were recorded for the old size.
+            processor.wait_for_frames();
+            graphics_processor.resize(size);
+        }
+
+        graphics_processor.reload_shaders_if_changed();
+
+        // Swapchain images aren't read back, so the frame is rendered again into the readback
+        // buffer.
+        if screenshot_requested.take() {
+            save_screenshot(|path| graphics_processor.execute_to_png(path));
+        }
+
+        // Blocks while `FRAMES_IN_FLIGHT` frames are still queued.
+        let frame_index = processor.begin_frame();
+        presenter.present(processor, frame_index, size, |image| {
+            graphics_processor.record_to_image(image)
+        })
+    });
+
+    if let Err(error) = wayland_client.run() {
+        println!("Lost the connection to the compositor: {}", error);
+    }
+
+    // The frames in flight hold on to the swapchain, which has to go before the connection does.
+    processor.wait_for_frames();
+
+    println!("Last measured frame rate: {:.1} fps.", wayland_client.fps());
 }
 
 fn main() {
-    let processor = VulkanProcessor::new();
-    let graphics_processor = GraphicsProcessor::new(&processor, SIZE);
+    let arguments = parse_arguments();
 
-    let mut wayland_client =
-        WaylandClient::new(|buffer_file| graphics_processor.execute(buffer_file));
+    // Command line options override the file's settings.
+    let mut config = match arguments.config {
+        Some(ref path) => RendererConfig::load(path)
+            .unwrap_or_else(|error| panic!("Failed to load config {}: {}", path.display(), error)),
+        None => RendererConfig::default(),
+    };
+
+    if let Some(size) = arguments.size {
+        config.size = size;
+    }
+
+    if let Some(render_scale) = arguments.render_scale {
+        config.render_scale = render_scale;
+    }
+
+    if arguments.software_fallback {
+        config.software_fallback = true;
+    }
+
+    if arguments.vulkan_presentation {
+        config.vulkan_presentation = true;
+    }
+
+    if let Some(present_mode) = arguments.present_mode {
+        config.present_mode = present_mode;
+    }
+
+    if arguments.max_fps.is_some() {
+        config.max_fps = arguments.max_fps;
+    }
+
+    if arguments.low_power {
+        config.power_preference = PowerPreference::LowPower;
+    }
 
-    wayland_client.run();
+    // Loaded before creating any Vulkan objects, so a bad file fails fast.
+    let mesh = arguments.mesh.map(|path| {
+        obj::load_obj(&path)
+            .unwrap_or_else(|error| panic!("Failed to load {}: {}", path.display(), error))
+    });
+
+    if let Some(frames) = arguments.benchmark_frames {
+        if arguments.render_once.is_some()
+            || arguments.render_sequence.is_some()
+            || arguments.watch_shaders
+            || arguments.vulkan_presentation
+        {
+            exit_with_usage(
+                "--benchmark is not supported together with --render-once, --render-sequence, \
+                 --watch-shaders or --vulkan-present.",
+            );
+        }
+
+        run_benchmark(&config, arguments.shader_files, mesh, frames);
+        return;
+    }
+
+    if let Some((out_dir, frames)) = arguments.render_sequence {
+        if arguments.render_once.is_some()
+            || arguments.watch_shaders
+            || arguments.vulkan_presentation
+        {
+            exit_with_usage(
+                "--render-sequence is not supported together with --render-once, \
+                 --watch-shaders or --vulkan-present.",
+            );
+        }
+
+        run_sequence(&config, arguments.shader_files, mesh, &out_dir, frames);
+        return;
+    }
+
+    if let Some(output_path) = arguments.render_once {
+        if arguments.watch_shaders {
+            exit_with_usage("--watch-shaders is not supported together with --render-once.");
+        }
+        if arguments.vulkan_presentation {
+            exit_with_usage("--vulkan-present is not supported together with --render-once.");
+        }
+
+        run_headless(
+            &config,
+            arguments.shader_files,
+            mesh,
+            &output_path,
+            arguments.verify,
+        );
+        return;
+    }
+
+    run_windowed(
+        &config,
+        arguments.shader_files,
+        mesh,
+        arguments.watch_shaders,
+        arguments.screenshot_key,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Seek, SeekFrom};
+
+    use super::*;
+
+    // Non-square, so swapping rows and columns shows.
+    const SIZE: (u32, u32) = (64, 32);
+
+    // Reads a normal that `MyVertex` doesn't have.
+    mod normal_vs {
+        vulkano_shaders::shader! {
+            ty: "vertex",
+            src: r"
+                #version 460
+
+                layout(location = 0) in vec2 position;
+                layout(location = 1) in vec2 offset;
+                layout(location = 2) in vec3 normal;
+
+                void main() {
+                    gl_Position = vec4(position + offset + normal.xy, 0.0, 1.0);
+                }
+            ",
+        }
+    }
+
+    #[test]
+    fn texel_offsets_are_row_major() {
+        assert_eq!(texel_offset(SIZE, 0, 0, 4), 0);
+        assert_eq!(texel_offset(SIZE, 1, 0, 4), 4);
+        assert_eq!(texel_offset(SIZE, 0, 1, 4), 64 * 4);
+        assert_eq!(texel_offset(SIZE, 63, 31, 4), (64 * 32 - 1) * 4);
+        assert_eq!(texel_offset(SIZE, 5, 3, 8), (3 * 64 + 5) * 8);
+    }
+
+    #[test]
+    fn rasterized_pixels_are_found_at_their_texel_offsets() {
+        // The left quarter and top half in normalized device coordinates, so 16x16 pixels.
+        let positions = [[-1.0, -1.0], [-0.5, -1.0], [-0.5, 0.0], [-1.0, 0.0]];
+        let pixels = rasterizer::rasterize(
+            SIZE,
+            [0.0, 0.0, 0.0, 1.0],
+            &positions,
+            &[0, 1, 2, 0, 2, 3],
+            &[[0.0, 0.0]],
+            [1.0; 4],
+        );
+        assert_eq!(pixels.len(), texel_offset(SIZE, 0, SIZE.1, 4));
+
+        for y in 0..SIZE.1 {
+            for x in 0..SIZE.0 {
+                let offset = texel_offset(SIZE, x, y, 4);
+                let expected = if x < 16 && y < 16 {
+                    [255; 4]
+                } else {
+                    [0, 0, 0, 255]
+                };
+
+                assert_eq!(
+                    pixels[offset..offset + 4],
+                    expected,
+                    "Pixel ({}, {}).",
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn execute_to_png_writes_the_frame_as_an_rgba_png() {
+        let Some(processor) = vulkan::testing::test_processor() else {
+            return;
+        };
+
+        let config = RendererConfig {
+            size: SIZE,
+            ..Default::default()
+        };
+        let mut graphics_processor = GraphicsProcessor::from_config(&processor, &config);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("frame.png");
+        graphics_processor.execute_to_png(&path);
+
+        let mut reader = png::Decoder::new(File::open(&path).unwrap())
+            .read_info()
+            .unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), SIZE);
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+
+        // The corner is the gray clear color, the center is inside the white triangle.
+        let pixel = |x, y| {
+            let offset = texel_offset(SIZE, x, y, 4);
+            <[u8; 4]>::try_from(&pixels[offset..offset + 4]).unwrap()
+        };
+        assert_eq!(pixel(0, 0), [51, 51, 51, 255]);
+        assert_eq!(pixel(SIZE.0 / 2, SIZE.1 / 2), [255; 4]);
+    }
+
+    #[test]
+    fn indexed_quad_covers_all_four_corners() {
+        let Some(processor) = vulkan::testing::test_processor() else {
+            return;
+        };
+
+        // The middle half of the frame, so pixels 16 to 47 across and 8 to 23 down.
+        let vertices = [[-0.5, -0.5], [0.5, -0.5], [0.5, 0.5], [-0.5, 0.5]]
+            .map(|position| MyVertex { position })
+            .to_vec();
+        let config = RendererConfig {
+            size: SIZE,
+            ..Default::default()
+        };
+        let mut graphics_processor =
+            GraphicsProcessor::with_vertices(&processor, &config, vertices, vec![0, 1, 2, 0, 2, 3]);
+        graphics_processor.execute_without_readback();
+
+        // Each triangle covers two of the quad's corners, and neither reaches the frame's corners.
+        for (x, y) in [(17, 9), (46, 9), (46, 22), (17, 22)] {
+            assert_eq!(
+                graphics_processor.read_pixel(x, y),
+                [255; 4],
+                "({}, {})",
+                x,
+                y
+            );
+        }
+        for (x, y) in [(0, 0), (63, 0), (63, 31), (0, 31)] {
+            assert_eq!(
+                graphics_processor.read_pixel(x, y),
+                [51, 51, 51, 255],
+                "({}, {})",
+                x,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn clear_color_with_zero_alpha_reads_back_transparent() {
+        let Some(processor) = vulkan::testing::test_processor() else {
+            return;
+        };
+
+        let config = RendererConfig {
+            size: SIZE,
+            ..Default::default()
+        };
+        let mut graphics_processor = GraphicsProcessor::from_config(&processor, &config);
+        // Premultiplied, the color goes with the alpha.
+        graphics_processor.set_clear_color([1.0, 0.5, 0.0, 0.0]);
+
+        graphics_processor.execute_without_readback();
+        assert_eq!(graphics_processor.read_pixel(0, 0), [0; 4]);
+        assert_eq!(
+            graphics_processor.read_pixel(SIZE.0 / 2, SIZE.1 / 2),
+            [255; 4]
+        );
+
+        // And so it reaches the Wayland buffer.
+        let mut buffer_file = tempfile::tempfile().unwrap();
+        buffer_file
+            .set_len(texel_offset(SIZE, 0, SIZE.1, 4) as u64)
+            .unwrap();
+        graphics_processor.execute(&mut buffer_file);
+        let mut pixels = Vec::new();
+        buffer_file.seek(SeekFrom::Start(0)).unwrap();
+        buffer_file.read_to_end(&mut pixels).unwrap();
+        assert_eq!(pixels.len(), texel_offset(SIZE, 0, SIZE.1, 4));
+        assert_eq!(pixels[..4], [0; 4]);
+    }
+
+    #[test]
+    fn msaa_leaves_intermediate_values_along_the_edges() {
+        let Some(processor) = vulkan::testing::test_processor() else {
+            return;
+        };
+        if !processor.supports_sample_count(SampleCount::Sample4) {
+            println!("Skipping, 4 samples are not supported.");
+            return;
+        }
+
+        // Between the gray clear color and the white triangle, so partly covered.
+        let count_edge_pixels = |msaa_samples| {
+            let config = RendererConfig {
+                size: (128, 128),
+                msaa_samples,
+                ..Default::default()
+            };
+            let mut graphics_processor = GraphicsProcessor::from_config(&processor, &config);
+            graphics_processor.execute_without_readback();
+
+            graphics_processor
+                .read_frame()
+                .chunks_exact(4)
+                .filter(|rgba| rgba[0] > 51 && rgba[0] < 255)
+                .count()
+        };
+
+        assert_eq!(count_edge_pixels(1), 0);
+        assert!(count_edge_pixels(4) > 0);
+    }
+
+    #[test]
+    fn mismatched_vertex_inputs_fail_to_build_a_pipeline() {
+        let Some(processor) = vulkan::testing::test_processor() else {
+            return;
+        };
+
+        let graphics_processor = GraphicsProcessor::from_config(&processor, &Default::default());
+        let shader_modules = processor.load_shader_modules(vec![normal_vs::load, fs::load]);
+
+        let result = GraphicsProcessor::create_pipeline(
+            &processor,
+            &graphics_processor.render_pass,
+            &shader_modules,
+        );
+        assert!(
+            matches!(result, Err(PipelineError::VertexInput(_))),
+            "{:?}",
+            result.map(|_| ())
+        );
+    }
+
+    #[test]
+    fn every_instance_is_drawn_at_its_offset() {
+        let Some(processor) = vulkan::testing::test_processor() else {
+            return;
+        };
+
+        let size = (200, 200);
+        let config = RendererConfig {
+            size,
+            ..Default::default()
+        };
+        let mut graphics_processor = GraphicsProcessor::from_config(&processor, &config);
+
+        // A 10x10 grid, one triangle per 20x20 pixel cell.
+        let offsets: Vec<[f32; 2]> = (0..100)
+            .map(|i| [-0.9 + 0.2 * (i % 10) as f32, -0.9 + 0.2 * (i / 10) as f32])
+            .collect();
+        graphics_processor.set_instances(
+            offsets
+                .iter()
+                .map(|&offset| MyInstance { offset })
+                .collect(),
+        );
+        graphics_processor.execute_without_readback();
+
+        // The triangle's centroid is a little below its offset.
+        for offset in offsets {
+            let [x, y] = [offset[0], offset[1] + 0.02]
+                .map(|coordinate| ((coordinate + 1.0) / 2.0 * size.0 as f32) as u32);
+            assert_eq!(
+                graphics_processor.read_pixel(x, y),
+                [255; 4],
+                "The instance at {:?} is missing.",
+                offset
+            );
+        }
+        assert_eq!(graphics_processor.read_pixel(0, 0), [51, 51, 51, 255]);
+    }
+
+    #[test]
+    fn half_render_scale_is_scaled_back_up_to_the_output_size() {
+        let Some(processor) = vulkan::testing::test_processor() else {
+            return;
+        };
+
+        // Big enough that the triangle still covers the center at half the resolution.
+        let size = (200, 200);
+        let config = RendererConfig {
+            size,
+            ..Default::default()
+        };
+        let mut graphics_processor = GraphicsProcessor::from_config(&processor, &config);
+        graphics_processor.set_render_scale(0.5);
+        assert_eq!(graphics_processor.render_size(), (100, 100));
+
+        graphics_processor.execute_without_readback();
+        assert_eq!(
+            graphics_processor.read_frame().len(),
+            texel_offset(size, 0, size.1, 4)
+        );
+        assert_ne!(
+            graphics_processor.read_pixel(size.0 / 2, size.1 / 2),
+            [51, 51, 51, 255]
+        );
+        assert_eq!(graphics_processor.read_pixel(0, 0), [51, 51, 51, 255]);
+    }
+
+    #[test]
+    fn frame_stats_are_filled_in_after_a_frame() {
+        let Some(processor) = vulkan::testing::test_processor() else {
+            return;
+        };
+
+        let config = RendererConfig {
+            size: SIZE,
+            ..Default::default()
+        };
+        let mut graphics_processor = GraphicsProcessor::from_config(&processor, &config);
+        let mut buffer_file = tempfile::tempfile().unwrap();
+        buffer_file
+            .set_len(texel_offset(SIZE, 0, SIZE.1, 4) as u64)
+            .unwrap();
+
+        // The first frame includes recording the command buffer.
+        let stats = graphics_processor.execute(&mut buffer_file);
+        assert!(stats.cpu_record_ms > 0.0, "{:?}", stats);
+        assert!(stats.gpu_execute_ms > 0.0, "{:?}", stats);
+        assert!(stats.readback_ms > 0.0, "{:?}", stats);
+
+        // The next one reuses it.
+        let stats = graphics_processor.execute(&mut buffer_file);
+        assert_eq!(stats.cpu_record_ms, 0.0);
+        assert!(stats.gpu_execute_ms > 0.0, "{:?}", stats);
+    }
+
+    #[test]
+    fn config_keeps_the_settings_it_was_created_with() {
+        let Some(processor) = vulkan::testing::test_processor() else {
+            return;
+        };
+
+        let config = RendererConfig {
+            size: (32, 16),
+            power_preference: PowerPreference::LowPower,
+            vulkan_presentation: true,
+            present_mode: PresentMode::Mailbox,
+            max_fps: Some(30),
+            ..Default::default()
+        };
+        let mut graphics_processor = GraphicsProcessor::from_config(&processor, &config);
+        assert_eq!(graphics_processor.config(), config);
+
+        graphics_processor.resize((16, 8));
+        graphics_processor.set_clear_color([0.0; 4]);
+        assert_eq!(
+            graphics_processor.config(),
+            RendererConfig {
+                size: (16, 8),
+                clear_color: [0.0; 4],
+                ..config
+            }
+        );
+    }
+
+    #[test]
+    fn float_frames_keep_values_8_bits_would_round_or_clamp() {
+        let Some(processor) = vulkan::testing::test_processor() else {
+            return;
+        };
+
+        // 0.3 and 0.7 fall between 8-bit steps, and 2.5 is past what UNORM can hold.
+        let config = RendererConfig {
+            size: (8, 8),
+            format: Format::R16G16B16A16_SFLOAT,
+            clear_color: [2.5, 0.3, 0.7, 1.0],
+            ..Default::default()
+        };
+        let graphics_processor = GraphicsProcessor::from_config(&processor, &config);
+        processor.execute_then_wait(graphics_processor.command_buffer.clone());
+
+        // The top left pixel is outside the triangle, so holds the clear color.
+        let pixels = processor.download_buffer(&graphics_processor.data_buffer);
+        let channels: Vec<f32> = pixels[..8]
+            .chunks_exact(2)
+            .map(|bytes| swizzle::f16_to_f32(u16::from_le_bytes([bytes[0], bytes[1]])))
+            .collect();
+
+        // Half floats are within 2^-11 of these, 8 bits only within 2^-9 of the first two.
+        for (channel, expected) in channels.into_iter().zip(config.clear_color) {
+            assert!(
+                (channel - expected).abs() < 1.0 / 2048.0,
+                "{} read back as {}.",
+                expected,
+                channel
+            );
+        }
+    }
 }
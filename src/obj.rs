@@ -0,0 +1,181 @@
+use std::{fs, io, path::Path};
+
+use crate::MyVertex;
+
+// Reads the vertex positions and faces of a Wavefront OBJ file for `GraphicsProcessor::set_mesh`.
+// The scene is 2D, so z is dropped and positions are taken to be in normalized device
+// coordinates already. Faces with more than three vertices are split into a fan of triangles,
+// and texture coordinates, normals, groups and materials are ignored.
+pub fn load_obj(path: impl AsRef<Path>) -> io::Result<(Vec<MyVertex>, Vec<u32>)> {
+    let contents = fs::read_to_string(path)?;
+
+    return parse_obj(&contents)
+        .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message));
+}
+
+fn parse_obj(contents: &str) -> Result<(Vec<MyVertex>, Vec<u32>), String> {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+
+    for (line_index, line) in contents.lines().enumerate() {
+        let line_number = line_index + 1;
+        let line = line.split('#').next().unwrap();
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            Some("v") => {
+                let coordinates: Vec<f32> = tokens
+                    .map(|token| token.parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| format!("Line {}: invalid vertex coordinate.", line_number))?;
+                if coordinates.len() < 3 {
+                    return Err(format!("Line {}: a vertex needs x, y and z.", line_number));
+                }
+
+                vertices.push(MyVertex {
+                    position: [coordinates[0], coordinates[1]],
+                });
+            }
+            Some("f") => {
+                let face: Vec<u32> = tokens
+                    .map(|token| resolve_index(token, vertices.len()))
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| format!("Line {}: invalid face index.", line_number))?;
+                if face.len() < 3 {
+                    return Err(format!(
+                        "Line {}: a face needs at least three vertices.",
+                        line_number
+                    ));
+                }
+
+                for i in 1..face.len() - 1 {
+                    indices.extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    if indices.is_empty() {
+        return Err("The file has no faces.".to_owned());
+    }
+
+    return Ok((vertices, indices));
+}
+
+// Face vertices are `v`, `v/vt`, `v//vn` or `v/vt/vn`, with 1-based positions, or negative ones
+// counting back from the last vertex read so far.
+fn resolve_index(token: &str, vertex_count: usize) -> Option<u32> {
+    let index: i64 = token.split('/').next()?.parse().ok()?;
+
+    let index = if index > 0 {
+        index - 1
+    } else if index < 0 {
+        vertex_count as i64 + index
+    } else {
+        return None;
+    };
+
+    if index < 0 || index >= vertex_count as i64 {
+        return None;
+    }
+
+    return Some(index as u32);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(vertices: &[MyVertex]) -> Vec<[f32; 2]> {
+        return vertices.iter().map(|vertex| vertex.position).collect();
+    }
+
+    #[test]
+    fn cube_has_eight_vertices_and_twelve_triangles() {
+        let cube = "\
+# A unit cube with quad faces.
+o Cube
+v -1.0 -1.0 -1.0
+v  1.0 -1.0 -1.0
+v  1.0  1.0 -1.0
+v -1.0  1.0 -1.0
+v -1.0 -1.0  1.0
+v  1.0 -1.0  1.0
+v  1.0  1.0  1.0
+v -1.0  1.0  1.0
+vn 0.0 0.0 1.0
+usemtl Material
+s off
+f 1 2 3 4
+f 5 8 7 6
+f 1 5 6 2
+f 2 6 7 3
+f 3 7 8 4
+f 5 1 4 8
+";
+        let (vertices, indices) = parse_obj(cube).unwrap();
+
+        assert_eq!(vertices.len(), 8);
+        assert_eq!(indices.len(), 36);
+        assert!(indices.iter().all(|&index| index < 8));
+        assert_eq!(positions(&vertices)[6], [1.0, 1.0]);
+    }
+
+    #[test]
+    fn polygons_are_split_into_a_fan() {
+        let pentagon = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv -1 1 0\nf 1 2 3 4 5\n";
+        let (_, indices) = parse_obj(pentagon).unwrap();
+
+        assert_eq!(indices, [0, 1, 2, 0, 2, 3, 0, 3, 4]);
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_vertex() {
+        let contents = "v 0 0 0\nv 1 0 0\nv 1 1 0\nf -3 -2 -1\nv 0 1 0\nf -4 -2 -1\n";
+        let (vertices, indices) = parse_obj(contents).unwrap();
+
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(indices, [0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn texture_and_normal_indices_are_ignored() {
+        let contents = "\
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vn 0 0 1
+f 1/1 2/1 3/1
+f 1//1 3//1 4//1
+f 4/1/1 3/1/1 2/1/1
+";
+        let (_, indices) = parse_obj(contents).unwrap();
+
+        assert_eq!(indices, [0, 1, 2, 0, 2, 3, 3, 2, 1]);
+    }
+
+    #[test]
+    fn out_of_range_indices_are_rejected() {
+        let triangle = "v 0 0 0\nv 1 0 0\nv 1 1 0\n";
+
+        for face in ["f 1 2 4", "f 0 1 2", "f -4 1 2", "f 1 2 x", "f 1 2"] {
+            let error = parse_obj(&format!("{}{}\n", triangle, face)).err().unwrap();
+            assert!(error.starts_with("Line 4:"), "{}: {}", face, error);
+        }
+
+        // Faces may only refer to vertices read before them.
+        assert!(parse_obj("v 0 0 0\nv 1 0 0\nf 1 2 3\nv 1 1 0\n").is_err());
+    }
+
+    #[test]
+    fn files_without_faces_are_rejected() {
+        assert_eq!(
+            parse_obj("v 0 0 0\nv 1 0 0\nv 1 1 0\n").err().unwrap(),
+            "The file has no faces."
+        );
+        assert!(parse_obj("v 0 0\nf 1 1 1\n").is_err());
+    }
+}
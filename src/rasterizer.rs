@@ -0,0 +1,163 @@
+// A CPU reference for what the GPU draws with the built-in shaders: flat triangles of one color
+// over a cleared background. It samples at pixel centers like the GPU does, so apart from pixels
+// on an edge, where drivers may round differently, the two should match exactly.
+
+// Returns RGBA bytes for a `size` frame. `clear_color` is straight alpha, premultiplied like the
+// framebuffer clear. Positions are in normalized device coordinates, and every triangle of
+// `indices` is drawn once per offset, as the instanced draw does.
+pub fn rasterize(
+    size: (u32, u32),
+    clear_color: [f32; 4],
+    positions: &[[f32; 2]],
+    indices: &[u32],
+    offsets: &[[f32; 2]],
+    fill_color: [f32; 4],
+) -> Vec<u8> {
+    let [red, green, blue, alpha] = clear_color;
+    let clear = to_unorm([red * alpha, green * alpha, blue * alpha, alpha]);
+    let fill = to_unorm(fill_color);
+
+    let mut pixels: Vec<u8> = (0..size.0 * size.1).flat_map(|_| clear).collect();
+
+    let to_pixel = |position: [f32; 2], offset: [f32; 2]| {
+        [
+            (position[0] + offset[0] + 1.0) * 0.5 * size.0 as f32,
+            (position[1] + offset[1] + 1.0) * 0.5 * size.1 as f32,
+        ]
+    };
+
+    for &offset in offsets {
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| to_pixel(positions[triangle[i] as usize], offset));
+            fill_triangle(&mut pixels, size, [a, b, c], fill);
+        }
+    }
+
+    return pixels;
+}
+
+// Pixels differing by more than `tolerance` in any channel.
+pub fn count_mismatched_pixels(expected: &[u8], actual: &[u8], tolerance: u8) -> usize {
+    assert!(
+        expected.len() == actual.len(),
+        "Can't compare frames of {} and {} bytes.",
+        expected.len(),
+        actual.len()
+    );
+
+    expected
+        .chunks_exact(4)
+        .zip(actual.chunks_exact(4))
+        .filter(|(expected, actual)| {
+            expected
+                .iter()
+                .zip(actual.iter())
+                .any(|(expected, actual)| expected.abs_diff(*actual) > tolerance)
+        })
+        .count()
+}
+
+fn fill_triangle(pixels: &mut [u8], size: (u32, u32), [a, b, c]: [[f32; 2]; 3], fill: [u8; 4]) {
+    let area = edge(a, b, c);
+    if area == 0.0 {
+        return;
+    }
+
+    let min_x = a[0].min(b[0]).min(c[0]).floor().max(0.0) as u32;
+    let min_y = a[1].min(b[1]).min(c[1]).floor().max(0.0) as u32;
+    let max_x = (a[0].max(b[0]).max(c[0]).ceil() as u32).min(size.0);
+    let max_y = (a[1].max(b[1]).max(c[1]).ceil() as u32).min(size.1);
+
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let center = [x as f32 + 0.5, y as f32 + 0.5];
+
+            // Both windings are drawn, as the pipeline doesn't cull.
+            let weights = [edge(b, c, center), edge(c, a, center), edge(a, b, center)];
+            let inside = weights.iter().all(|weight| weight * area >= 0.0);
+
+            if inside {
+                let index = crate::texel_offset(size, x, y, 4);
+                pixels[index..index + 4].copy_from_slice(&fill);
+            }
+        }
+    }
+}
+
+// Twice the signed area of the triangle (a, b, p).
+fn edge(a: [f32; 2], b: [f32; 2], p: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+fn to_unorm(color: [f32; 4]) -> [u8; 4] {
+    color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+    const WHITE: [f32; 4] = [1.0; 4];
+
+    fn count_color(pixels: &[u8], color: [u8; 4]) -> usize {
+        pixels
+            .chunks_exact(4)
+            .filter(|&pixel| pixel == color)
+            .count()
+    }
+
+    #[test]
+    fn full_screen_quad_fills_every_pixel() {
+        let positions = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]];
+        let pixels = rasterize(
+            (16, 8),
+            BLACK,
+            &positions,
+            &[0, 1, 2, 0, 2, 3],
+            &[[0.0, 0.0]],
+            WHITE,
+        );
+
+        assert_eq!(pixels.len(), 16 * 8 * 4);
+        assert_eq!(count_color(&pixels, [255; 4]), 16 * 8);
+    }
+
+    #[test]
+    fn degenerate_triangle_draws_nothing() {
+        let positions = [[-1.0, -1.0], [0.0, 0.0], [1.0, 1.0]];
+        let pixels = rasterize((8, 8), BLACK, &positions, &[0, 1, 2], &[[0.0, 0.0]], WHITE);
+
+        assert_eq!(count_color(&pixels, [0, 0, 0, 255]), 8 * 8);
+    }
+
+    #[test]
+    fn both_windings_are_filled() {
+        let positions = [[-1.0, -1.0], [1.0, -1.0], [-1.0, 1.0]];
+        let clockwise = rasterize((8, 8), BLACK, &positions, &[0, 1, 2], &[[0.0, 0.0]], WHITE);
+        let counter_clockwise =
+            rasterize((8, 8), BLACK, &positions, &[0, 2, 1], &[[0.0, 0.0]], WHITE);
+
+        assert_eq!(clockwise, counter_clockwise);
+        // The half below the diagonal, plus the pixels whose centers lie on it.
+        assert_eq!(count_color(&clockwise, [255; 4]), 8 * 9 / 2);
+    }
+
+    #[test]
+    fn clear_color_is_premultiplied() {
+        let pixels = rasterize((2, 2), [1.0, 0.5, 0.0, 0.5], &[], &[], &[[0.0, 0.0]], WHITE);
+
+        assert_eq!(count_color(&pixels, [128, 64, 0, 128]), 4);
+    }
+
+    #[test]
+    fn mismatches_within_tolerance_are_ignored() {
+        let expected = [10, 20, 30, 255, 10, 20, 30, 255, 10, 20, 30, 255];
+        let actual = [11, 20, 30, 255, 10, 22, 30, 255, 10, 20, 30, 250];
+
+        assert_eq!(count_mismatched_pixels(&expected, &actual, 0), 3);
+        assert_eq!(count_mismatched_pixels(&expected, &actual, 1), 2);
+        assert_eq!(count_mismatched_pixels(&expected, &actual, 2), 1);
+        assert_eq!(count_mismatched_pixels(&expected, &actual, 5), 0);
+    }
+}
@@ -0,0 +1,57 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver},
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+pub struct ShaderWatcher {
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    paths: HashSet<PathBuf>,
+}
+
+impl ShaderWatcher {
+    // The parent directories are watched rather than the files themselves, since editors and
+    // compilers often replace a file instead of writing to it, which would end a watch on the file.
+    pub fn new(paths: &[&Path]) -> notify::Result<Self> {
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+
+        let mut watched_paths = HashSet::new();
+        for path in paths {
+            let path = path.canonicalize()?;
+            let directory = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+
+            watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+            watched_paths.insert(path);
+        }
+
+        return Ok(ShaderWatcher {
+            _watcher: watcher,
+            events,
+            paths: watched_paths,
+        });
+    }
+
+    // Drains every pending event, so a burst of writes to the same file counts as one change.
+    pub fn poll_changed(&self) -> bool {
+        let mut changed = false;
+
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                Ok(event) => {
+                    if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                        && event.paths.iter().any(|path| self.paths.contains(path))
+                    {
+                        changed = true;
+                    }
+                }
+                Err(error) => println!("Shader watch error: {}", error),
+            }
+        }
+
+        return changed;
+    }
+}
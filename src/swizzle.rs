@@ -0,0 +1,153 @@
+use vulkano::format::Format;
+
+// Wayland's `Argb8888` is a little-endian 32-bit value, so its bytes are in B, G, R, A order.
+pub fn swizzle_to_wayland(format: Format, pixels: &mut [u8]) {
+    match format {
+        Format::B8G8R8A8_UNORM | Format::B8G8R8A8_SRGB => (),
+        Format::R8G8B8A8_UNORM | Format::R8G8B8A8_SRGB => swap_red_blue(pixels),
+        _ => panic!("Can't convert {:?} pixels to Argb8888.", format),
+    }
+}
+
+// PNG stores 8-bit RGBA in R, G, B, A byte order.
+pub fn swizzle_to_rgba(format: Format, pixels: &mut [u8]) {
+    match format {
+        Format::R8G8B8A8_UNORM | Format::R8G8B8A8_SRGB => (),
+        Format::B8G8R8A8_UNORM | Format::B8G8R8A8_SRGB => swap_red_blue(pixels),
+        _ => panic!("Can't convert {:?} pixels to RGBA.", format),
+    }
+}
+
+// Whether frames in `format` can be read back, by the functions above for the 8-bit formats and
+// by `to_rgba8` for the float ones.
+pub fn is_readable(format: Format) -> bool {
+    is_8_bit(format)
+        || matches!(
+            format,
+            Format::R16G16B16A16_SFLOAT | Format::R32G32B32A32_SFLOAT
+        )
+}
+
+pub fn is_8_bit(format: Format) -> bool {
+    matches!(
+        format,
+        Format::B8G8R8A8_UNORM
+            | Format::B8G8R8A8_SRGB
+            | Format::R8G8B8A8_UNORM
+            | Format::R8G8B8A8_SRGB
+    )
+}
+
+// 8-bit RGBA for PNGs and the Wayland buffer. Float channels are clamped to [0, 1], so anything
+// brighter than white is lost, and otherwise taken to be encoded already like the UNORM formats.
+pub fn to_rgba8(format: Format, mut pixels: Vec<u8>) -> Vec<u8> {
+    let quantize = |channel: f32| (channel.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+    match format {
+        Format::R16G16B16A16_SFLOAT => pixels
+            .chunks_exact(2)
+            .map(|bytes| quantize(f16_to_f32(u16::from_le_bytes([bytes[0], bytes[1]]))))
+            .collect(),
+        Format::R32G32B32A32_SFLOAT => pixels
+            .chunks_exact(4)
+            .map(|bytes| quantize(f32::from_le_bytes(bytes.try_into().unwrap())))
+            .collect(),
+        _ => {
+            swizzle_to_rgba(format, &mut pixels);
+            pixels
+        }
+    }
+}
+
+// NaN comes out as 0 once quantized.
+pub fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 == 0 { 1.0 } else { -1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+fn swap_red_blue(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two pixels, so a swap that only touched the first one would show.
+    const RGBA_PIXELS: [u8; 8] = [10, 20, 30, 40, 50, 60, 70, 80];
+    const BGRA_PIXELS: [u8; 8] = [30, 20, 10, 40, 70, 60, 50, 80];
+
+    #[test]
+    fn rgba_source_swaps_red_and_blue_for_wayland() {
+        let mut pixels = RGBA_PIXELS;
+        swizzle_to_wayland(Format::R8G8B8A8_UNORM, &mut pixels);
+        assert_eq!(pixels, BGRA_PIXELS);
+    }
+
+    #[test]
+    fn bgra_source_is_unchanged_for_wayland() {
+        let mut pixels = BGRA_PIXELS;
+        swizzle_to_wayland(Format::B8G8R8A8_UNORM, &mut pixels);
+        assert_eq!(pixels, BGRA_PIXELS);
+    }
+
+    #[test]
+    fn bgra_source_swaps_red_and_blue_for_rgba() {
+        let mut pixels = BGRA_PIXELS;
+        swizzle_to_rgba(Format::B8G8R8A8_SRGB, &mut pixels);
+        assert_eq!(pixels, RGBA_PIXELS);
+
+        assert_eq!(
+            to_rgba8(Format::R8G8B8A8_UNORM, RGBA_PIXELS.to_vec()),
+            RGBA_PIXELS
+        );
+    }
+
+    #[test]
+    fn f16_pixels_are_clamped_and_quantized() {
+        // 0.0, 0.5, 1.0 and 2.0, then -1.0, NaN, infinity and the smallest subnormal.
+        let halves: [u16; 8] = [
+            0x0000, 0x3800, 0x3c00, 0x4000, 0xbc00, 0x7e00, 0x7c00, 0x0001,
+        ];
+        let bytes: Vec<u8> = halves.iter().flat_map(|half| half.to_le_bytes()).collect();
+
+        assert_eq!(
+            to_rgba8(Format::R16G16B16A16_SFLOAT, bytes),
+            [0, 128, 255, 255, 0, 0, 255, 0]
+        );
+    }
+
+    #[test]
+    fn f32_pixels_are_clamped_and_quantized() {
+        let floats = [0.0, 0.25, 1.0, 7.5, -0.5, f32::NAN, f32::INFINITY, 0.75];
+        let bytes: Vec<u8> = floats
+            .iter()
+            .flat_map(|float| float.to_le_bytes())
+            .collect();
+
+        assert_eq!(
+            to_rgba8(Format::R32G32B32A32_SFLOAT, bytes),
+            [0, 64, 255, 255, 0, 0, 255, 191]
+        );
+    }
+
+    #[test]
+    fn f16_conversion_is_exact_for_representable_values() {
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x3555), 0.333_251_95);
+        assert_eq!(f16_to_f32(0x0400), 2f32.powi(-14));
+        assert_eq!(f16_to_f32(0xfc00), f32::NEG_INFINITY);
+        assert!(f16_to_f32(0x7e00).is_nan());
+    }
+}
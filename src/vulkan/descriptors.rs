@@ -0,0 +1,127 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use vulkano::{
+    descriptor_set::{
+        allocator::{
+            DescriptorSetAlloc, DescriptorSetAllocator, StandardDescriptorSetAlloc,
+            StandardDescriptorSetAllocator,
+        },
+        layout::DescriptorSetLayout,
+        pool::{DescriptorPool, DescriptorPoolAlloc},
+        PersistentDescriptorSet,
+    },
+    device::{Device, DeviceOwned},
+    Handle, Validated, VulkanError, VulkanObject,
+};
+
+use super::DescriptorPoolStats;
+
+// Warns once a pool is this full, see `TrackingDescriptorSetAllocator::allocate`.
+const POOL_WARNING_PERCENT: usize = 90;
+
+// A set from the processor's allocator, which frees its slot in the pool when dropped.
+#[allow(dead_code)]
+pub type DescriptorSet = PersistentDescriptorSet<TrackedDescriptorSetAlloc>;
+
+// Live sets and capacity of each pool with any live sets in it, by pool handle.
+type PoolUsage = Mutex<HashMap<u64, (usize, usize)>>;
+
+// Counts the sets alive in each pool of a `StandardDescriptorSetAllocator`. That only allocates
+// from its newest pool for a layout and starts another one once it's full, so sets dropped from
+// older pools don't make room for new ones until the whole pool is free.
+pub(super) struct TrackingDescriptorSetAllocator {
+    inner: StandardDescriptorSetAllocator,
+    pool_usage: Arc<PoolUsage>,
+}
+
+impl TrackingDescriptorSetAllocator {
+    pub(super) fn new(inner: StandardDescriptorSetAllocator) -> Self {
+        TrackingDescriptorSetAllocator {
+            inner,
+            pool_usage: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub(super) fn stats(&self) -> DescriptorPoolStats {
+        let pool_usage = self.pool_usage.lock().unwrap();
+
+        DescriptorPoolStats {
+            allocated_sets: pool_usage.values().map(|&(live_sets, _)| live_sets).sum(),
+            pool_capacity: pool_usage.values().map(|&(_, capacity)| capacity).sum(),
+        }
+    }
+}
+
+unsafe impl DescriptorSetAllocator for TrackingDescriptorSetAllocator {
+    type Alloc = TrackedDescriptorSetAlloc;
+
+    fn allocate(
+        &self,
+        layout: &Arc<DescriptorSetLayout>,
+        variable_descriptor_count: u32,
+    ) -> Result<TrackedDescriptorSetAlloc, Validated<VulkanError>> {
+        let inner = self.inner.allocate(layout, variable_descriptor_count)?;
+        let pool_handle = inner.pool().handle().as_raw();
+        let max_sets = inner.pool().max_sets() as usize;
+
+        let mut pool_usage = self.pool_usage.lock().unwrap();
+        let (live_sets, capacity) = pool_usage.entry(pool_handle).or_insert((0, max_sets));
+        *live_sets += 1;
+
+        // Only when crossing the threshold, so a filling pool warns once.
+        if *live_sets * 100 >= *capacity * POOL_WARNING_PERCENT
+            && (*live_sets - 1) * 100 < *capacity * POOL_WARNING_PERCENT
+        {
+            println!(
+                "Warning: a descriptor pool is {}% full ({} of {} sets), the next allocations \
+                 will create another pool.",
+                POOL_WARNING_PERCENT, live_sets, capacity
+            );
+        }
+
+        return Ok(TrackedDescriptorSetAlloc {
+            inner,
+            pool_handle,
+            pool_usage: self.pool_usage.clone(),
+        });
+    }
+}
+
+unsafe impl DeviceOwned for TrackingDescriptorSetAllocator {
+    fn device(&self) -> &Arc<Device> {
+        self.inner.device()
+    }
+}
+
+pub struct TrackedDescriptorSetAlloc {
+    inner: StandardDescriptorSetAlloc,
+    pool_handle: u64,
+    pool_usage: Arc<PoolUsage>,
+}
+
+impl DescriptorSetAlloc for TrackedDescriptorSetAlloc {
+    fn inner(&self) -> &DescriptorPoolAlloc {
+        self.inner.inner()
+    }
+
+    fn pool(&self) -> &DescriptorPool {
+        self.inner.pool()
+    }
+}
+
+impl Drop for TrackedDescriptorSetAlloc {
+    fn drop(&mut self) {
+        let mut pool_usage = self.pool_usage.lock().unwrap();
+        let (live_sets, _) = pool_usage.get_mut(&self.pool_handle).unwrap();
+        *live_sets -= 1;
+
+        // The pool may be destroyed after this, and its handle reused by a new one.
+        if *live_sets == 0 {
+            pool_usage.remove(&self.pool_handle);
+        }
+    }
+}
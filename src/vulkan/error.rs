@@ -0,0 +1,322 @@
+use std::{error::Error, fmt, io};
+
+use vulkano::{
+    command_buffer::CommandBufferExecError,
+    device::{DeviceExtensions, Features},
+    format::Format,
+    image::{AllocateImageError, ImageUsage, SampleCount},
+    instance::InstanceExtensions,
+    pipeline::layout::IntoPipelineLayoutCreateInfoError,
+    Validated, ValidationError, VulkanError,
+};
+
+#[derive(Debug)]
+pub enum ProcessorCreationError {
+    // Not supported by the Vulkan loader or any of its layers.
+    UnsupportedInstanceExtensions {
+        missing_extensions: InstanceExtensions,
+    },
+    NoDevices,
+    NoSuitableDevice,
+    // Reported against the device that would otherwise have been chosen.
+    UnsupportedRequirements {
+        device_name: String,
+        missing_features: Features,
+        missing_extensions: DeviceExtensions,
+    },
+}
+
+impl fmt::Display for ProcessorCreationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessorCreationError::UnsupportedInstanceExtensions { missing_extensions } => write!(
+                f,
+                "The vulkan library doesn't support instance extensions {:?}.",
+                missing_extensions
+            ),
+            ProcessorCreationError::NoDevices => write!(f, "No vulkan devices are available."),
+            ProcessorCreationError::NoSuitableDevice => write!(
+                f,
+                "No vulkan device met the requirements of the device filter."
+            ),
+            ProcessorCreationError::UnsupportedRequirements {
+                device_name,
+                missing_features,
+                missing_extensions,
+            } => write!(
+                f,
+                "No vulkan device supports the requested features and extensions, \
+                {} is missing features {:?} and extensions {:?}.",
+                device_name, missing_features, missing_extensions
+            ),
+        }
+    }
+}
+
+impl Error for ProcessorCreationError {}
+
+#[derive(Debug)]
+#[allow(dead_code)]
+pub struct DescriptorSetIndexError {
+    pub set_index: usize,
+    pub set_count: usize,
+}
+
+impl fmt::Display for DescriptorSetIndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Descriptor set {} is out of range, the pipeline layout has {} set layouts.",
+            self.set_index, self.set_count
+        )
+    }
+}
+
+impl Error for DescriptorSetIndexError {}
+
+#[derive(Debug)]
+pub enum ShaderLoadError {
+    Io(io::Error),
+    // SPIR-V is a stream of 32-bit words, so the length in bytes must be a multiple of 4.
+    InvalidLength(usize),
+    Vulkan(Validated<VulkanError>),
+}
+
+impl fmt::Display for ShaderLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderLoadError::Io(error) => write!(f, "Failed to read SPIR-V file: {}", error),
+            ShaderLoadError::InvalidLength(length) => write!(
+                f,
+                "SPIR-V code is {} bytes long, which is not a multiple of 4.",
+                length
+            ),
+            ShaderLoadError::Vulkan(error) => {
+                write!(f, "Failed to create shader module: {}", error)
+            }
+        }
+    }
+}
+
+impl Error for ShaderLoadError {}
+
+impl From<io::Error> for ShaderLoadError {
+    fn from(error: io::Error) -> Self {
+        ShaderLoadError::Io(error)
+    }
+}
+
+// Shaders that can't be built into a pipeline, e.g. ones edited while they're being watched.
+#[derive(Debug)]
+pub enum PipelineError {
+    // No entry point of this name in the module.
+    MissingEntryPoint(String),
+    // The stages declare descriptor sets or push constants that don't fit together.
+    Layout(IntoPipelineLayoutCreateInfoError),
+    // The vertex type's attributes don't match the vertex shader's inputs.
+    VertexInput(Box<ValidationError>),
+    Vulkan(Validated<VulkanError>),
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PipelineError::MissingEntryPoint(name) => {
+                write!(f, "Shader module has no entry point named `{}`.", name)
+            }
+            PipelineError::Layout(error) => {
+                write!(f, "Failed to create pipeline layout: {}", error)
+            }
+            PipelineError::VertexInput(error) => write!(
+                f,
+                "Vertex type doesn't match the vertex shader inputs: {}",
+                error
+            ),
+            PipelineError::Vulkan(error) => write!(f, "Failed to create pipeline: {}", error),
+        }
+    }
+}
+
+impl Error for PipelineError {}
+
+impl From<Validated<VulkanError>> for PipelineError {
+    fn from(error: Validated<VulkanError>) -> Self {
+        PipelineError::Vulkan(error)
+    }
+}
+
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum ExecutionError {
+    Timeout,
+    // Usually caused by a shader that hangs or crashes the GPU. The device can't be used anymore.
+    DeviceLost,
+    // The command buffer couldn't be submitted, e.g. a one-time command buffer submitted again.
+    Submission(CommandBufferExecError),
+    Vulkan(Validated<VulkanError>),
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecutionError::Timeout => write!(f, "Timed out waiting for the GPU."),
+            ExecutionError::DeviceLost => write!(f, "The vulkan device was lost."),
+            ExecutionError::Submission(error) => {
+                write!(f, "Failed to submit command buffer: {}", error)
+            }
+            ExecutionError::Vulkan(error) => {
+                write!(f, "Failed to execute command buffer: {}", error)
+            }
+        }
+    }
+}
+
+impl Error for ExecutionError {}
+
+impl From<Validated<VulkanError>> for ExecutionError {
+    fn from(error: Validated<VulkanError>) -> Self {
+        match error {
+            Validated::Error(VulkanError::Timeout) => ExecutionError::Timeout,
+            Validated::Error(VulkanError::DeviceLost) => ExecutionError::DeviceLost,
+            error => ExecutionError::Vulkan(error),
+        }
+    }
+}
+
+impl From<CommandBufferExecError> for ExecutionError {
+    fn from(error: CommandBufferExecError) -> Self {
+        ExecutionError::Submission(error)
+    }
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    // Some dimension is zero or above the device's limit for the image type.
+    InvalidExtent {
+        extent: [u32; 3],
+        max_extent: [u32; 3],
+    },
+    TooManyArrayLayers {
+        array_layers: u32,
+        max_array_layers: u32,
+    },
+    // The format's optimal tiling features don't cover every requested usage.
+    UnsupportedUsage {
+        format: Format,
+        unsupported_usage: ImageUsage,
+    },
+    // Mipmaps are generated with linear blits, which the format must support.
+    #[allow(dead_code)]
+    UnsupportedMipmapGeneration(Format),
+    Vulkan(Validated<AllocateImageError>),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::InvalidExtent { extent, max_extent } => write!(
+                f,
+                "Image extent {:?} is outside the device's supported range of 1 to {:?}.",
+                extent, max_extent
+            ),
+            ImageError::TooManyArrayLayers {
+                array_layers,
+                max_array_layers,
+            } => write!(
+                f,
+                "Image has {} array layers, but the device supports at most {}.",
+                array_layers, max_array_layers
+            ),
+            ImageError::UnsupportedUsage {
+                format,
+                unsupported_usage,
+            } => write!(
+                f,
+                "Format {:?} doesn't support image usage {:?} on this device.",
+                format, unsupported_usage
+            ),
+            ImageError::UnsupportedMipmapGeneration(format) => write!(
+                f,
+                "Format {:?} doesn't support linearly filtered blits, so its mipmaps can't be \
+                generated on this device.",
+                format
+            ),
+            ImageError::Vulkan(error) => write!(f, "Failed to create image: {}", error),
+        }
+    }
+}
+
+impl Error for ImageError {}
+
+// A view that doesn't fit the render pass attachment at the same index.
+#[derive(Debug)]
+pub enum FramebufferError {
+    AttachmentCount {
+        view_count: usize,
+        attachment_count: usize,
+    },
+    FormatMismatch {
+        attachment: usize,
+        view_format: Format,
+        attachment_format: Format,
+    },
+    SamplesMismatch {
+        attachment: usize,
+        view_samples: SampleCount,
+        attachment_samples: SampleCount,
+    },
+    // The view is smaller than the framebuffer.
+    ViewTooSmall {
+        attachment: usize,
+        view_extent: [u32; 2],
+        extent: [u32; 2],
+    },
+    Vulkan(Validated<VulkanError>),
+}
+
+impl fmt::Display for FramebufferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FramebufferError::AttachmentCount {
+                view_count,
+                attachment_count,
+            } => write!(
+                f,
+                "Got {} views for a render pass with {} attachments.",
+                view_count, attachment_count
+            ),
+            FramebufferError::FormatMismatch {
+                attachment,
+                view_format,
+                attachment_format,
+            } => write!(
+                f,
+                "The view for attachment {} is {:?}, but the render pass expects {:?}.",
+                attachment, view_format, attachment_format
+            ),
+            FramebufferError::SamplesMismatch {
+                attachment,
+                view_samples,
+                attachment_samples,
+            } => write!(
+                f,
+                "The view for attachment {} has {} samples, but the render pass expects {}.",
+                attachment, *view_samples as u32, *attachment_samples as u32
+            ),
+            FramebufferError::ViewTooSmall {
+                attachment,
+                view_extent,
+                extent,
+            } => write!(
+                f,
+                "The view for attachment {} is {:?}, smaller than the framebuffer's {:?}.",
+                attachment, view_extent, extent
+            ),
+            FramebufferError::Vulkan(error) => {
+                write!(f, "Failed to create framebuffer: {}", error)
+            }
+        }
+    }
+}
+
+impl Error for FramebufferError {}
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use vulkano::{
+    command_buffer::PrimaryAutoCommandBuffer,
+    sync::{self, future::FenceSignalFuture, GpuFuture},
+};
+
+use super::VulkanProcessor;
+
+// How many frames may be submitted before `begin_frame` blocks, and so how many copies of each
+// per-frame resource a renderer needs.
+pub const FRAMES_IN_FLIGHT: usize = 2;
+
+// Boxed so that frames ending in a swapchain present fit too, and `Send` and `Sync` to keep the
+// processor so.
+pub(super) type FrameFence = FenceSignalFuture<Box<dyn GpuFuture + Send + Sync>>;
+
+pub(super) struct FrameRing {
+    fences: Vec<Option<FrameFence>>,
+    next: usize,
+}
+
+impl FrameRing {
+    pub(super) fn new() -> Self {
+        FrameRing {
+            fences: (0..FRAMES_IN_FLIGHT).map(|_| None).collect(),
+            next: 0,
+        }
+    }
+}
+
+impl VulkanProcessor {
+    // Returns the ring slot for the next frame, to index per-frame resources such as uniform
+    // buffers with. Blocks until the frame that last used the slot has finished on the GPU, so
+    // its resources can be overwritten. A frame that is never ended leaves the slot to the next.
+    pub fn begin_frame(&self) -> usize {
+        let mut frame_ring = self.frame_ring.lock().unwrap();
+        let frame_index = frame_ring.next;
+
+        if let Some(fence) = frame_ring.fences[frame_index].take() {
+            fence.wait(None).unwrap();
+        }
+
+        return frame_index;
+    }
+
+    // Submits the frame begun with `begin_frame` without waiting for it, and moves on to the
+    // next slot.
+    pub fn end_frame(&self, frame_index: usize, command_buffer: Arc<PrimaryAutoCommandBuffer>) {
+        let fence = sync::now(self.device.clone())
+            .then_execute(self.graphics_queue.clone(), command_buffer)
+            .unwrap()
+            .boxed_send_sync()
+            .then_signal_fence_and_flush()
+            .unwrap();
+
+        self.end_frame_with_fence(frame_index, fence);
+    }
+
+    // As `end_frame`, for a frame already submitted some other way, e.g. by a `Presenter`.
+    pub(super) fn end_frame_with_fence(&self, frame_index: usize, fence: FrameFence) {
+        let mut frame_ring = self.frame_ring.lock().unwrap();
+        assert!(
+            frame_index == frame_ring.next,
+            "Frame {} was ended out of order, expected frame {}.",
+            frame_index,
+            frame_ring.next
+        );
+
+        frame_ring.fences[frame_index] = Some(fence);
+        frame_ring.next = (frame_index + 1) % FRAMES_IN_FLIGHT;
+    }
+
+    // Blocks until every submitted frame has finished, e.g. before recreating resources all the
+    // slots share.
+    pub fn wait_for_frames(&self) {
+        let mut frame_ring = self.frame_ring.lock().unwrap();
+
+        for fence in frame_ring.fences.iter_mut().filter_map(Option::take) {
+            fence.wait(None).unwrap();
+        }
+    }
+}
@@ -1,79 +1,442 @@
-use std::{sync::Arc, time::Instant};
+use std::{
+    fs,
+    mem::size_of,
+    panic::RefUnwindSafe,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use bytemuck::AnyBitPattern;
+use descriptors::{DescriptorSet, TrackingDescriptorSetAllocator};
+use frames::FrameRing;
+use pipeline::{AttachmentOps, BlendMode, DepthStencilConfig, RasterizationConfig};
+use serde::{Deserialize, Serialize};
 use vulkano::{
     buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer},
     command_buffer::{
         allocator::{StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo},
-        AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer,
+        AutoCommandBufferBuilder, CommandBufferInheritanceInfo,
+        CommandBufferInheritanceRenderPassInfo, CommandBufferInheritanceRenderPassType,
+        CommandBufferUsage, CopyBufferInfo, CopyImageToBufferInfo, PrimaryAutoCommandBuffer,
+        RenderPassBeginInfo, RenderingAttachmentInfo, RenderingInfo, SecondaryAutoCommandBuffer,
+        SecondaryCommandBufferAbstract, SubpassBeginInfo, SubpassContents, SubpassEndInfo,
     },
     descriptor_set::{
-        allocator::StandardDescriptorSetAllocator, PersistentDescriptorSet, WriteDescriptorSet,
+        allocator::{StandardDescriptorSetAllocator, StandardDescriptorSetAllocatorCreateInfo},
+        layout::DescriptorSetLayout,
+        PersistentDescriptorSet, WriteDescriptorSet,
     },
     device::{
-        physical::PhysicalDeviceType, Device, DeviceCreateInfo, Queue, QueueCreateInfo, QueueFlags,
+        physical::{PhysicalDevice, PhysicalDeviceType},
+        Device, DeviceCreateInfo, DeviceExtensions, DeviceOwned, Features, Properties, Queue,
+        QueueCreateInfo, QueueFlags,
+    },
+    format::{ClearValue, Format, FormatFeatures},
+    image::{
+        view::{ImageView, ImageViewCreateInfo, ImageViewType},
+        Image, ImageAspects, ImageCreateInfo, ImageLayout, ImageSubresourceRange, ImageType,
+        ImageUsage, SampleCount,
+    },
+    instance::{
+        debug::{
+            DebugUtilsMessageType, DebugUtilsMessenger, DebugUtilsMessengerCallback,
+            DebugUtilsMessengerCreateInfo,
+        },
+        Instance, InstanceCreateInfo, InstanceExtensions,
     },
-    format::Format,
-    image::{Image, ImageCreateInfo, ImageType, ImageUsage},
-    instance::{Instance, InstanceCreateInfo},
     memory::allocator::{
         AllocationCreateInfo, MemoryAllocator, MemoryTypeFilter, StandardMemoryAllocator,
     },
     pipeline::{
         compute::ComputePipelineCreateInfo,
         graphics::{
-            color_blend::{ColorBlendAttachmentState, ColorBlendState},
-            input_assembly::InputAssemblyState,
+            color_blend::ColorBlendState,
+            depth_stencil::{DepthStencilState, StencilState},
+            input_assembly::{InputAssemblyState, PrimitiveTopology},
             multisample::MultisampleState,
-            rasterization::RasterizationState,
-            vertex_input::VertexInputState,
-            viewport::{Viewport, ViewportState},
+            rasterization::{PolygonMode, RasterizationState},
+            subpass::PipelineSubpassType,
+            vertex_input::{Vertex, VertexDefinition, VertexInputState},
+            viewport::{Scissor, Viewport, ViewportState},
             GraphicsPipelineCreateInfo,
         },
         layout::PipelineDescriptorSetLayoutCreateInfo,
-        ComputePipeline, GraphicsPipeline, Pipeline, PipelineLayout, PipelineShaderStageCreateInfo,
+        ComputePipeline, DynamicState, GraphicsPipeline, Pipeline, PipelineBindPoint,
+        PipelineLayout, PipelineShaderStageCreateInfo,
     },
-    render_pass::{RenderPass, Subpass},
-    shader::ShaderModule,
-    sync::{self, GpuFuture},
-    Validated, VulkanError, VulkanLibrary,
+    query::{QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType},
+    render_pass::{
+        AttachmentDescription, AttachmentLoadOp, AttachmentReference, AttachmentStoreOp,
+        Framebuffer, FramebufferCreateInfo, RenderPass, RenderPassCreateInfo, Subpass,
+        SubpassDescription,
+    },
+    shader::{
+        spirv::{bytes_to_words, ExecutionModel},
+        ShaderInterface, ShaderModule, ShaderModuleCreateInfo,
+    },
+    sync::{self, future::FenceSignalFuture, GpuFuture, HostAccessError, PipelineStage},
+    Validated, Version, VulkanError, VulkanLibrary,
 };
 use wayland_client::backend::smallvec::SmallVec;
 
+use crate::config::RendererConfig;
+use error::{
+    DescriptorSetIndexError, ExecutionError, FramebufferError, ImageError, PipelineError,
+    ProcessorCreationError, ShaderLoadError,
+};
+
+pub mod descriptors;
+pub mod error;
+pub mod frames;
+pub mod pipeline;
+pub mod swapchain;
+#[cfg(test)]
+pub(crate) mod testing;
+pub mod texture;
+pub mod vertex;
+
+#[derive(Default)]
+pub struct VulkanProcessorCreateInfo {
+    // Prefer the first device whose name contains this string, falling back to the type ranking.
+    pub device_name: Option<String>,
+    // Decides between discrete and integrated GPUs when no device name matches.
+    pub power_preference: PowerPreference,
+    // Devices rejected by this predicate are never selected, whatever their ranking.
+    pub device_filter: Option<Box<dyn Fn(&PhysicalDevice) -> bool>>,
+    // Prefers software implementations such as lavapipe (`PhysicalDeviceType::Cpu`) over
+    // hardware devices. Without it they rank last, so they're only chosen when no hardware device
+    // is suitable or `device_name` selects one.
+    pub software_fallback: bool,
+    // Enabled in addition to those the processor needs itself, e.g. `khr_surface` and
+    // `khr_wayland_surface` for presenting to a Wayland surface.
+    pub instance_extensions: InstanceExtensions,
+    // Devices that don't support all of these are skipped during selection.
+    pub device_features: Features,
+    pub device_extensions: DeviceExtensions,
+    // Enables the extensions needed to present to a Wayland surface with a `Presenter`, and skips
+    // devices that can't create a swapchain.
+    pub wayland_presentation: bool,
+    // Only honoured when the validation layer is installed, otherwise a warning is printed.
+    pub enable_validation: bool,
+    // Receives validation messages, which are printed when no callback is given. Must not call
+    // into Vulkan.
+    pub validation_callback: Option<ValidationCallback>,
+    // Builds the allocator every buffer and image is allocated from, once the device exists.
+    // Defaults to a `StandardMemoryAllocator`.
+    pub memory_allocator: Option<MemoryAllocatorFactory>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PowerPreference {
+    #[default]
+    HighPerformance,
+    // Prefers an integrated GPU, e.g. to save battery on a laptop.
+    LowPower,
+}
+
+// Where a buffer lives, named by how the host and GPU use it, for `create_data_buffer_in` and
+// `create_iter_buffer_in`. Each maps to a memory type filter, plus the usage it implies:
+//
+//     GpuOnly   PREFER_DEVICE                          + TRANSFER_DST, filled through staging
+//     CpuToGpu  PREFER_DEVICE | HOST_SEQUENTIAL_WRITE
+//     GpuToCpu  PREFER_HOST | HOST_RANDOM_ACCESS       + TRANSFER_DST
+//     CpuOnly   PREFER_HOST | HOST_RANDOM_ACCESS       + TRANSFER_SRC
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum BufferLocality {
+    // Static data the host never touches again, e.g. meshes.
+    GpuOnly,
+    // Written by the host every frame and read by the GPU, e.g. uniforms.
+    CpuToGpu,
+    // Written by the GPU and read back, e.g. readback targets.
+    GpuToCpu,
+    // Staging data the GPU only copies from.
+    CpuOnly,
+}
+
+impl BufferLocality {
+    #[allow(dead_code)]
+    pub fn memory_type_filter(self) -> MemoryTypeFilter {
+        match self {
+            BufferLocality::GpuOnly => MemoryTypeFilter::PREFER_DEVICE,
+            BufferLocality::CpuToGpu => {
+                MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE
+            }
+            BufferLocality::GpuToCpu | BufferLocality::CpuOnly => {
+                MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn implied_usage(self) -> BufferUsage {
+        match self {
+            BufferLocality::GpuOnly | BufferLocality::GpuToCpu => BufferUsage::TRANSFER_DST,
+            BufferLocality::CpuToGpu => BufferUsage::empty(),
+            BufferLocality::CpuOnly => BufferUsage::TRANSFER_SRC,
+        }
+    }
+}
+
+pub type ValidationCallback = Arc<dyn Fn(&str) + RefUnwindSafe + Send + Sync>;
+
+pub type MemoryAllocatorFactory = Box<dyn FnOnce(Arc<Device>) -> Arc<dyn MemoryAllocator>>;
+
+const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+
+const DESCRIPTOR_SETS_PER_POOL: usize = 32;
+
+#[derive(Clone, Copy, Debug)]
+#[allow(dead_code)]
+pub struct DescriptorPoolStats {
+    pub allocated_sets: usize,
+    pub pool_capacity: usize,
+}
+
+#[derive(Clone, Debug)]
+#[allow(dead_code)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub device_type: PhysicalDeviceType,
+    // Encoded in a vendor-specific way, e.g. NVIDIA packs it differently from Mesa.
+    pub driver_version: u32,
+    pub api_version: Version,
+}
+
+// See `create_timestamp_query`.
+pub struct TimestampQuery {
+    query_pool: Arc<QueryPool>,
+}
+
 pub struct VulkanProcessor {
     device: Arc<Device>,
     graphics_queue: Arc<Queue>,
+    #[allow(dead_code)]
+    transfer_queue: Option<Arc<Queue>>,
     memory_allocator: Arc<dyn MemoryAllocator>,
     command_buffer_allocator: StandardCommandBufferAllocator,
+    #[allow(dead_code)]
+    descriptor_set_allocator: TrackingDescriptorSetAllocator,
+    // Of the graphics queue family's timestamps, `None` if it doesn't support them.
+    timestamp_valid_bits: Option<u32>,
+    frame_ring: Mutex<FrameRing>,
+    _debug_messenger: Option<DebugUtilsMessenger>,
 }
 
 impl VulkanProcessor {
+    #[allow(dead_code)]
     pub fn new() -> Self {
+        Self::new_with_info(VulkanProcessorCreateInfo::default())
+            .expect("Failed to create vulkan processor.")
+    }
+
+    #[allow(dead_code)]
+    pub fn new_with_validation(enable: bool) -> Self {
+        Self::new_with_info(VulkanProcessorCreateInfo {
+            enable_validation: enable,
+            ..Default::default()
+        })
+        .expect("Failed to create vulkan processor.")
+    }
+
+    // E.g. a `GenericMemoryAllocator` with tuned block sizes. The allocator must be created for
+    // the device it's given.
+    #[allow(dead_code)]
+    pub fn new_with_memory_allocator<T>(memory_allocator: T) -> Self
+    where
+        T: FnOnce(Arc<Device>) -> Arc<dyn MemoryAllocator> + 'static,
+    {
+        Self::new_with_info(VulkanProcessorCreateInfo {
+            memory_allocator: Some(Box::new(memory_allocator)),
+            ..Default::default()
+        })
+        .expect("Failed to create vulkan processor.")
+    }
+
+    pub fn from_config(config: &RendererConfig) -> Self {
+        Self::new_with_info(VulkanProcessorCreateInfo {
+            device_name: config.device_name.clone(),
+            software_fallback: config.software_fallback,
+            power_preference: config.power_preference,
+            wayland_presentation: config.vulkan_presentation,
+            ..Default::default()
+        })
+        .expect("Failed to create vulkan processor.")
+    }
+
+    pub fn new_with_info(
+        create_info: VulkanProcessorCreateInfo,
+    ) -> Result<Self, ProcessorCreationError> {
         println!("Creating new vulkan processor.");
         let creation_start = Instant::now();
 
         let library = VulkanLibrary::new().expect("No local Vulkan library/DLL.");
-        let instance = Instance::new(library, InstanceCreateInfo::default())
-            .expect("Failed to create instance.");
 
-        let physical_device = instance
+        let mut instance_extensions = create_info.instance_extensions;
+        let mut device_extensions = create_info.device_extensions;
+        if create_info.wayland_presentation {
+            instance_extensions.khr_surface = true;
+            instance_extensions.khr_wayland_surface = true;
+            device_extensions.khr_swapchain = true;
+        }
+
+        let validation_enabled = create_info.enable_validation
+            && library
+                .layer_properties()
+                .map(|mut layers| layers.any(|layer| layer.name() == VALIDATION_LAYER))
+                .unwrap_or(false);
+
+        if create_info.enable_validation && !validation_enabled {
+            println!(
+                "Warning: {} is not available, continuing without validation.",
+                VALIDATION_LAYER
+            );
+        }
+
+        let debug_utils_enabled =
+            validation_enabled && library.supported_extensions().ext_debug_utils;
+
+        if !library
+            .supported_extensions()
+            .contains(&instance_extensions)
+        {
+            return Err(ProcessorCreationError::UnsupportedInstanceExtensions {
+                missing_extensions: instance_extensions.difference(library.supported_extensions()),
+            });
+        }
+
+        let mut instance_create_info = InstanceCreateInfo {
+            enabled_extensions: instance_extensions,
+            ..Default::default()
+        };
+        if validation_enabled {
+            instance_create_info.enabled_layers = vec![VALIDATION_LAYER.to_owned()];
+        }
+        if debug_utils_enabled {
+            instance_create_info.enabled_extensions.ext_debug_utils = true;
+        }
+
+        let instance =
+            Instance::new(library, instance_create_info).expect("Failed to create instance.");
+
+        let debug_messenger = if debug_utils_enabled {
+            let validation_callback = create_info.validation_callback.clone();
+
+            // Safety: the callback only prints or forwards to a callback documented not to call
+            // into Vulkan.
+            let user_callback = unsafe {
+                DebugUtilsMessengerCallback::new(move |_severity, _message_type, callback_data| {
+                    match validation_callback {
+                        Some(ref validation_callback) => validation_callback(callback_data.message),
+                        None => println!("Vulkan validation: {}", callback_data.message),
+                    }
+                })
+            };
+
+            Some(
+                DebugUtilsMessenger::new(
+                    instance.clone(),
+                    DebugUtilsMessengerCreateInfo {
+                        message_type: DebugUtilsMessageType::GENERAL
+                            | DebugUtilsMessageType::VALIDATION
+                            | DebugUtilsMessageType::PERFORMANCE,
+                        ..DebugUtilsMessengerCreateInfo::user_callback(user_callback)
+                    },
+                )
+                .expect("Failed to create debug messenger."),
+            )
+        } else {
+            None
+        };
+
+        let physical_devices: Vec<Arc<PhysicalDevice>> = instance
             .enumerate_physical_devices()
             .expect("Could not enumerate devices.")
-            .min_by_key(|device| match device.properties().device_type {
-                PhysicalDeviceType::DiscreteGpu => 0,
-                PhysicalDeviceType::IntegratedGpu => 1,
+            .collect();
+
+        if physical_devices.is_empty() {
+            return Err(ProcessorCreationError::NoDevices);
+        }
+
+        let (discrete_rank, integrated_rank) = match create_info.power_preference {
+            PowerPreference::HighPerformance => (0, 1),
+            PowerPreference::LowPower => (1, 0),
+        };
+
+        let rank_device = |device: &Arc<PhysicalDevice>| {
+            let name_rank = match create_info.device_name {
+                Some(ref name) if device.properties().device_name.contains(name.as_str()) => 0,
+                Some(_) => 1,
+                None => 0,
+            };
+
+            // Before the type, so it also puts software devices ahead of discrete GPUs.
+            let software_rank = match device.properties().device_type {
+                PhysicalDeviceType::Cpu if create_info.software_fallback => 0,
+                PhysicalDeviceType::Cpu => 1,
+                _ if create_info.software_fallback => 1,
+                _ => 0,
+            };
+
+            let type_rank = match device.properties().device_type {
+                PhysicalDeviceType::DiscreteGpu => discrete_rank,
+                PhysicalDeviceType::IntegratedGpu => integrated_rank,
                 PhysicalDeviceType::VirtualGpu => 2,
                 PhysicalDeviceType::Cpu => 3,
                 PhysicalDeviceType::Other => 4,
                 _ => 5,
+            };
+
+            (name_rank, software_rank, type_rank)
+        };
+
+        let candidates: Vec<Arc<PhysicalDevice>> = physical_devices
+            .into_iter()
+            .filter(|device| match create_info.device_filter {
+                Some(ref device_filter) => device_filter(device),
+                None => true,
             })
-            .expect("No devices available.");
+            .collect();
+
+        let best_candidate = candidates
+            .iter()
+            .min_by_key(|device| rank_device(device))
+            .ok_or(ProcessorCreationError::NoSuitableDevice)?
+            .clone();
+
+        let physical_device = candidates
+            .into_iter()
+            .filter(|device| {
+                device
+                    .supported_features()
+                    .contains(&create_info.device_features)
+                    && device.supported_extensions().contains(&device_extensions)
+            })
+            .min_by_key(rank_device)
+            .ok_or_else(|| ProcessorCreationError::UnsupportedRequirements {
+                device_name: best_candidate.properties().device_name.clone(),
+                missing_features: create_info
+                    .device_features
+                    .difference(best_candidate.supported_features()),
+                missing_extensions: device_extensions
+                    .difference(best_candidate.supported_extensions()),
+            })?;
 
         println!(
-            "Chose physical device: {:?}.",
-            physical_device.properties().device_name
+            "Chose physical device: {:?} ({:?}).",
+            physical_device.properties().device_name,
+            physical_device.properties().device_type
         );
 
+        if physical_device.properties().device_type == PhysicalDeviceType::Cpu
+            && !create_info.software_fallback
+        {
+            println!(
+                "Warning: using a software Vulkan device, rendering is not hardware accelerated."
+            );
+        }
+
         let queue_family_index = physical_device
             .queue_family_properties()
             .iter()
@@ -86,40 +449,217 @@ impl VulkanProcessor {
             .expect("Couldn't find a graphical queue family.")
             as u32;
 
+        // Prefer a transfer-only family, which usually maps to a dedicated DMA engine.
+        let transfer_queue_family_index = physical_device
+            .queue_family_properties()
+            .iter()
+            .enumerate()
+            .filter(|(index, queue_family_properties)| {
+                *index as u32 != queue_family_index
+                    && queue_family_properties
+                        .queue_flags
+                        .contains(QueueFlags::TRANSFER)
+            })
+            .min_by_key(|(_index, queue_family_properties)| {
+                queue_family_properties
+                    .queue_flags
+                    .intersects(QueueFlags::GRAPHICS | QueueFlags::COMPUTE)
+            })
+            .map(|(index, _queue_family_properties)| index as u32);
+
+        let mut queue_create_infos = vec![QueueCreateInfo {
+            queue_family_index,
+            ..Default::default()
+        }];
+
+        if let Some(transfer_queue_family_index) = transfer_queue_family_index {
+            queue_create_infos.push(QueueCreateInfo {
+                queue_family_index: transfer_queue_family_index,
+                ..Default::default()
+            });
+        }
+
         let (device, mut queues) = Device::new(
             physical_device,
             DeviceCreateInfo {
-                queue_create_infos: vec![QueueCreateInfo {
-                    queue_family_index,
-                    ..Default::default()
-                }],
+                queue_create_infos,
+                enabled_features: create_info.device_features,
+                enabled_extensions: device_extensions,
                 ..Default::default()
             },
         )
         .expect("Failed to create device.");
 
-        let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
+        let memory_allocator: Arc<dyn MemoryAllocator> = match create_info.memory_allocator {
+            Some(create_memory_allocator) => {
+                let memory_allocator = create_memory_allocator(device.clone());
+                assert!(
+                    memory_allocator.device() == &device,
+                    "The memory allocator was created for a different device."
+                );
+
+                memory_allocator
+            }
+            None => Arc::new(StandardMemoryAllocator::new_default(device.clone())),
+        };
 
         let command_buffer_allocator = StandardCommandBufferAllocator::new(
             device.clone(),
             StandardCommandBufferAllocatorCreateInfo::default(),
         );
 
+        let descriptor_set_allocator =
+            TrackingDescriptorSetAllocator::new(StandardDescriptorSetAllocator::new(
+                device.clone(),
+                StandardDescriptorSetAllocatorCreateInfo {
+                    set_count: DESCRIPTOR_SETS_PER_POOL,
+                    ..Default::default()
+                },
+            ));
+
         let graphics_queue = queues.next().unwrap();
+        let transfer_queue = queues.next();
+
+        let timestamp_valid_bits = device.physical_device().queue_family_properties()
+            [queue_family_index as usize]
+            .timestamp_valid_bits;
 
         println!(
             "Vulkan processor creation completed in {} milliseconds. \n",
             creation_start.elapsed().as_millis()
         );
 
-        return VulkanProcessor {
+        return Ok(VulkanProcessor {
             device,
             graphics_queue,
+            transfer_queue,
             memory_allocator,
             command_buffer_allocator,
-        };
+            descriptor_set_allocator,
+            timestamp_valid_bits,
+            frame_ring: Mutex::new(FrameRing::new()),
+            _debug_messenger: debug_messenger,
+        });
+    }
+
+    #[allow(dead_code)]
+    pub fn device_name(&self) -> String {
+        self.device
+            .physical_device()
+            .properties()
+            .device_name
+            .clone()
+    }
+
+    // For display or bug reports, the rest of the properties are in `physical_device_properties`.
+    #[allow(dead_code)]
+    pub fn device_info(&self) -> DeviceInfo {
+        let properties = self.device.physical_device().properties();
+
+        DeviceInfo {
+            name: properties.device_name.clone(),
+            device_type: properties.device_type,
+            driver_version: properties.driver_version,
+            api_version: properties.api_version,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn is_software_device(&self) -> bool {
+        self.device.physical_device().properties().device_type == PhysicalDeviceType::Cpu
+    }
+
+    pub fn max_image_dimension_2d(&self) -> u32 {
+        self.device
+            .physical_device()
+            .properties()
+            .max_image_dimension2_d
+    }
+
+    #[allow(dead_code)]
+    pub fn max_push_constants_size(&self) -> u32 {
+        self.device
+            .physical_device()
+            .properties()
+            .max_push_constants_size
+    }
+
+    #[allow(dead_code)]
+    pub fn max_compute_work_group_count(&self) -> [u32; 3] {
+        self.device
+            .physical_device()
+            .properties()
+            .max_compute_work_group_count
+    }
+
+    // The local size limits of a compute shader, per dimension and in total.
+    #[allow(dead_code)]
+    pub fn max_compute_work_group_size(&self) -> [u32; 3] {
+        self.device
+            .physical_device()
+            .properties()
+            .max_compute_work_group_size
+    }
+
+    #[allow(dead_code)]
+    pub fn max_compute_work_group_invocations(&self) -> u32 {
+        self.device
+            .physical_device()
+            .properties()
+            .max_compute_work_group_invocations
+    }
+
+    // Local sizes that are a multiple of this keep every subgroup full. `None` before Vulkan 1.1.
+    #[allow(dead_code)]
+    pub fn subgroup_size(&self) -> Option<u32> {
+        self.device.physical_device().properties().subgroup_size
+    }
+
+    // The group counts covering `invocations` with groups of `local_size`, as declared in the
+    // shader, checked against the device's limits.
+    #[allow(dead_code)]
+    pub fn group_counts_for(&self, invocations: [u32; 3], local_size: [u32; 3]) -> [u32; 3] {
+        let max_size = self.max_compute_work_group_size();
+        assert!(
+            (0..3).all(|i| local_size[i] > 0 && local_size[i] <= max_size[i]),
+            "Local size {:?} exceeds the device's maximum of {:?}.",
+            local_size,
+            max_size
+        );
+
+        let max_invocations = self.max_compute_work_group_invocations();
+        assert!(
+            local_size.iter().product::<u32>() <= max_invocations,
+            "Local size {:?} has more than the device's maximum of {} invocations.",
+            local_size,
+            max_invocations
+        );
+
+        let group_counts = [0, 1, 2].map(|i| invocations[i].div_ceil(local_size[i]));
+        self.check_group_counts(group_counts);
+
+        return group_counts;
+    }
+
+    #[allow(dead_code)]
+    fn check_group_counts(&self, group_counts: [u32; 3]) {
+        let max_count = self.max_compute_work_group_count();
+
+        assert!(
+            (0..3).all(|i| group_counts[i] <= max_count[i]),
+            "Group counts {:?} exceed the device's maximum of {:?}.",
+            group_counts,
+            max_count
+        );
+    }
+
+    // All of the device's properties and limits, for those without an accessor of their own.
+    #[allow(dead_code)]
+    pub fn physical_device_properties(&self) -> Properties {
+        self.device.physical_device().properties().clone()
     }
 
+    #[allow(dead_code)]
     pub fn create_data_buffer<T: AnyBitPattern + BufferContents>(
         &self,
         data: T,
@@ -143,6 +683,29 @@ impl VulkanProcessor {
         return buffer;
     }
 
+    // Host-writable, so the contents can be updated between frames with `write()`. Bound with
+    // `WriteDescriptorSet::buffer`, e.g. for `layout(binding = 0) uniform Mvp { mat4 mvp; }`:
+    //
+    //     let mvp = processor.create_uniform_buffer([[1.0f32, 0.0, 0.0, 0.0], ...]);
+    //     let descriptor_set = processor.create_graphics_descriptor_set(
+    //         pipeline.clone(),
+    //         [WriteDescriptorSet::buffer(0, mvp.clone())],
+    //     );
+    //
+    // and bound with `bind_descriptor_sets(PipelineBindPoint::Graphics, ...)` before drawing.
+    // GLSL's std140 layout applies, so e.g. a `vec3` member must be padded to 16 bytes on our side.
+    #[allow(dead_code)]
+    pub fn create_uniform_buffer<T: AnyBitPattern + BufferContents>(
+        &self,
+        data: T,
+    ) -> Subbuffer<T> {
+        self.create_data_buffer(
+            data,
+            BufferUsage::UNIFORM_BUFFER,
+            MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+        )
+    }
+
     pub fn create_iter_buffer<T: AnyBitPattern + BufferContents>(
         &self,
         iter: Vec<T>,
@@ -165,233 +728,2817 @@ impl VulkanProcessor {
         return buffer;
     }
 
-    pub fn create_image(
+    // As `create_data_buffer`, with the memory picked by intent. `GpuOnly` buffers aren't
+    // host-visible, so they're filled through a staging buffer.
+    #[allow(dead_code)]
+    pub fn create_data_buffer_in<T: AnyBitPattern + BufferContents>(
         &self,
-        image_type: ImageType,
-        format: Format,
-        extent: [u32; 3],
-        usage: ImageUsage,
-        memory_type_filters: MemoryTypeFilter,
-    ) -> Arc<Image> {
-        Image::new(
+        data: T,
+        buffer_usage: BufferUsage,
+        locality: BufferLocality,
+    ) -> Subbuffer<T> {
+        let buffer_usage = buffer_usage | locality.implied_usage();
+
+        if locality != BufferLocality::GpuOnly {
+            return self.create_data_buffer(data, buffer_usage, locality.memory_type_filter());
+        }
+
+        let staging_buffer = self.create_data_buffer(
+            data,
+            BufferUsage::TRANSFER_SRC,
+            BufferLocality::CpuOnly.memory_type_filter(),
+        );
+        let device_buffer: Subbuffer<T> = Buffer::new_sized(
             self.memory_allocator.clone(),
-            ImageCreateInfo {
-                image_type: image_type,
-                format: format,
-                extent: extent,
-                usage: usage,
+            BufferCreateInfo {
+                usage: buffer_usage,
                 ..Default::default()
             },
             AllocationCreateInfo {
-                memory_type_filter: memory_type_filters,
+                memory_type_filter: locality.memory_type_filter(),
                 ..Default::default()
             },
         )
-        .unwrap()
-    }
+        .expect("Failed to create buffer.");
 
-    pub fn create_render_pass(&self, format: Format) -> Arc<RenderPass> {
-        vulkano::single_pass_renderpass!(
-            self.device.clone(),
-            attachments: {
-                color: {
-                    format: format,
-                    samples: 1,
-                    load_op: Clear,
-                    store_op: Store,
-                },
-            },
-            pass: {
-                color: [color],
-                depth_stencil: {},
+        let command_buffer = self.create_command_buffer(
+            |builder| {
+                builder
+                    .copy_buffer(CopyBufferInfo::buffers(
+                        staging_buffer,
+                        device_buffer.clone(),
+                    ))
+                    .unwrap();
             },
-        )
-        .unwrap()
+            CommandBufferUsage::OneTimeSubmit,
+        );
+        self.execute_then_wait(command_buffer);
+
+        return device_buffer;
     }
 
-    pub fn create_pipeline_stages_layout<T>(
+    // As `create_iter_buffer`, with the memory picked by intent.
+    #[allow(dead_code)]
+    pub fn create_iter_buffer_in<T: AnyBitPattern + BufferContents>(
         &self,
-        load_functions: Vec<T>,
-    ) -> (Vec<PipelineShaderStageCreateInfo>, Arc<PipelineLayout>)
-    where
-        T: Fn(Arc<Device>) -> Result<Arc<ShaderModule>, Validated<VulkanError>>,
-    {
-        let mut stages: Vec<PipelineShaderStageCreateInfo> = vec![];
-
-        for load_function in load_functions {
-            stages.push(PipelineShaderStageCreateInfo::new(
-                load_function(self.device.clone())
-                    .expect("Failed to create shader module.")
-                    .entry_point("main")
-                    .unwrap(),
-            ));
+        iter: Vec<T>,
+        buffer_usage: BufferUsage,
+        locality: BufferLocality,
+    ) -> Subbuffer<[T]> {
+        if locality == BufferLocality::GpuOnly {
+            return self.upload_to_device_buffer(iter, buffer_usage);
         }
 
-        let layout = PipelineLayout::new(
-            self.device.clone(),
-            PipelineDescriptorSetLayoutCreateInfo::from_stages(
-                stages
-                    .iter()
-                    .collect::<Vec<&PipelineShaderStageCreateInfo>>(),
-            )
-            .into_pipeline_layout_create_info(self.device.clone())
-            .unwrap(),
-        )
-        .expect("Failed to create pipeline layout.");
-
-        return (stages, layout);
+        return self.create_iter_buffer(
+            iter,
+            buffer_usage | locality.implied_usage(),
+            locality.memory_type_filter(),
+        );
     }
 
-    pub fn create_compute_pipeline(
-        &self,
-        stage: PipelineShaderStageCreateInfo,
-        layout: Arc<PipelineLayout>,
-    ) -> Arc<ComputePipeline> {
-        return ComputePipeline::new(
-            self.device.clone(),
-            None,
-            ComputePipelineCreateInfo::stage_layout(stage, layout),
+    pub fn create_index_buffer(&self, indices: Vec<u32>) -> Subbuffer<[u32]> {
+        self.create_iter_buffer(
+            indices,
+            BufferUsage::INDEX_BUFFER,
+            MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
         )
-        .expect("Failed to create compute pipeline.");
     }
 
-    pub fn create_graphics_pipeline(
+    // Copies the data through a temporary staging buffer into DEVICE_LOCAL memory that the host
+    // can't access, the fastest place for static vertex and index data. Runs on the graphics
+    // queue, so the buffer is ready for use there without a queue family ownership transfer.
+    #[allow(dead_code)]
+    pub fn upload_to_device_buffer<T: AnyBitPattern + BufferContents>(
         &self,
-        (stages, layout): (Vec<PipelineShaderStageCreateInfo>, Arc<PipelineLayout>),
-        vertex_input_state: VertexInputState,
-        viewport: Viewport,
-        subpass: Subpass,
-    ) -> Arc<GraphicsPipeline> {
-        GraphicsPipeline::new(
-            self.device.clone(),
-            None,
-            GraphicsPipelineCreateInfo {
-                stages: SmallVec::from_vec(stages),
-                vertex_input_state: Some(vertex_input_state),
-                input_assembly_state: Some(InputAssemblyState::default()),
-                viewport_state: Some(ViewportState {
-                    viewports: [viewport].into_iter().collect(),
-                    ..Default::default()
-                }),
-                rasterization_state: Some(RasterizationState::default()),
-                multisample_state: Some(MultisampleState::default()),
-                color_blend_state: Some(ColorBlendState::with_attachment_states(
-                    subpass.num_color_attachments(),
-                    ColorBlendAttachmentState::default(),
-                )),
-                subpass: Some(subpass.into()),
-                ..GraphicsPipelineCreateInfo::layout(layout)
+        data: Vec<T>,
+        usage: BufferUsage,
+    ) -> Subbuffer<[T]> {
+        let staging_buffer = self.create_iter_buffer(
+            data,
+            BufferUsage::TRANSFER_SRC,
+            MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+        );
+
+        let device_buffer: Subbuffer<[T]> = Buffer::new_slice(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: usage | BufferUsage::TRANSFER_DST,
+                ..Default::default()
             },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+            staging_buffer.len(),
         )
-        .unwrap()
+        .expect("Failed to create buffer.");
+
+        let command_buffer = self.create_command_buffer(
+            |builder| {
+                builder
+                    .copy_buffer(CopyBufferInfo::buffers(
+                        staging_buffer,
+                        device_buffer.clone(),
+                    ))
+                    .unwrap();
+            },
+            CommandBufferUsage::OneTimeSubmit,
+        );
+        self.execute_then_wait(command_buffer);
+
+        return device_buffer;
     }
 
-    pub fn create_compute_descriptor_set(
+    // Reads host-visible buffers directly. Anything else, such as a PREFER_DEVICE buffer on a
+    // discrete GPU, is first copied into a staging buffer, which needs the buffer to have been
+    // created with TRANSFER_SRC usage. The GPU must be done writing the buffer.
+    pub fn download_buffer<T: AnyBitPattern + BufferContents>(
         &self,
-        compute_pipeline: Arc<ComputePipeline>,
-        write_descriptor_sets: impl IntoIterator<Item = WriteDescriptorSet>,
-    ) -> Arc<PersistentDescriptorSet> {
-        return PersistentDescriptorSet::new(
-            &StandardDescriptorSetAllocator::new(self.device.clone(), Default::default()),
-            compute_pipeline
-                .layout()
-                .set_layouts()
-                .get(0)
-                .unwrap()
-                .clone(),
-            write_descriptor_sets,
-            [],
+        buffer: &Subbuffer<[T]>,
+    ) -> Vec<T> {
+        match buffer.read() {
+            Ok(contents) => return contents.to_vec(),
+            Err(HostAccessError::NotHostMapped) => (),
+            Err(error) => panic!("Failed to read buffer: {}", error),
+        }
+
+        let staging_buffer: Subbuffer<[T]> = Buffer::new_slice(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            buffer.len(),
         )
-        .expect("Failed to create descriptor set.");
+        .expect("Failed to create buffer.");
+
+        let command_buffer = self.create_command_buffer(
+            |builder| {
+                builder
+                    .copy_buffer(CopyBufferInfo::buffers(
+                        buffer.clone(),
+                        staging_buffer.clone(),
+                    ))
+                    .expect("Buffer isn't host-visible and lacks TRANSFER_SRC usage.");
+            },
+            CommandBufferUsage::OneTimeSubmit,
+        );
+        self.execute_then_wait(command_buffer);
+
+        return staging_buffer.read().unwrap().to_vec();
     }
 
-    pub fn create_graphics_descriptor_set(
+    // Sets every element to `value` without a host upload, e.g. to zero a storage buffer before
+    // a compute pass. The buffer needs TRANSFER_DST usage.
+    #[allow(dead_code)]
+    pub fn fill_buffer(&self, buffer: &Subbuffer<[u32]>, value: u32) {
+        let command_buffer = self.create_command_buffer(
+            |builder| {
+                builder
+                    .fill_buffer(buffer.clone(), value)
+                    .expect("Buffer lacks TRANSFER_DST usage.");
+            },
+            CommandBufferUsage::OneTimeSubmit,
+        );
+        self.execute_then_wait(command_buffer);
+    }
+
+    // Copies all of `src` into `dst` and waits for it, e.g. from a staging buffer or between
+    // device-local buffers. `src` needs TRANSFER_SRC usage and `dst` TRANSFER_DST usage.
+    #[allow(dead_code)]
+    pub fn copy_buffer<T: BufferContents>(&self, src: &Subbuffer<[T]>, dst: &Subbuffer<[T]>) {
+        assert!(
+            src.len() == dst.len(),
+            "Can't copy {} elements into a buffer of {}.",
+            src.len(),
+            dst.len()
+        );
+
+        let command_buffer = self.create_command_buffer(
+            |builder| {
+                builder
+                    .copy_buffer(CopyBufferInfo::buffers(src.clone(), dst.clone()))
+                    .expect("Buffers lack TRANSFER_SRC or TRANSFER_DST usage.");
+            },
+            CommandBufferUsage::OneTimeSubmit,
+        );
+        self.execute_then_wait(command_buffer);
+    }
+
+    // Records a copy of just the `extent` rectangle at `offset` in the first layer and mip level of
+    // `image`, tightly packed at the start of `buffer`, e.g. to read back one tile of a frame.
+    #[allow(dead_code)]
+    pub fn record_copy_image_region_to_buffer<T: BufferContents>(
         &self,
-        graphics_pipeline: Arc<GraphicsPipeline>,
-        write_descriptor_sets: impl IntoIterator<Item = WriteDescriptorSet>,
-    ) -> Arc<PersistentDescriptorSet> {
-        return PersistentDescriptorSet::new(
-            &StandardDescriptorSetAllocator::new(self.device.clone(), Default::default()),
-            graphics_pipeline
-                .layout()
-                .set_layouts()
-                .get(0)
-                .unwrap()
-                .clone(),
-            write_descriptor_sets,
-            [],
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image: Arc<Image>,
+        buffer: Subbuffer<[T]>,
+        offset: [u32; 2],
+        extent: [u32; 2],
+    ) {
+        let image_extent = image.extent();
+        assert!(
+            extent[0] > 0
+                && extent[1] > 0
+                && offset[0] + extent[0] <= image_extent[0]
+                && offset[1] + extent[1] <= image_extent[1],
+            "Region {:?} at {:?} is empty or outside the {}x{} image.",
+            extent,
+            offset,
+            image_extent[0],
+            image_extent[1]
+        );
+
+        let mut copy_info = CopyImageToBufferInfo::image_buffer(image, buffer);
+        copy_info.regions[0].image_offset = [offset[0], offset[1], 0];
+        copy_info.regions[0].image_extent = [extent[0], extent[1], 1];
+
+        builder
+            .copy_image_to_buffer(copy_info)
+            .expect("Buffer is too small for the region or lacks TRANSFER_DST usage.");
+    }
+
+    // For vertices generated by a compute shader and then drawn, which never touch the host.
+    #[allow(dead_code)]
+    pub fn create_storage_vertex_buffer<T: BufferContents>(&self, len: u64) -> Subbuffer<[T]> {
+        Buffer::new_slice(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER | BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+            len,
         )
-        .expect("Failed to create descriptor set.");
+        .expect("Failed to create buffer.")
     }
 
-    pub fn create_command_buffer<T>(
+    // Checks the extent and usage against the device before creating the image, since sizes often
+    // come from outside, e.g. the compositor or an image file.
+    pub fn create_image(
         &self,
-        builder_fn: T,
-        usage: CommandBufferUsage,
-    ) -> Arc<PrimaryAutoCommandBuffer>
-    where
-        T: FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>),
-    {
-        let mut builder = AutoCommandBufferBuilder::primary(
-            &self.command_buffer_allocator,
-            self.graphics_queue.queue_family_index(),
+        image_type: ImageType,
+        format: Format,
+        extent: [u32; 3],
+        usage: ImageUsage,
+        memory_type_filters: MemoryTypeFilter,
+    ) -> Result<Arc<Image>, ImageError> {
+        self.create_image_with_mip_levels(image_type, format, extent, 1, usage, memory_type_filters)
+    }
+
+    // See `texture::mip_levels_for_extent` for a full mip chain, filled in with `generate_mipmaps`.
+    pub fn create_image_with_mip_levels(
+        &self,
+        image_type: ImageType,
+        format: Format,
+        extent: [u32; 3],
+        mip_levels: u32,
+        usage: ImageUsage,
+        memory_type_filters: MemoryTypeFilter,
+    ) -> Result<Arc<Image>, ImageError> {
+        self.create_validated_image(
+            ImageCreateInfo {
+                image_type: image_type,
+                format: format,
+                extent: extent,
+                mip_levels: mip_levels,
+                usage: usage,
+                ..Default::default()
+            },
+            memory_type_filters,
+        )
+    }
+
+    // A 2D image with `array_layers` layers, each of which can be rendered to through
+    // `create_layer_view`, e.g. for a texture atlas or the six faces of a cubemap.
+    #[allow(dead_code)]
+    pub fn create_image_array(
+        &self,
+        format: Format,
+        extent: [u32; 2],
+        array_layers: u32,
+        usage: ImageUsage,
+        memory_type_filters: MemoryTypeFilter,
+    ) -> Result<Arc<Image>, ImageError> {
+        self.create_validated_image(
+            ImageCreateInfo {
+                image_type: ImageType::Dim2d,
+                format: format,
+                extent: [extent[0], extent[1], 1],
+                array_layers: array_layers,
+                usage: usage,
+                ..Default::default()
+            },
+            memory_type_filters,
+        )
+    }
+
+    // A 2D view of a single layer and the first mip level, usable as a framebuffer attachment.
+    #[allow(dead_code)]
+    pub fn create_layer_view(&self, image: Arc<Image>, layer: u32) -> Arc<ImageView> {
+        assert!(
+            layer < image.array_layers(),
+            "Layer {} is out of range, the image has {} layers.",
+            layer,
+            image.array_layers()
+        );
+
+        let create_info = ImageViewCreateInfo {
+            view_type: ImageViewType::Dim2d,
+            subresource_range: ImageSubresourceRange {
+                aspects: ImageAspects::COLOR,
+                mip_levels: 0..1,
+                array_layers: layer..layer + 1,
+            },
+            ..ImageViewCreateInfo::from_image(&image)
+        };
+
+        return ImageView::new(image, create_info).expect("Failed to create layer view.");
+    }
+
+    fn create_validated_image(
+        &self,
+        create_info: ImageCreateInfo,
+        memory_type_filters: MemoryTypeFilter,
+    ) -> Result<Arc<Image>, ImageError> {
+        let ImageCreateInfo {
+            image_type,
+            format,
+            extent,
+            array_layers,
             usage,
+            ..
+        } = create_info;
+
+        let properties = self.device.physical_device().properties();
+        let max_extent = match image_type {
+            ImageType::Dim1d => [properties.max_image_dimension1_d, 1, 1],
+            ImageType::Dim2d => [
+                properties.max_image_dimension2_d,
+                properties.max_image_dimension2_d,
+                1,
+            ],
+            _ => [properties.max_image_dimension3_d; 3],
+        };
+
+        if extent
+            .iter()
+            .zip(max_extent)
+            .any(|(&dimension, max_dimension)| dimension == 0 || dimension > max_dimension)
+        {
+            return Err(ImageError::InvalidExtent { extent, max_extent });
+        }
+
+        if array_layers > properties.max_image_array_layers {
+            return Err(ImageError::TooManyArrayLayers {
+                array_layers,
+                max_array_layers: properties.max_image_array_layers,
+            });
+        }
+
+        let unsupported_usage = usage.difference(self.supported_image_usage(format));
+        if !unsupported_usage.is_empty() {
+            return Err(ImageError::UnsupportedUsage {
+                format,
+                unsupported_usage,
+            });
+        }
+
+        return Image::new(
+            self.memory_allocator.clone(),
+            create_info,
+            AllocationCreateInfo {
+                memory_type_filter: memory_type_filters,
+                ..Default::default()
+            },
         )
-        .expect("Failed to create command buffer builder.");
+        .map_err(ImageError::Vulkan);
+    }
 
-        builder_fn(&mut builder);
+    pub fn create_image_or_panic(
+        &self,
+        image_type: ImageType,
+        format: Format,
+        extent: [u32; 3],
+        usage: ImageUsage,
+        memory_type_filters: MemoryTypeFilter,
+    ) -> Arc<Image> {
+        self.create_image(image_type, format, extent, usage, memory_type_filters)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
 
-        return builder.build().expect("Failed to create command buffer.");
+    // The usages an optimally tiled image of `format` can have, judged by its format features.
+    fn supported_image_usage(&self, format: Format) -> ImageUsage {
+        let features = self
+            .device
+            .physical_device()
+            .format_properties(format)
+            .expect("Failed to query format properties.")
+            .optimal_tiling_features;
+
+        let mut usage = ImageUsage::TRANSIENT_ATTACHMENT | ImageUsage::INPUT_ATTACHMENT;
+        for (feature, feature_usage) in [
+            (FormatFeatures::TRANSFER_SRC, ImageUsage::TRANSFER_SRC),
+            (FormatFeatures::TRANSFER_DST, ImageUsage::TRANSFER_DST),
+            (FormatFeatures::SAMPLED_IMAGE, ImageUsage::SAMPLED),
+            (FormatFeatures::STORAGE_IMAGE, ImageUsage::STORAGE),
+            (
+                FormatFeatures::COLOR_ATTACHMENT,
+                ImageUsage::COLOR_ATTACHMENT,
+            ),
+            (
+                FormatFeatures::DEPTH_STENCIL_ATTACHMENT,
+                ImageUsage::DEPTH_STENCIL_ATTACHMENT,
+            ),
+        ] {
+            if features.contains(feature) {
+                usage |= feature_usage;
+            }
+        }
+
+        return usage;
     }
 
-    pub fn execute_then_wait(&self, command_buffer: Arc<PrimaryAutoCommandBuffer>) {
-        sync::now(self.device.clone())
-            .then_execute(self.graphics_queue.clone(), command_buffer)
-            .unwrap()
-            .then_signal_fence_and_flush()
-            .unwrap()
-            .wait(None)
-            .unwrap();
+    // Transient, since the multisampled image is only ever resolved and never read back.
+    pub fn create_multisampled_image(
+        &self,
+        format: Format,
+        extent: [u32; 2],
+        samples: SampleCount,
+    ) -> Arc<Image> {
+        Image::new(
+            self.memory_allocator.clone(),
+            ImageCreateInfo {
+                image_type: ImageType::Dim2d,
+                format: format,
+                extent: [extent[0], extent[1], 1],
+                samples: samples,
+                usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSIENT_ATTACHMENT,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+        )
+        .unwrap()
     }
 
-    #[cfg(debug_assertions)]
-    pub fn _print_physical_devices(&self) {
-        println!(
-            "Found {} physical devices:",
-            self.device
-                .instance()
-                .enumerate_physical_devices()
-                .unwrap()
-                .len()
+    pub fn supports_sample_count(&self, samples: SampleCount) -> bool {
+        self.device
+            .physical_device()
+            .properties()
+            .framebuffer_color_sample_counts
+            .contains_enum(samples)
+    }
+
+    // With more than one sample, the scene is drawn into a multisampled `intermediary`
+    // attachment which is resolved into the single-sampled `color` attachment at the end of the
+    // pass, so `color` stays attachment 1 and can be copied out as before. `ops` apply to the
+    // attachment drawn into, the resolve target is always overwritten and stored.
+    //
+    // Attachments start and end in `ColorAttachmentOptimal`, the command buffer builder
+    // transitions the images into it, so a `Load` sees whatever the image last held.
+    pub fn create_render_pass(
+        &self,
+        format: Format,
+        samples: SampleCount,
+        ops: AttachmentOps,
+    ) -> Arc<RenderPass> {
+        assert!(
+            self.supports_sample_count(samples),
+            "{} samples are not supported for color attachments on this device.",
+            samples as u32
         );
 
-        for physical_device in self.device.instance().enumerate_physical_devices().unwrap() {
-            println!(
-                "  Found a physical device with name: {:?},",
-                physical_device.properties().device_name
+        let drawn = AttachmentDescription {
+            format,
+            samples,
+            load_op: ops.load,
+            store_op: ops.store,
+            initial_layout: ImageLayout::ColorAttachmentOptimal,
+            final_layout: ImageLayout::ColorAttachmentOptimal,
+            ..Default::default()
+        };
+        let reference = |attachment| {
+            Some(AttachmentReference {
+                attachment,
+                layout: ImageLayout::ColorAttachmentOptimal,
+                ..Default::default()
+            })
+        };
+
+        let (attachments, subpass) = if samples == SampleCount::Sample1 {
+            (
+                vec![drawn],
+                SubpassDescription {
+                    color_attachments: vec![reference(0)],
+                    ..Default::default()
+                },
             )
-        }
+        } else {
+            let resolved = AttachmentDescription {
+                samples: SampleCount::Sample1,
+                load_op: AttachmentLoadOp::DontCare,
+                store_op: AttachmentStoreOp::Store,
+                ..drawn
+            };
 
-        println!()
+            (
+                vec![drawn, resolved],
+                SubpassDescription {
+                    color_attachments: vec![reference(0)],
+                    color_resolve_attachments: vec![reference(1)],
+                    ..Default::default()
+                },
+            )
+        };
+
+        return RenderPass::new(
+            self.device.clone(),
+            RenderPassCreateInfo {
+                attachments,
+                subpasses: vec![subpass],
+                ..Default::default()
+            },
+        )
+        .expect("Failed to create render pass.");
     }
 
-    #[cfg(debug_assertions)]
-    pub fn _print_queue_families(&self) {
-        println!(
-            "Found {} queue families:",
-            self.device
-                .physical_device()
-                .queue_family_properties()
-                .len()
+    // A single-sampled pass with a color attachment and a depth and/or stencil attachment, e.g.
+    // `D24_UNORM_S8_UINT` for both. Both are cleared, the depth/stencil contents aren't stored.
+    #[allow(dead_code)]
+    pub fn create_render_pass_with_depth_stencil(
+        &self,
+        color_format: Format,
+        depth_stencil_format: Format,
+    ) -> Arc<RenderPass> {
+        assert!(
+            depth_stencil_format
+                .aspects()
+                .intersects(ImageAspects::DEPTH | ImageAspects::STENCIL),
+            "{:?} has neither a depth nor a stencil aspect.",
+            depth_stencil_format
+        );
+        assert!(
+            self.supported_image_usage(depth_stencil_format)
+                .intersects(ImageUsage::DEPTH_STENCIL_ATTACHMENT),
+            "{:?} can't be used as a depth/stencil attachment on this device.",
+            depth_stencil_format
         );
 
-        for family in self.device.physical_device().queue_family_properties() {
-            println!(
-                "  Found a queue family with flags '{:?}' containing {:?} queue(s),",
-                family.queue_flags, family.queue_count
-            );
+        vulkano::single_pass_renderpass!(
+            self.device.clone(),
+            attachments: {
+                color: {
+                    format: color_format,
+                    samples: 1,
+                    load_op: Clear,
+                    store_op: Store,
+                },
+                depth_stencil: {
+                    format: depth_stencil_format,
+                    samples: 1,
+                    load_op: Clear,
+                    store_op: DontCare,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth_stencil},
+            },
+        )
+        .unwrap()
+    }
+
+    // One view per render pass attachment, in the same order, each matching its attachment's format
+    // and sample count and at least `extent` in size.
+    pub fn create_framebuffer(
+        &self,
+        render_pass: &Arc<RenderPass>,
+        views: Vec<Arc<ImageView>>,
+        extent: [u32; 2],
+    ) -> Result<Arc<Framebuffer>, FramebufferError> {
+        let attachments = render_pass.attachments();
+        if views.len() != attachments.len() {
+            return Err(FramebufferError::AttachmentCount {
+                view_count: views.len(),
+                attachment_count: attachments.len(),
+            });
         }
 
-        println!()
+        for (index, (view, attachment)) in views.iter().zip(attachments).enumerate() {
+            if view.format() != attachment.format {
+                return Err(FramebufferError::FormatMismatch {
+                    attachment: index,
+                    view_format: view.format(),
+                    attachment_format: attachment.format,
+                });
+            }
+
+            if view.image().samples() != attachment.samples {
+                return Err(FramebufferError::SamplesMismatch {
+                    attachment: index,
+                    view_samples: view.image().samples(),
+                    attachment_samples: attachment.samples,
+                });
+            }
+
+            let mip_level = view.subresource_range().mip_levels.start;
+            let image_extent = view.image().extent();
+            let view_extent = [
+                (image_extent[0] >> mip_level).max(1),
+                (image_extent[1] >> mip_level).max(1),
+            ];
+            if view_extent[0] < extent[0] || view_extent[1] < extent[1] {
+                return Err(FramebufferError::ViewTooSmall {
+                    attachment: index,
+                    view_extent,
+                    extent,
+                });
+            }
+        }
+
+        return Framebuffer::new(
+            render_pass.clone(),
+            FramebufferCreateInfo {
+                attachments: views,
+                extent,
+                ..Default::default()
+            },
+        )
+        .map_err(FramebufferError::Vulkan);
+    }
+
+    // The second attachment of a `create_render_pass_with_depth_stencil` framebuffer, cleared with
+    // e.g. `ClearValue::DepthStencil((1.0, 0))`.
+    #[allow(dead_code)]
+    pub fn create_depth_stencil_image(&self, format: Format, extent: [u32; 2]) -> Arc<Image> {
+        self.create_image_or_panic(
+            ImageType::Dim2d,
+            format,
+            [extent[0], extent[1], 1],
+            ImageUsage::DEPTH_STENCIL_ATTACHMENT | ImageUsage::TRANSIENT_ATTACHMENT,
+            MemoryTypeFilter::PREFER_DEVICE,
+        )
+    }
+
+    pub fn load_shader_from_spirv(
+        &self,
+        bytes: &[u8],
+    ) -> Result<Arc<ShaderModule>, ShaderLoadError> {
+        if bytes.len() % 4 != 0 {
+            return Err(ShaderLoadError::InvalidLength(bytes.len()));
+        }
+
+        let words = bytes_to_words(bytes).unwrap();
+
+        // The SPIR-V is only parsed as far as vulkano needs for reflection, so a malformed module
+        // may still be rejected later by the driver or the validation layer.
+        return unsafe {
+            ShaderModule::new(self.device.clone(), ShaderModuleCreateInfo::new(&words))
+        }
+        .map_err(ShaderLoadError::Vulkan);
+    }
+
+    pub fn load_shader_from_path(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<Arc<ShaderModule>, ShaderLoadError> {
+        let bytes = fs::read(path)?;
+
+        return self.load_shader_from_spirv(&bytes);
+    }
+
+    // One cleared and stored color attachment per format, written by fragment shader outputs at
+    // the matching `location`.
+    #[allow(dead_code)]
+    pub fn create_render_pass_mrt(&self, formats: &[Format]) -> Arc<RenderPass> {
+        let max_color_attachments = self
+            .device
+            .physical_device()
+            .properties()
+            .max_color_attachments;
+        assert!(
+            !formats.is_empty() && formats.len() as u32 <= max_color_attachments,
+            "Between 1 and {} color attachments are supported, got {}.",
+            max_color_attachments,
+            formats.len()
+        );
+
+        let attachments = formats
+            .iter()
+            .map(|&format| AttachmentDescription {
+                format,
+                samples: SampleCount::Sample1,
+                load_op: AttachmentLoadOp::Clear,
+                store_op: AttachmentStoreOp::Store,
+                initial_layout: ImageLayout::ColorAttachmentOptimal,
+                final_layout: ImageLayout::ColorAttachmentOptimal,
+                ..Default::default()
+            })
+            .collect();
+
+        let color_attachments = (0..formats.len() as u32)
+            .map(|attachment| {
+                Some(AttachmentReference {
+                    attachment,
+                    layout: ImageLayout::ColorAttachmentOptimal,
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        return RenderPass::new(
+            self.device.clone(),
+            RenderPassCreateInfo {
+                attachments,
+                subpasses: vec![SubpassDescription {
+                    color_attachments,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )
+        .expect("Failed to create render pass.");
+    }
+
+    #[allow(dead_code)]
+    pub fn create_pipeline_stages_layout<T>(
+        &self,
+        load_functions: Vec<T>,
+    ) -> Result<(Vec<PipelineShaderStageCreateInfo>, Arc<PipelineLayout>), PipelineError>
+    where
+        T: Fn(Arc<Device>) -> Result<Arc<ShaderModule>, Validated<VulkanError>>,
+    {
+        return self
+            .create_pipeline_stages_layout_from_modules(&self.load_shader_modules(load_functions));
+    }
+
+    pub fn load_shader_modules<T>(&self, load_functions: Vec<T>) -> Vec<Arc<ShaderModule>>
+    where
+        T: Fn(Arc<Device>) -> Result<Arc<ShaderModule>, Validated<VulkanError>>,
+    {
+        return load_functions
+            .into_iter()
+            .map(|load_function| {
+                load_function(self.device.clone()).expect("Failed to create shader module.")
+            })
+            .collect();
+    }
+
+    // Every stage is entered through `main`, as GLSL requires.
+    pub fn create_pipeline_stages_layout_from_modules(
+        &self,
+        modules: &[Arc<ShaderModule>],
+    ) -> Result<(Vec<PipelineShaderStageCreateInfo>, Arc<PipelineLayout>), PipelineError> {
+        let modules: Vec<(Arc<ShaderModule>, &str)> = modules
+            .iter()
+            .map(|module| (module.clone(), "main"))
+            .collect();
+
+        return self.create_pipeline_stages_layout_with_entry_points(&modules);
+    }
+
+    // For shaders whose entry points aren't called `main`, e.g. compiled from HLSL, or several
+    // stages in one module. Each module is paired with the name of its entry point.
+    pub fn create_pipeline_stages_layout_with_entry_points(
+        &self,
+        modules: &[(Arc<ShaderModule>, &str)],
+    ) -> Result<(Vec<PipelineShaderStageCreateInfo>, Arc<PipelineLayout>), PipelineError> {
+        let mut stages: Vec<PipelineShaderStageCreateInfo> = vec![];
+
+        for (module, entry_point) in modules {
+            let entry_point = module
+                .entry_point(entry_point)
+                .ok_or_else(|| PipelineError::MissingEntryPoint(entry_point.to_string()))?;
+
+            stages.push(PipelineShaderStageCreateInfo::new(entry_point));
+        }
+
+        // Push constant ranges are reflected from the shaders along with the descriptor sets.
+        let layout = PipelineLayout::new(
+            self.device.clone(),
+            PipelineDescriptorSetLayoutCreateInfo::from_stages(
+                stages
+                    .iter()
+                    .collect::<Vec<&PipelineShaderStageCreateInfo>>(),
+            )
+            .into_pipeline_layout_create_info(self.device.clone())
+            .map_err(PipelineError::Layout)?,
+        )?;
+
+        return Ok((stages, layout));
+    }
+
+    #[allow(dead_code)]
+    pub fn create_compute_pipeline(
+        &self,
+        stage: PipelineShaderStageCreateInfo,
+        layout: Arc<PipelineLayout>,
+    ) -> Arc<ComputePipeline> {
+        return ComputePipeline::new(
+            self.device.clone(),
+            None,
+            ComputePipelineCreateInfo::stage_layout(stage, layout),
+        )
+        .expect("Failed to create compute pipeline.");
+    }
+
+    // Matches the attributes of `V` to the inputs of the vertex stage by name.
+    #[allow(dead_code)]
+    pub fn create_vertex_input_state<V: Vertex>(
+        stages: &[PipelineShaderStageCreateInfo],
+    ) -> Result<VertexInputState, PipelineError> {
+        return V::per_vertex()
+            .definition(Self::vertex_stage_inputs(stages))
+            .map_err(PipelineError::VertexInput);
+    }
+
+    // Binding 0 advances per vertex with `V`, binding 1 per instance with `I`.
+    pub fn create_instanced_vertex_input_state<V: Vertex, I: Vertex>(
+        stages: &[PipelineShaderStageCreateInfo],
+    ) -> Result<VertexInputState, PipelineError> {
+        return [V::per_vertex(), I::per_instance()]
+            .definition(Self::vertex_stage_inputs(stages))
+            .map_err(PipelineError::VertexInput);
+    }
+
+    fn vertex_stage_inputs(stages: &[PipelineShaderStageCreateInfo]) -> &ShaderInterface {
+        let vertex_stage = stages
+            .iter()
+            .find(|stage| stage.entry_point.info().execution_model == ExecutionModel::Vertex)
+            .expect("No vertex stage to take the vertex inputs from.");
+
+        return &vertex_stage.entry_point.info().input_interface;
+    }
+
+    // Without a `viewport`, the viewport and scissor are dynamic state, to be set with
+    // `set_viewport_and_scissor` before drawing, so the pipeline can be reused across sizes.
+    // Strip and fan topologies get primitive restart, so an index of `u32::MAX` starts a new
+    // strip. With `PointList`, the vertex shader should write `gl_PointSize`.
+    //
+    // `subpass` is either a `Subpass` of a render pass, or a `PipelineRenderingCreateInfo` listing
+    // attachment formats for drawing between `begin_rendering` and `end_rendering` without one,
+    // which needs the `dynamic_rendering` device feature and is single-sampled.
+    //
+    // Shaders that don't fit the rest of the pipeline are an error rather than a panic, so e.g.
+    // reloaded shaders can be rejected. Options the device wasn't created for still panic.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_graphics_pipeline(
+        &self,
+        (stages, layout): (Vec<PipelineShaderStageCreateInfo>, Arc<PipelineLayout>),
+        vertex_input_state: VertexInputState,
+        viewport: Option<Viewport>,
+        subpass: impl Into<PipelineSubpassType>,
+        rasterization: RasterizationConfig,
+        depth_stencil: DepthStencilConfig,
+        blend_mode: BlendMode,
+        topology: PrimitiveTopology,
+    ) -> Result<Arc<GraphicsPipeline>, PipelineError> {
+        let enabled_features = self.device.enabled_features();
+        let subpass = subpass.into();
+
+        let (depth_stencil_aspects, samples, color_attachment_count) = match subpass {
+            PipelineSubpassType::BeginRenderPass(ref subpass) => (
+                subpass
+                    .subpass_desc()
+                    .depth_stencil_attachment
+                    .as_ref()
+                    .map(|reference| {
+                        subpass.render_pass().attachments()[reference.attachment as usize]
+                            .format
+                            .aspects()
+                    })
+                    .unwrap_or(ImageAspects::empty()),
+                subpass.num_samples().unwrap_or(SampleCount::Sample1),
+                subpass.num_color_attachments(),
+            ),
+            PipelineSubpassType::BeginRendering(ref rendering_info) => {
+                assert!(
+                    enabled_features.dynamic_rendering,
+                    "Rendering without a render pass requires the `dynamic_rendering` device \
+                     feature."
+                );
+
+                (
+                    rendering_info
+                        .depth_attachment_format
+                        .iter()
+                        .chain(rendering_info.stencil_attachment_format.iter())
+                        .fold(ImageAspects::empty(), |aspects, format| {
+                            aspects | format.aspects()
+                        }),
+                    SampleCount::Sample1,
+                    rendering_info.color_attachment_formats.len() as u32,
+                )
+            }
+        };
+
+        assert!(
+            depth_stencil.depth.is_none() || depth_stencil_aspects.intersects(ImageAspects::DEPTH),
+            "The depth test requires a subpass with a depth attachment."
+        );
+        assert!(
+            depth_stencil.stencil.is_none()
+                || depth_stencil_aspects.intersects(ImageAspects::STENCIL),
+            "The stencil test requires a subpass with a stencil attachment."
+        );
+
+        if rasterization.depth_clamp_enable {
+            assert!(
+                enabled_features.depth_clamp,
+                "Depth clamp requires the `depth_clamp` device feature."
+            );
+        }
+
+        if let Some(depth_bias) = rasterization.depth_bias {
+            assert!(
+                depth_bias.clamp == 0.0 || enabled_features.depth_bias_clamp,
+                "A non-zero depth bias clamp requires the `depth_bias_clamp` device feature."
+            );
+        }
+
+        if rasterization.polygon_mode != PolygonMode::Fill {
+            assert!(
+                enabled_features.fill_mode_non_solid,
+                "Line and point polygon modes require the `fill_mode_non_solid` device feature."
+            );
+        }
+
+        let (viewport_state, dynamic_state) = match viewport {
+            Some(viewport) => (
+                ViewportState {
+                    viewports: [viewport].into_iter().collect(),
+                    ..Default::default()
+                },
+                Default::default(),
+            ),
+            None => (
+                ViewportState::default(),
+                [DynamicState::Viewport, DynamicState::Scissor]
+                    .into_iter()
+                    .collect(),
+            ),
+        };
+
+        let pipeline = GraphicsPipeline::new(
+            self.device.clone(),
+            None,
+            GraphicsPipelineCreateInfo {
+                stages: SmallVec::from_vec(stages),
+                vertex_input_state: Some(vertex_input_state),
+                input_assembly_state: Some(InputAssemblyState {
+                    topology,
+                    primitive_restart_enable: matches!(
+                        topology,
+                        PrimitiveTopology::LineStrip
+                            | PrimitiveTopology::TriangleStrip
+                            | PrimitiveTopology::TriangleFan
+                    ),
+                    ..Default::default()
+                }),
+                viewport_state: Some(viewport_state),
+                rasterization_state: Some(RasterizationState {
+                    depth_clamp_enable: rasterization.depth_clamp_enable,
+                    depth_bias: rasterization.depth_bias,
+                    cull_mode: rasterization.cull_mode,
+                    front_face: rasterization.front_face,
+                    polygon_mode: rasterization.polygon_mode,
+                    ..Default::default()
+                }),
+                multisample_state: Some(MultisampleState {
+                    rasterization_samples: samples,
+                    ..Default::default()
+                }),
+                depth_stencil_state: (!depth_stencil_aspects.is_empty()).then(|| {
+                    DepthStencilState {
+                        depth: depth_stencil.depth,
+                        stencil: depth_stencil.stencil.map(|stencil| StencilState {
+                            front: stencil,
+                            back: stencil,
+                        }),
+                        ..Default::default()
+                    }
+                }),
+                color_blend_state: Some(ColorBlendState::with_attachment_states(
+                    color_attachment_count,
+                    blend_mode.attachment_state(),
+                )),
+                dynamic_state,
+                subpass: Some(subpass),
+                ..GraphicsPipelineCreateInfo::layout(layout)
+            },
+        )?;
+
+        return Ok(pipeline);
+    }
+
+    #[allow(dead_code)]
+    pub fn create_compute_descriptor_set(
+        &self,
+        compute_pipeline: Arc<ComputePipeline>,
+        write_descriptor_sets: impl IntoIterator<Item = WriteDescriptorSet>,
+    ) -> Arc<DescriptorSet> {
+        return self
+            .create_compute_descriptor_set_at(compute_pipeline, 0, write_descriptor_sets)
+            .unwrap();
+    }
+
+    #[allow(dead_code)]
+    pub fn create_compute_descriptor_set_at(
+        &self,
+        compute_pipeline: Arc<ComputePipeline>,
+        set_index: usize,
+        write_descriptor_sets: impl IntoIterator<Item = WriteDescriptorSet>,
+    ) -> Result<Arc<DescriptorSet>, DescriptorSetIndexError> {
+        let layout = Self::set_layout(compute_pipeline.layout(), set_index)?;
+
+        return Ok(self.allocate_descriptor_set(layout, write_descriptor_sets));
+    }
+
+    // A 2D image for a compute shader to write through e.g.
+    // `layout(binding = 0, rgba8) uniform writeonly image2D`, with the descriptor write binding it
+    // for `create_compute_descriptor_set`. It can be copied out with `CopyImageToBufferInfo` for
+    // readback. The format must support storage images, which among 8-bit formats usually means
+    // `R8G8B8A8_UNORM` rather than BGRA.
+    #[allow(dead_code)]
+    pub fn create_storage_image(
+        &self,
+        binding: u32,
+        format: Format,
+        extent: [u32; 2],
+    ) -> (Arc<Image>, WriteDescriptorSet) {
+        let image = self.create_image_or_panic(
+            ImageType::Dim2d,
+            format,
+            [extent[0], extent[1], 1],
+            ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC,
+            MemoryTypeFilter::PREFER_DEVICE,
+        );
+        let view = ImageView::new_default(image.clone()).expect("Failed to create image view.");
+
+        return (image, WriteDescriptorSet::image_view(binding, view));
+    }
+
+    #[allow(dead_code)]
+    pub fn create_graphics_descriptor_set(
+        &self,
+        graphics_pipeline: Arc<GraphicsPipeline>,
+        write_descriptor_sets: impl IntoIterator<Item = WriteDescriptorSet>,
+    ) -> Arc<DescriptorSet> {
+        return self
+            .create_graphics_descriptor_set_at(graphics_pipeline, 0, write_descriptor_sets)
+            .unwrap();
+    }
+
+    #[allow(dead_code)]
+    pub fn create_graphics_descriptor_set_at(
+        &self,
+        graphics_pipeline: Arc<GraphicsPipeline>,
+        set_index: usize,
+        write_descriptor_sets: impl IntoIterator<Item = WriteDescriptorSet>,
+    ) -> Result<Arc<DescriptorSet>, DescriptorSetIndexError> {
+        let layout = Self::set_layout(graphics_pipeline.layout(), set_index)?;
+
+        return Ok(self.allocate_descriptor_set(layout, write_descriptor_sets));
+    }
+
+    #[allow(dead_code)]
+    fn set_layout(
+        pipeline_layout: &Arc<PipelineLayout>,
+        set_index: usize,
+    ) -> Result<Arc<DescriptorSetLayout>, DescriptorSetIndexError> {
+        let set_layouts = pipeline_layout.set_layouts();
+
+        return set_layouts
+            .get(set_index)
+            .cloned()
+            .ok_or(DescriptorSetIndexError {
+                set_index,
+                set_count: set_layouts.len(),
+            });
+    }
+
+    #[allow(dead_code)]
+    fn allocate_descriptor_set(
+        &self,
+        layout: Arc<DescriptorSetLayout>,
+        write_descriptor_sets: impl IntoIterator<Item = WriteDescriptorSet>,
+    ) -> Arc<DescriptorSet> {
+        // The allocator starts a new pool whenever the current one is full, so allocation never
+        // runs out.
+        return PersistentDescriptorSet::new(
+            &self.descriptor_set_allocator,
+            layout,
+            write_descriptor_sets,
+            [],
+        )
+        .expect("Failed to create descriptor set.");
+    }
+
+    // Of the pools that have live sets in them, so dropped sets are no longer counted.
+    #[allow(dead_code)]
+    pub fn descriptor_pool_stats(&self) -> DescriptorPoolStats {
+        self.descriptor_set_allocator.stats()
+    }
+
+    #[allow(dead_code)]
+    pub fn dispatch_compute(
+        &self,
+        compute_pipeline: Arc<ComputePipeline>,
+        descriptor_set: Arc<DescriptorSet>,
+        group_counts: [u32; 3],
+    ) {
+        let command_buffer = self.create_command_buffer(
+            |builder| {
+                self.record_dispatch(builder, compute_pipeline, descriptor_set, group_counts);
+            },
+            CommandBufferUsage::OneTimeSubmit,
+        );
+
+        self.execute_then_wait(command_buffer);
+    }
+
+    // Runs a compute shader over `input` and returns its output, for using the GPU without
+    // touching vulkano. The shader reads `input` from a storage buffer at set 0, binding 0 and
+    // writes `output_len` elements to a storage buffer at binding 1, e.g.
+    //
+    //     layout(set = 0, binding = 0) readonly buffer Input { float data[]; } input_data;
+    //     layout(set = 0, binding = 1) writeonly buffer Output { float data[]; } output_data;
+    #[allow(dead_code)]
+    pub fn run_compute<S, T, U>(
+        &self,
+        shader_load_function: S,
+        input: Vec<T>,
+        output_len: u64,
+        group_counts: [u32; 3],
+    ) -> Vec<U>
+    where
+        S: Fn(Arc<Device>) -> Result<Arc<ShaderModule>, Validated<VulkanError>>,
+        T: AnyBitPattern + BufferContents,
+        U: AnyBitPattern + BufferContents,
+    {
+        let (mut stages, layout) = self
+            .create_pipeline_stages_layout(vec![shader_load_function])
+            .unwrap();
+        let compute_pipeline = self.create_compute_pipeline(stages.remove(0), layout);
+
+        let input_buffer = self.upload_to_device_buffer(input, BufferUsage::STORAGE_BUFFER);
+        let output_buffer: Subbuffer<[U]> = Buffer::new_slice(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+            output_len,
+        )
+        .expect("Failed to create buffer.");
+
+        let descriptor_set = self.create_compute_descriptor_set(
+            compute_pipeline.clone(),
+            [
+                WriteDescriptorSet::buffer(0, input_buffer),
+                WriteDescriptorSet::buffer(1, output_buffer.clone()),
+            ],
+        );
+
+        self.dispatch_compute(compute_pipeline, descriptor_set, group_counts);
+
+        return self.download_buffer(&output_buffer);
+    }
+
+    // Records a dispatch into a command buffer that may also draw. The builder tracks every
+    // resource the commands use and inserts the pipeline barrier between a dispatch writing a
+    // buffer and a draw reading it as vertices, so no explicit barrier is needed.
+    #[allow(dead_code)]
+    pub fn record_dispatch(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        compute_pipeline: Arc<ComputePipeline>,
+        descriptor_set: Arc<DescriptorSet>,
+        group_counts: [u32; 3],
+    ) {
+        self.check_group_counts(group_counts);
+
+        builder
+            .bind_pipeline_compute(compute_pipeline.clone())
+            .unwrap()
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                compute_pipeline.layout().clone(),
+                0,
+                descriptor_set,
+            )
+            .unwrap()
+            .dispatch(group_counts)
+            .unwrap();
+    }
+
+    // Checks the data against the layout's push constant ranges before recording, so a mismatch
+    // with the shader's declaration names the offending range instead of a raw validation error.
+    #[allow(dead_code)]
+    pub fn push_constants<Pc: BufferContents>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        pipeline_layout: Arc<PipelineLayout>,
+        offset: u32,
+        data: Pc,
+    ) {
+        let end = offset + size_of::<Pc>() as u32;
+
+        assert!(
+            pipeline_layout
+                .push_constant_ranges()
+                .iter()
+                .any(|range| range.offset <= offset && end <= range.offset + range.size),
+            "Push constants at {}..{} aren't covered by the pipeline layout's ranges {:?}.",
+            offset,
+            end,
+            pipeline_layout.push_constant_ranges()
+        );
+
+        builder
+            .push_constants(pipeline_layout, offset, data)
+            .unwrap();
+    }
+
+    // A pair of queries for `begin_timestamp` and `end_timestamp`, `None` when the graphics queue
+    // family doesn't support timestamps. Each command buffer that's in flight at the same time as
+    // others needs a pair of its own, or they overwrite each other's results.
+    pub fn create_timestamp_query(&self) -> Option<TimestampQuery> {
+        if self.timestamp_valid_bits.is_none() {
+            return None;
+        }
+
+        let query_pool = QueryPool::new(
+            self.device.clone(),
+            QueryPoolCreateInfo {
+                query_count: 2,
+                ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+            },
+        )
+        .expect("Failed to create timestamp query pool.");
+
+        return Some(TimestampQuery { query_pool });
+    }
+
+    // Brackets the commands recorded between these two calls with GPU timestamps, read back with
+    // `gpu_time_nanos`. Only the most recently executed bracket of a query can be read.
+    pub fn begin_timestamp(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        query: &TimestampQuery,
+    ) {
+        unsafe {
+            builder
+                .reset_query_pool(query.query_pool.clone(), 0..2)
+                .unwrap()
+                .write_timestamp(query.query_pool.clone(), 0, PipelineStage::TopOfPipe)
+                .unwrap();
+        }
+    }
+
+    pub fn end_timestamp(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        query: &TimestampQuery,
+    ) {
+        unsafe {
+            builder
+                .write_timestamp(query.query_pool.clone(), 1, PipelineStage::BottomOfPipe)
+                .unwrap();
+        }
+    }
+
+    // `None` when the bracketed work hasn't finished executing.
+    pub fn gpu_time_nanos(&self, query: &TimestampQuery) -> Option<u64> {
+        let mut timestamps = [0u64; 2];
+        let available = query
+            .query_pool
+            .get_results(0..2, &mut timestamps, QueryResultFlags::empty())
+            .unwrap();
+        if !available {
+            return None;
+        }
+
+        // Bits above the valid ones are undefined, and the counter wraps around within them.
+        let valid_bits = self.timestamp_valid_bits.unwrap();
+        let mask = if valid_bits >= 64 {
+            u64::MAX
+        } else {
+            (1 << valid_bits) - 1
+        };
+
+        let timestamp_period = self.device.physical_device().properties().timestamp_period;
+        let ticks = (timestamps[1] & mask).wrapping_sub(timestamps[0] & mask) & mask;
+
+        return Some((ticks as f64 * timestamp_period as f64) as u64);
+    }
+
+    pub fn create_command_buffer<T>(
+        &self,
+        builder_fn: T,
+        usage: CommandBufferUsage,
+    ) -> Arc<PrimaryAutoCommandBuffer>
+    where
+        T: FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>),
+    {
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.graphics_queue.queue_family_index(),
+            usage,
+        )
+        .expect("Failed to create command buffer builder.");
+
+        builder_fn(&mut builder);
+
+        return builder.build().expect("Failed to create command buffer.");
+    }
+
+    // For pipelines created without a fixed viewport, covers the whole `extent` of the
+    // framebuffer. Must be recorded after binding the pipeline.
+    pub fn set_viewport_and_scissor<L>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L>,
+        extent: [u32; 2],
+    ) {
+        let viewport = Viewport {
+            offset: [0.0, 0.0],
+            extent: [extent[0] as f32, extent[1] as f32],
+            depth_range: 0.0..=1.0,
+        };
+        let scissor = Scissor {
+            offset: [0, 0],
+            extent,
+        };
+
+        builder
+            .set_viewport(0, [viewport].into_iter().collect())
+            .unwrap()
+            .set_scissor(0, [scissor].into_iter().collect())
+            .unwrap();
+    }
+
+    // Starts drawing into `view` without a render pass, for pipelines created with a
+    // `PipelineRenderingCreateInfo`. The view is cleared to `clear_value` if one is given and
+    // keeps its contents otherwise. Ended with `end_rendering`.
+    #[allow(dead_code)]
+    pub fn begin_rendering_to_view(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        view: Arc<ImageView>,
+        clear_value: Option<ClearValue>,
+    ) {
+        let load_op = match clear_value {
+            Some(_) => AttachmentLoadOp::Clear,
+            None => AttachmentLoadOp::Load,
+        };
+
+        builder
+            .begin_rendering(RenderingInfo {
+                color_attachments: vec![Some(RenderingAttachmentInfo {
+                    load_op,
+                    store_op: AttachmentStoreOp::Store,
+                    clear_value,
+                    ..RenderingAttachmentInfo::image_view(view)
+                })],
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    // Records commands to run inside `subpass`, for splitting the draws of a render pass across
+    // threads. The allocator keeps a pool per thread, so several of these can be recorded at once.
+    // The framebuffer isn't known ahead of time, so the result works with any that is compatible.
+    #[allow(dead_code)]
+    pub fn create_secondary_command_buffer<T>(
+        &self,
+        subpass: Subpass,
+        builder_fn: T,
+        usage: CommandBufferUsage,
+    ) -> Arc<SecondaryAutoCommandBuffer>
+    where
+        T: FnOnce(&mut AutoCommandBufferBuilder<SecondaryAutoCommandBuffer>),
+    {
+        let mut builder = AutoCommandBufferBuilder::secondary(
+            &self.command_buffer_allocator,
+            self.graphics_queue.queue_family_index(),
+            usage,
+            CommandBufferInheritanceInfo {
+                render_pass: Some(CommandBufferInheritanceRenderPassType::BeginRenderPass(
+                    CommandBufferInheritanceRenderPassInfo {
+                        subpass,
+                        framebuffer: None,
+                    },
+                )),
+                ..Default::default()
+            },
+        )
+        .expect("Failed to create secondary command buffer builder.");
+
+        builder_fn(&mut builder);
+
+        return builder
+            .build()
+            .expect("Failed to create secondary command buffer.");
+    }
+
+    // The render pass must have been begun with `SubpassContents::SecondaryCommandBuffers`, and no
+    // draw commands may be recorded directly in that subpass.
+    #[allow(dead_code)]
+    pub fn execute_secondary_command_buffers(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        command_buffers: &[Arc<SecondaryAutoCommandBuffer>],
+    ) {
+        let command_buffers: Vec<Arc<dyn SecondaryCommandBufferAbstract>> = command_buffers
+            .iter()
+            .map(|command_buffer| command_buffer.clone() as Arc<dyn SecondaryCommandBufferAbstract>)
+            .collect();
+
+        builder
+            .execute_commands_from_vec(command_buffers)
+            .expect("Failed to execute secondary command buffers.");
+    }
+
+    // Begins a render pass on `framebuffer`, runs `draw_fn` to record its draws inline, and ends
+    // it, e.g. from inside a `create_command_buffer` closure. `clear_values` has one entry per
+    // attachment, `None` for those not cleared. With several subpasses, `draw_fn` moves through
+    // them with `next_subpass` and must finish in the last one.
+    pub fn record_render_pass<T>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        framebuffer: Arc<Framebuffer>,
+        clear_values: Vec<Option<ClearValue>>,
+        draw_fn: T,
+    ) where
+        T: FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>),
+    {
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values,
+                    ..RenderPassBeginInfo::framebuffer(framebuffer)
+                },
+                SubpassBeginInfo {
+                    contents: SubpassContents::Inline,
+                    ..Default::default()
+                },
+            )
+            .expect("Failed to begin render pass.");
+
+        draw_fn(builder);
+
+        builder
+            .end_render_pass(SubpassEndInfo::default())
+            .expect("Failed to end render pass.");
+    }
+
+    // Draws the scene twice in one pass over a `create_render_pass_with_depth_stencil` framebuffer,
+    // so that with a lot of overdraw each pixel is still only shaded once. First `depth_pipeline`,
+    // built with `BlendMode::DepthOnly` and `DepthStencilConfig::depth_prepass`, lays down the
+    // nearest depth, then `color_pipeline`, built with `DepthStencilConfig::depth_equal`, shades
+    // only the fragments at that depth. `draw_fn` records the draws, and runs once after each
+    // pipeline is bound. Both vertex stages must compute bit-identical positions for the equal
+    // test to pass, e.g. by sharing the shader and declaring `gl_Position` invariant.
+    #[allow(dead_code)]
+    pub fn record_depth_prepass<T>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        framebuffer: Arc<Framebuffer>,
+        clear_values: Vec<Option<ClearValue>>,
+        depth_pipeline: Arc<GraphicsPipeline>,
+        color_pipeline: Arc<GraphicsPipeline>,
+        mut draw_fn: T,
+    ) where
+        T: FnMut(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>),
+    {
+        self.record_render_pass(builder, framebuffer, clear_values, |builder| {
+            builder.bind_pipeline_graphics(depth_pipeline).unwrap();
+            draw_fn(builder);
+
+            builder.bind_pipeline_graphics(color_pipeline).unwrap();
+            draw_fn(builder);
+        });
+    }
+
+    // Falls back to the graphics queue when the device has no separate transfer family.
+    #[allow(dead_code)]
+    fn transfer_queue(&self) -> &Arc<Queue> {
+        self.transfer_queue.as_ref().unwrap_or(&self.graphics_queue)
+    }
+
+    // Builds a command buffer for `execute_transfer_then_wait`. Resources shared with the graphics
+    // queue must be created with concurrent sharing when a separate transfer queue is in use.
+    #[allow(dead_code)]
+    pub fn create_transfer_command_buffer<T>(
+        &self,
+        builder_fn: T,
+        usage: CommandBufferUsage,
+    ) -> Arc<PrimaryAutoCommandBuffer>
+    where
+        T: FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>),
+    {
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.transfer_queue().queue_family_index(),
+            usage,
+        )
+        .expect("Failed to create command buffer builder.");
+
+        builder_fn(&mut builder);
+
+        return builder.build().expect("Failed to create command buffer.");
+    }
+
+    #[allow(dead_code)]
+    pub fn execute_transfer_then_wait(&self, command_buffer: Arc<PrimaryAutoCommandBuffer>) {
+        sync::now(self.device.clone())
+            .then_execute(self.transfer_queue().clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+    }
+
+    pub fn execute_then_wait(&self, command_buffer: Arc<PrimaryAutoCommandBuffer>) {
+        self.execute_with_fence(command_buffer).wait(None).unwrap();
+    }
+
+    // Chains the command buffers into one queue submission with a single fence, executed in the
+    // given order, so a later one can consume what an earlier one wrote.
+    #[allow(dead_code)]
+    pub fn execute_batch_then_wait(&self, command_buffers: &[Arc<PrimaryAutoCommandBuffer>]) {
+        let mut future = sync::now(self.device.clone()).boxed();
+
+        for command_buffer in command_buffers {
+            future = future
+                .then_execute(self.graphics_queue.clone(), command_buffer.clone())
+                .unwrap()
+                .boxed();
+        }
+
+        future
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+    }
+
+    #[allow(dead_code)]
+    pub fn execute_then_wait_timeout(
+        &self,
+        command_buffer: Arc<PrimaryAutoCommandBuffer>,
+        timeout: Duration,
+    ) -> Result<(), ExecutionError> {
+        let future = sync::now(self.device.clone())
+            .then_execute(self.graphics_queue.clone(), command_buffer)?
+            .then_signal_fence_and_flush()?;
+
+        if let Err(error) = future.wait(Some(timeout)) {
+            // Dropping the future waits for the fence without a timeout, which would hang on the
+            // very GPU we just gave up on. Leaking it is the lesser evil.
+            std::mem::forget(future);
+            return Err(error.into());
+        }
+
+        return Ok(());
+    }
+
+    pub fn execute_with_fence(
+        &self,
+        command_buffer: Arc<PrimaryAutoCommandBuffer>,
+    ) -> FenceSignalFuture<Box<dyn GpuFuture>> {
+        self.execute(command_buffer)
+            .then_signal_fence_and_flush()
+            .unwrap()
+    }
+
+    // Submits without waiting. The returned future keeps the command buffer alive and can be
+    // joined with other submissions before waiting on a fence.
+    pub fn execute(&self, command_buffer: Arc<PrimaryAutoCommandBuffer>) -> Box<dyn GpuFuture> {
+        let future = sync::now(self.device.clone())
+            .then_execute(self.graphics_queue.clone(), command_buffer)
+            .unwrap();
+
+        future.flush().unwrap();
+
+        return future.boxed();
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn _print_physical_devices(&self) {
+        println!(
+            "Found {} physical devices:",
+            self.device
+                .instance()
+                .enumerate_physical_devices()
+                .unwrap()
+                .len()
+        );
+
+        for physical_device in self.device.instance().enumerate_physical_devices().unwrap() {
+            println!(
+                "  Found a physical device with name: {:?},",
+                physical_device.properties().device_name
+            )
+        }
+
+        println!()
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn _print_queue_families(&self) {
+        println!(
+            "Found {} queue families:",
+            self.device
+                .physical_device()
+                .queue_family_properties()
+                .len()
+        );
+
+        for family in self.device.physical_device().queue_family_properties() {
+            println!(
+                "  Found a queue family with flags '{:?}' containing {:?} queue(s),",
+                family.queue_flags, family.queue_count
+            );
+        }
+
+        println!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use vulkano::{
+        buffer::BufferUsage,
+        command_buffer::{
+            AutoCommandBufferBuilder, ClearColorImageInfo, CommandBufferUsage, CopyBufferInfo,
+            CopyImageToBufferInfo, PrimaryAutoCommandBuffer, RenderPassBeginInfo, SubpassBeginInfo,
+            SubpassContents, SubpassEndInfo,
+        },
+        descriptor_set::WriteDescriptorSet,
+        device::Features,
+        format::{ClearValue, Format},
+        image::{view::ImageView, ImageType, ImageUsage, SampleCount},
+        memory::allocator::MemoryTypeFilter,
+        pipeline::{
+            graphics::{
+                input_assembly::PrimitiveTopology, subpass::PipelineRenderingCreateInfo,
+                vertex_input::VertexInputState, viewport::Scissor,
+            },
+            Pipeline, PipelineBindPoint,
+        },
+        render_pass::{AttachmentLoadOp, AttachmentStoreOp, Subpass},
+        shader::ShaderModule,
+    };
+
+    use super::{
+        pipeline::{AttachmentOps, BlendMode, DepthStencilConfig, RasterizationConfig},
+        testing::{
+            create_fullscreen_pipeline, draw_offscreen, fullscreen_vs, render_offscreen,
+            test_processor, test_processor_with,
+        },
+        VulkanProcessorCreateInfo,
+    };
+
+    mod double_cs {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            src: r"
+                #version 460
+
+                layout(local_size_x = 64) in;
+
+                layout(set = 0, binding = 0) readonly buffer Input { float data[]; } input_data;
+                layout(set = 0, binding = 1) writeonly buffer Output { float data[]; } output_data;
+
+                void main() {
+                    uint i = gl_GlobalInvocationID.x;
+                    output_data.data[i] = input_data.data[i] * 2.0;
+                }
+            ",
+        }
+    }
+
+    mod white_fs {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            src: r"
+                #version 460
+
+                layout(location = 0) in vec2 uv;
+                layout(location = 0) out vec4 f_color;
+
+                void main() {
+                    f_color = vec4(1.0);
+                }
+            ",
+        }
+    }
+
+    mod push_color_fs {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            src: r"
+                #version 460
+
+                layout(push_constant) uniform PushConstants { vec4 color; } push_constants;
+
+                layout(location = 0) in vec2 uv;
+                layout(location = 0) out vec4 f_color;
+
+                void main() {
+                    f_color = push_constants.color;
+                }
+            ",
+        }
+    }
+
+    // A small triangle around the origin, moved by the matrix in the uniform buffer.
+    mod mvp_vs {
+        vulkano_shaders::shader! {
+            ty: "vertex",
+            src: r"
+                #version 460
+
+                layout(set = 0, binding = 0) uniform Mvp { mat4 mvp; } mvp;
+
+                layout(location = 0) out vec2 uv;
+
+                const vec2 POSITIONS[3] = vec2[](vec2(-0.2, 0.2), vec2(0.2, 0.2), vec2(0.0, -0.2));
+
+                void main() {
+                    uv = vec2(0.0);
+                    gl_Position = mvp.mvp * vec4(POSITIONS[gl_VertexIndex], 0.0, 1.0);
+                }
+            ",
+        }
+    }
+
+    // Magenta to the first attachment, the interpolated `uv` to the second.
+    mod mrt_fs {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            src: r"
+                #version 460
+
+                layout(location = 0) in vec2 uv;
+                layout(location = 0) out vec4 f_color;
+                layout(location = 1) out vec4 f_uv;
+
+                void main() {
+                    f_color = vec4(1.0, 0.0, 1.0, 1.0);
+                    f_uv = vec4(uv, 0.0, 1.0);
+                }
+            ",
+        }
+    }
+
+    mod gradient_cs {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            src: r"
+                #version 460
+
+                layout(local_size_x = 8, local_size_y = 8) in;
+
+                layout(set = 0, binding = 0, rgba8) uniform writeonly image2D gradient;
+
+                void main() {
+                    ivec2 texel = ivec2(gl_GlobalInvocationID.xy);
+                    vec2 last = vec2(imageSize(gradient) - 1);
+                    imageStore(gradient, texel, vec4(vec2(texel) / last, 0.0, 1.0));
+                }
+            ",
+        }
+    }
+
+    // Two full-screen layers, the first instance behind the second.
+    mod layers_vs {
+        vulkano_shaders::shader! {
+            ty: "vertex",
+            src: r"
+                #version 460
+
+                invariant gl_Position;
+
+                void main() {
+                    vec2 position = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+                    float depth = gl_InstanceIndex == 0 ? 0.75 : 0.25;
+                    gl_Position = vec4(position * 2.0 - 1.0, depth, 1.0);
+                }
+            ",
+        }
+    }
+
+    mod depth_only_fs {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            src: r"
+                #version 460
+
+                void main() {}
+            ",
+        }
+    }
+
+    mod counting_fs {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            src: r"
+                #version 460
+
+                // Otherwise the depth test may run after the shader, which would count the
+                // fragments that then fail it.
+                layout(early_fragment_tests) in;
+
+                layout(location = 0) out vec4 f_color;
+
+                layout(set = 0, binding = 0) buffer Counter { uint fragments; } counter;
+
+                void main() {
+                    atomicAdd(counter.fragments, 1);
+                    f_color = vec4(1.0);
+                }
+            ",
+        }
+    }
+
+    #[test]
+    fn compute_and_graphics_pipelines_come_from_one_processor() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let input: Vec<f32> = (0..128).map(|i| i as f32).collect();
+        let output: Vec<f32> =
+            processor.run_compute(double_cs::load, input.clone(), 128, [2, 1, 1]);
+        assert_eq!(
+            output,
+            input.iter().map(|value| value * 2.0).collect::<Vec<f32>>()
+        );
+
+        let render_pass = processor.create_render_pass(
+            Format::R8G8B8A8_UNORM,
+            SampleCount::Sample1,
+            AttachmentOps::default(),
+        );
+        let pipeline = create_fullscreen_pipeline(&processor, &render_pass, white_fs::load);
+        let pixels = draw_offscreen(&processor, &render_pass, pipeline, [4, 4], |builder| {
+            builder.draw(3, 1, 0, 0).unwrap();
+        });
+        assert!(pixels.iter().all(|&byte| byte == 255));
+    }
+
+    #[test]
+    fn a_thousand_live_descriptor_sets_fit_in_the_pools() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let (mut stages, layout) = processor
+            .create_pipeline_stages_layout(vec![double_cs::load])
+            .unwrap();
+        let pipeline = processor.create_compute_pipeline(stages.remove(0), layout);
+        let [input, output] = [(); 2].map(|_| {
+            processor.create_iter_buffer(
+                vec![0.0f32; 64],
+                BufferUsage::STORAGE_BUFFER,
+                MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            )
+        });
+
+        let descriptor_sets: Vec<_> = (0..1000)
+            .map(|_| {
+                processor.create_compute_descriptor_set(
+                    pipeline.clone(),
+                    [
+                        WriteDescriptorSet::buffer(0, input.clone()),
+                        WriteDescriptorSet::buffer(1, output.clone()),
+                    ],
+                )
+            })
+            .collect();
+
+        let stats = processor.descriptor_pool_stats();
+        assert_eq!(stats.allocated_sets, 1000);
+        assert!(stats.pool_capacity >= 1000);
+
+        // The last set allocated is as usable as the first.
+        processor.dispatch_compute(pipeline, descriptor_sets[999].clone(), [1, 1, 1]);
+
+        drop(descriptor_sets);
+        assert_eq!(processor.descriptor_pool_stats().allocated_sets, 0);
+    }
+
+    #[test]
+    fn depth_prepass_shades_each_pixel_once() {
+        // For the storage buffer the fragments are counted in.
+        let Some(processor) = test_processor_with(VulkanProcessorCreateInfo {
+            device_features: Features {
+                fragment_stores_and_atomics: true,
+                ..Features::empty()
+            },
+            ..Default::default()
+        }) else {
+            return;
+        };
+
+        let render_pass = processor
+            .create_render_pass_with_depth_stencil(Format::R8G8B8A8_UNORM, Format::D16_UNORM);
+        let create_pipeline = |fragment_modules: Vec<Arc<ShaderModule>>,
+                               depth_stencil: DepthStencilConfig,
+                               blend_mode: BlendMode| {
+            let mut modules = processor.load_shader_modules(vec![layers_vs::load]);
+            modules.extend(fragment_modules);
+
+            processor
+                .create_graphics_pipeline(
+                    processor
+                        .create_pipeline_stages_layout_from_modules(&modules)
+                        .unwrap(),
+                    VertexInputState::new(),
+                    None,
+                    Subpass::from(render_pass.clone(), 0).unwrap(),
+                    RasterizationConfig::default(),
+                    depth_stencil,
+                    blend_mode,
+                    PrimitiveTopology::TriangleList,
+                )
+                .unwrap()
+        };
+        let depth_pipeline = create_pipeline(
+            processor.load_shader_modules(vec![depth_only_fs::load]),
+            DepthStencilConfig::depth_prepass(),
+            BlendMode::DepthOnly,
+        );
+        let color_pipeline = create_pipeline(
+            processor.load_shader_modules(vec![counting_fs::load]),
+            DepthStencilConfig::depth_equal(),
+            BlendMode::Opaque,
+        );
+        // Draws the layers back to front with an ordinary depth test, so both are shaded.
+        let single_pass_pipeline = create_pipeline(
+            processor.load_shader_modules(vec![counting_fs::load]),
+            DepthStencilConfig::depth_prepass(),
+            BlendMode::Opaque,
+        );
+
+        let extent = [16, 16];
+        let count_fragments = |prepass: bool| {
+            let counting_pipeline = if prepass {
+                &color_pipeline
+            } else {
+                &single_pass_pipeline
+            };
+            let counter = processor.create_iter_buffer(
+                vec![0u32],
+                BufferUsage::STORAGE_BUFFER,
+                MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+            );
+            let descriptor_set = processor.create_graphics_descriptor_set(
+                counting_pipeline.clone(),
+                [WriteDescriptorSet::buffer(0, counter.clone())],
+            );
+
+            let draw = |builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>| {
+                builder
+                    .bind_descriptor_sets(
+                        PipelineBindPoint::Graphics,
+                        counting_pipeline.layout().clone(),
+                        0,
+                        descriptor_set.clone(),
+                    )
+                    .unwrap();
+                processor.set_viewport_and_scissor(builder, extent);
+                builder.draw(3, 2, 0, 0).unwrap();
+            };
+
+            render_offscreen(
+                &processor,
+                &render_pass,
+                extent,
+                |builder, framebuffer, clear_values| {
+                    if prepass {
+                        processor.record_depth_prepass(
+                            builder,
+                            framebuffer,
+                            clear_values,
+                            depth_pipeline.clone(),
+                            color_pipeline.clone(),
+                            draw,
+                        );
+                    } else {
+                        processor.record_render_pass(
+                            builder,
+                            framebuffer,
+                            clear_values,
+                            |builder| {
+                                builder
+                                    .bind_pipeline_graphics(single_pass_pipeline.clone())
+                                    .unwrap();
+                                draw(builder);
+                            },
+                        );
+                    }
+                },
+            );
+
+            return processor.download_buffer(&counter)[0];
+        };
+
+        let pixels = extent[0] * extent[1];
+        assert_eq!(count_fragments(false), 2 * pixels);
+        assert_eq!(count_fragments(true), pixels);
+    }
+
+    #[test]
+    fn pushed_color_reaches_the_fragment_shader() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let render_pass = processor.create_render_pass(
+            Format::R8G8B8A8_UNORM,
+            SampleCount::Sample1,
+            AttachmentOps::default(),
+        );
+        let pipeline = create_fullscreen_pipeline(&processor, &render_pass, push_color_fs::load);
+        let layout = pipeline.layout().clone();
+
+        let color = [0.25f32, 0.5, 0.75, 1.0];
+        let pixels = draw_offscreen(&processor, &render_pass, pipeline, [4, 4], |builder| {
+            processor.push_constants(builder, layout, 0, color);
+            builder.draw(3, 1, 0, 0).unwrap();
+        });
+
+        let expected = color.map(|channel| (channel * 255.0).round() as u8);
+        for rgba in pixels.chunks_exact(4) {
+            assert!(
+                rgba.iter()
+                    .zip(expected)
+                    .all(|(&actual, expected)| actual.abs_diff(expected) <= 1),
+                "Pixel is {:?}, expected {:?}.",
+                rgba,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn compute_shader_writes_a_gradient_into_a_storage_image() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let (mut stages, layout) = processor
+            .create_pipeline_stages_layout(vec![gradient_cs::load])
+            .unwrap();
+        let pipeline = processor.create_compute_pipeline(stages.remove(0), layout);
+        let size = 16;
+        let (image, write) = processor.create_storage_image(0, Format::R8G8B8A8_UNORM, [size; 2]);
+        let descriptor_set = processor.create_compute_descriptor_set(pipeline.clone(), [write]);
+        processor.dispatch_compute(pipeline, descriptor_set, [size / 8, size / 8, 1]);
+
+        let buffer = processor.create_iter_buffer(
+            vec![0u8; (size * size * 4) as usize],
+            BufferUsage::TRANSFER_DST,
+            MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+        );
+        let command_buffer = processor.create_command_buffer(
+            |builder| {
+                builder
+                    .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                        image,
+                        buffer.clone(),
+                    ))
+                    .unwrap();
+            },
+            CommandBufferUsage::OneTimeSubmit,
+        );
+        processor.execute_then_wait(command_buffer);
+        let pixels = processor.download_buffer(&buffer);
+
+        // Red runs from 0 to 255 across and green down, in steps of 17.
+        for y in 0..size {
+            for x in 0..size {
+                let offset = ((y * size + x) * 4) as usize;
+                let expected = [x as u8 * 17, y as u8 * 17, 0, 255];
+
+                assert!(
+                    pixels[offset..offset + 4]
+                        .iter()
+                        .zip(expected)
+                        .all(|(&actual, expected)| actual.abs_diff(expected) <= 1),
+                    "Texel ({}, {}) is {:?}, expected {:?}.",
+                    x,
+                    y,
+                    &pixels[offset..offset + 4],
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn each_output_is_written_to_its_own_attachment() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let format = Format::R8G8B8A8_UNORM;
+        let render_pass = processor.create_render_pass_mrt(&[format; 2]);
+        let pipeline = create_fullscreen_pipeline(&processor, &render_pass, mrt_fs::load);
+
+        let size = 4;
+        let images = [(); 2].map(|_| {
+            processor.create_image_or_panic(
+                ImageType::Dim2d,
+                format,
+                [size, size, 1],
+                ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+                MemoryTypeFilter::PREFER_DEVICE,
+            )
+        });
+        let buffers = [(); 2].map(|_| {
+            processor.create_iter_buffer(
+                vec![0u8; (size * size * 4) as usize],
+                BufferUsage::TRANSFER_DST,
+                MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+            )
+        });
+        let framebuffer = processor
+            .create_framebuffer(
+                &render_pass,
+                images
+                    .iter()
+                    .map(|image| ImageView::new_default(image.clone()).unwrap())
+                    .collect(),
+                [size, size],
+            )
+            .unwrap();
+
+        let command_buffer = processor.create_command_buffer(
+            |builder| {
+                processor.record_render_pass(
+                    builder,
+                    framebuffer,
+                    vec![Some(ClearValue::Float([0.0; 4])); 2],
+                    |builder| {
+                        builder.bind_pipeline_graphics(pipeline).unwrap();
+                        processor.set_viewport_and_scissor(builder, [size, size]);
+                        builder.draw(3, 1, 0, 0).unwrap();
+                    },
+                );
+
+                for (image, buffer) in images.iter().zip(&buffers) {
+                    builder
+                        .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                            image.clone(),
+                            buffer.clone(),
+                        ))
+                        .unwrap();
+                }
+            },
+            CommandBufferUsage::OneTimeSubmit,
+        );
+        processor.execute_then_wait(command_buffer);
+        let [colors, uvs] = buffers.map(|buffer| processor.download_buffer(&buffer));
+
+        for y in 0..size {
+            for x in 0..size {
+                let offset = ((y * size + x) * 4) as usize;
+                assert_eq!(colors[offset..offset + 4], [255, 0, 255, 255]);
+
+                // Sampled at pixel centers.
+                let expected =
+                    [x, y].map(|i| ((i as f32 + 0.5) / size as f32 * 255.0).round() as u8);
+                assert!(
+                    uvs[offset..offset + 2]
+                        .iter()
+                        .zip(expected)
+                        .all(|(&actual, expected)| actual.abs_diff(expected) <= 1),
+                    "The uv at ({}, {}) is {:?}, expected {:?}.",
+                    x,
+                    y,
+                    &uvs[offset..offset + 2],
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn uniform_matrix_transforms_the_vertices() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let render_pass = processor.create_render_pass(
+            Format::R8G8B8A8_UNORM,
+            SampleCount::Sample1,
+            AttachmentOps::default(),
+        );
+        let modules = processor.load_shader_modules(vec![mvp_vs::load, white_fs::load]);
+        let pipeline = processor
+            .create_graphics_pipeline(
+                processor
+                    .create_pipeline_stages_layout_from_modules(&modules)
+                    .unwrap(),
+                VertexInputState::new(),
+                None,
+                Subpass::from(render_pass.clone(), 0).unwrap(),
+                RasterizationConfig::default(),
+                DepthStencilConfig::default(),
+                BlendMode::Opaque,
+                PrimitiveTopology::TriangleList,
+            )
+            .unwrap();
+
+        // Column-major, a translation by (0.5, 0.5) into the bottom right quarter.
+        let mvp = processor.create_uniform_buffer([
+            [1.0f32, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.5, 0.5, 0.0, 1.0],
+        ]);
+        let descriptor_set = processor
+            .create_graphics_descriptor_set(pipeline.clone(), [WriteDescriptorSet::buffer(0, mvp)]);
+        let layout = pipeline.layout().clone();
+
+        let size = 16;
+        let pixels = draw_offscreen(
+            &processor,
+            &render_pass,
+            pipeline,
+            [size, size],
+            |builder| {
+                builder
+                    .bind_descriptor_sets(PipelineBindPoint::Graphics, layout, 0, descriptor_set)
+                    .unwrap()
+                    .draw(3, 1, 0, 0)
+                    .unwrap();
+            },
+        );
+        let pixel = |x: u32, y: u32| {
+            let offset = ((y * size + x) * 4) as usize;
+            pixels[offset..offset + 4].to_vec()
+        };
+
+        // Now around (0.5, 0.5), covering pixel (12, 12) and clear of the origin.
+        assert_eq!(pixel(12, 12), [255; 4]);
+        assert_eq!(pixel(8, 8), [0; 4]);
+    }
+
+    #[test]
+    fn secondaries_recorded_on_two_threads_draw_in_one_pass() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let render_pass = processor.create_render_pass(
+            Format::R8G8B8A8_UNORM,
+            SampleCount::Sample1,
+            AttachmentOps::default(),
+        );
+        let pipeline = create_fullscreen_pipeline(&processor, &render_pass, push_color_fs::load);
+        let subpass = Subpass::from(render_pass.clone(), 0).unwrap();
+        let extent = [4, 4];
+
+        // Fills the half of the framebuffer starting at `left` with `color`.
+        let record_half = |left: u32, color: [f32; 4]| {
+            processor.create_secondary_command_buffer(
+                subpass.clone(),
+                |builder| {
+                    builder.bind_pipeline_graphics(pipeline.clone()).unwrap();
+                    processor.set_viewport_and_scissor(builder, extent);
+                    let scissor = Scissor {
+                        offset: [left, 0],
+                        extent: [extent[0] / 2, extent[1]],
+                    };
+                    builder
+                        .set_scissor(0, [scissor].into_iter().collect())
+                        .unwrap()
+                        .push_constants(pipeline.layout().clone(), 0, color)
+                        .unwrap()
+                        .draw(3, 1, 0, 0)
+                        .unwrap();
+                },
+                CommandBufferUsage::OneTimeSubmit,
+            )
+        };
+        let record_half = &record_half;
+        let secondaries = thread::scope(|scope| {
+            [
+                (0, [1.0, 0.0, 0.0, 1.0]),
+                (extent[0] / 2, [0.0, 0.0, 1.0, 1.0]),
+            ]
+            .map(|(left, color)| scope.spawn(move || record_half(left, color)))
+            .map(|thread| thread.join().unwrap())
+        });
+
+        let pixels = render_offscreen(
+            &processor,
+            &render_pass,
+            extent,
+            |builder, framebuffer, clear_values| {
+                builder
+                    .begin_render_pass(
+                        RenderPassBeginInfo {
+                            clear_values,
+                            ..RenderPassBeginInfo::framebuffer(framebuffer)
+                        },
+                        SubpassBeginInfo {
+                            contents: SubpassContents::SecondaryCommandBuffers,
+                            ..Default::default()
+                        },
+                    )
+                    .unwrap();
+                processor.execute_secondary_command_buffers(builder, &secondaries);
+                builder.end_render_pass(SubpassEndInfo::default()).unwrap();
+            },
+        );
+
+        for (index, rgba) in pixels.chunks_exact(4).enumerate() {
+            let expected = if (index as u32 % extent[0]) < extent[0] / 2 {
+                [255, 0, 0, 255]
+            } else {
+                [0, 0, 255, 255]
+            };
+            assert_eq!(rgba, expected, "Pixel {}.", index);
+        }
+    }
+
+    #[test]
+    fn each_layer_of_an_array_is_rendered_separately() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let format = Format::R8G8B8A8_UNORM;
+        let render_pass =
+            processor.create_render_pass(format, SampleCount::Sample1, AttachmentOps::default());
+        let pipeline = create_fullscreen_pipeline(&processor, &render_pass, push_color_fs::load);
+
+        let size = 4;
+        let colors = [
+            [1.0f32, 0.0, 0.0, 1.0],
+            [0.0, 1.0, 0.0, 1.0],
+            [0.0, 0.0, 1.0, 1.0],
+            [1.0, 1.0, 1.0, 1.0],
+        ];
+        let image = processor
+            .create_image_array(
+                format,
+                [size, size],
+                colors.len() as u32,
+                ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+                MemoryTypeFilter::PREFER_DEVICE,
+            )
+            .unwrap();
+        let layer_length = (size * size * 4) as usize;
+        let buffer = processor.create_iter_buffer(
+            vec![0u8; layer_length * colors.len()],
+            BufferUsage::TRANSFER_DST,
+            MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+        );
+
+        let command_buffer = processor.create_command_buffer(
+            |builder| {
+                for (layer, color) in colors.iter().enumerate() {
+                    let framebuffer = processor
+                        .create_framebuffer(
+                            &render_pass,
+                            vec![processor.create_layer_view(image.clone(), layer as u32)],
+                            [size, size],
+                        )
+                        .unwrap();
+                    processor.record_render_pass(
+                        builder,
+                        framebuffer,
+                        vec![Some(ClearValue::Float([0.0; 4]))],
+                        |builder| {
+                            builder.bind_pipeline_graphics(pipeline.clone()).unwrap();
+                            processor.set_viewport_and_scissor(builder, [size, size]);
+                            processor.push_constants(builder, pipeline.layout().clone(), 0, *color);
+                            builder.draw(3, 1, 0, 0).unwrap();
+                        },
+                    );
+                }
+
+                // Every layer, one after the other.
+                builder
+                    .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                        image.clone(),
+                        buffer.clone(),
+                    ))
+                    .unwrap();
+            },
+            CommandBufferUsage::OneTimeSubmit,
+        );
+        processor.execute_then_wait(command_buffer);
+        let pixels = processor.download_buffer(&buffer);
+
+        for (layer, (layer_pixels, color)) in
+            pixels.chunks_exact(layer_length).zip(colors).enumerate()
+        {
+            let expected = color.map(|channel| (channel * 255.0) as u8);
+            for rgba in layer_pixels.chunks_exact(4) {
+                assert_eq!(rgba, expected, "Wrong color in layer {}.", layer);
+            }
+        }
+    }
+
+    #[test]
+    fn later_buffers_in_a_batch_see_what_earlier_ones_wrote() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let source = processor.create_iter_buffer(
+            vec![0u32; 64],
+            BufferUsage::TRANSFER_SRC | BufferUsage::TRANSFER_DST,
+            MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+        );
+        let destination = processor.create_iter_buffer(
+            vec![0u32; 64],
+            BufferUsage::TRANSFER_DST,
+            MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+        );
+
+        let fill = processor.create_command_buffer(
+            |builder| {
+                builder.fill_buffer(source.clone(), 7).unwrap();
+            },
+            CommandBufferUsage::OneTimeSubmit,
+        );
+        let copy = processor.create_command_buffer(
+            |builder| {
+                builder
+                    .copy_buffer(CopyBufferInfo::buffers(source.clone(), destination.clone()))
+                    .unwrap();
+            },
+            CommandBufferUsage::OneTimeSubmit,
+        );
+        processor.execute_batch_then_wait(&[fill, copy]);
+
+        assert!(processor
+            .download_buffer(&destination)
+            .iter()
+            .all(|&value| value == 7));
+    }
+
+    #[test]
+    fn loaded_passes_accumulate_additively() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let format = Format::R8G8B8A8_UNORM;
+        let render_pass = processor.create_render_pass(
+            format,
+            SampleCount::Sample1,
+            AttachmentOps {
+                load: AttachmentLoadOp::Load,
+                store: AttachmentStoreOp::Store,
+            },
+        );
+        let mut modules = processor.load_shader_modules(vec![fullscreen_vs::load]);
+        modules.extend(processor.load_shader_modules(vec![push_color_fs::load]));
+        let pipeline = processor
+            .create_graphics_pipeline(
+                processor
+                    .create_pipeline_stages_layout_from_modules(&modules)
+                    .unwrap(),
+                VertexInputState::new(),
+                None,
+                Subpass::from(render_pass.clone(), 0).unwrap(),
+                RasterizationConfig::default(),
+                DepthStencilConfig::default(),
+                BlendMode::Additive,
+                PrimitiveTopology::TriangleList,
+            )
+            .unwrap();
+
+        let size = 4;
+        let image = processor.create_image_or_panic(
+            ImageType::Dim2d,
+            format,
+            [size, size, 1],
+            ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC | ImageUsage::TRANSFER_DST,
+            MemoryTypeFilter::PREFER_DEVICE,
+        );
+        let framebuffer = processor
+            .create_framebuffer(
+                &render_pass,
+                vec![ImageView::new_default(image.clone()).unwrap()],
+                [size, size],
+            )
+            .unwrap();
+        let buffer = processor.create_iter_buffer(
+            vec![0u8; (size * size * 4) as usize],
+            BufferUsage::TRANSFER_DST,
+            MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+        );
+
+        let command_buffer = processor.create_command_buffer(
+            |builder| {
+                // The first pass loads whatever the image held, so it has to be written before.
+                builder
+                    .clear_color_image(ClearColorImageInfo::image(image.clone()))
+                    .unwrap();
+
+                for _ in 0..2 {
+                    processor.record_render_pass(
+                        builder,
+                        framebuffer.clone(),
+                        vec![None],
+                        |builder| {
+                            builder.bind_pipeline_graphics(pipeline.clone()).unwrap();
+                            processor.set_viewport_and_scissor(builder, [size, size]);
+                            processor.push_constants(
+                                builder,
+                                pipeline.layout().clone(),
+                                0,
+                                [0.25f32, 0.25, 0.25, 1.0],
+                            );
+                            builder.draw(3, 1, 0, 0).unwrap();
+                        },
+                    );
+                }
+
+                builder
+                    .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                        image.clone(),
+                        buffer.clone(),
+                    ))
+                    .unwrap();
+            },
+            CommandBufferUsage::OneTimeSubmit,
+        );
+        processor.execute_then_wait(command_buffer);
+
+        // A quarter from each pass, and the alpha cleared to zero is kept by additive blending.
+        let expected = [128, 128, 128, 0];
+        for rgba in processor.download_buffer(&buffer).chunks_exact(4) {
+            assert!(
+                rgba.iter()
+                    .zip(expected)
+                    .all(|(&actual, expected)| actual.abs_diff(expected) <= 1),
+                "Pixel is {:?}, expected {:?}.",
+                rgba,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn one_dynamic_viewport_pipeline_renders_at_two_sizes() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let render_pass = processor.create_render_pass(
+            Format::R8G8B8A8_UNORM,
+            SampleCount::Sample1,
+            AttachmentOps::default(),
+        );
+        let pipeline = create_fullscreen_pipeline(&processor, &render_pass, white_fs::load);
+
+        for extent in [[4, 4], [16, 8]] {
+            let pixels = draw_offscreen(
+                &processor,
+                &render_pass,
+                pipeline.clone(),
+                extent,
+                |builder| {
+                    builder.draw(3, 1, 0, 0).unwrap();
+                },
+            );
+            assert_eq!(pixels.len(), (extent[0] * extent[1] * 4) as usize);
+            assert!(
+                pixels.iter().all(|&byte| byte == 255),
+                "Not covered at {:?}.",
+                extent
+            );
+        }
+    }
+
+    #[test]
+    fn fill_buffer_sets_every_element() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let buffer = processor.create_iter_buffer(
+            vec![0u32; 256],
+            BufferUsage::TRANSFER_DST,
+            MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+        );
+        processor.fill_buffer(&buffer, 0xDEADBEEF);
+
+        assert!(processor
+            .download_buffer(&buffer)
+            .iter()
+            .all(|&value| value == 0xDEADBEEF));
+    }
+
+    #[test]
+    fn compute_limits_are_reported() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        assert!(processor
+            .max_compute_work_group_size()
+            .iter()
+            .all(|&size| size > 0));
+        assert!(processor
+            .max_compute_work_group_count()
+            .iter()
+            .all(|&count| count > 0));
+        assert!(processor.max_compute_work_group_invocations() > 0);
+        if let Some(subgroup_size) = processor.subgroup_size() {
+            assert!(subgroup_size.is_power_of_two(), "{}", subgroup_size);
+        }
+    }
+
+    #[test]
+    fn dynamic_rendering_draws_without_a_render_pass() {
+        let Some(processor) = test_processor_with(VulkanProcessorCreateInfo {
+            device_features: Features {
+                dynamic_rendering: true,
+                ..Features::empty()
+            },
+            ..Default::default()
+        }) else {
+            return;
+        };
+
+        let format = Format::R8G8B8A8_UNORM;
+        let mut modules = processor.load_shader_modules(vec![fullscreen_vs::load]);
+        modules.extend(processor.load_shader_modules(vec![push_color_fs::load]));
+        let pipeline = processor
+            .create_graphics_pipeline(
+                processor
+                    .create_pipeline_stages_layout_from_modules(&modules)
+                    .unwrap(),
+                VertexInputState::new(),
+                None,
+                PipelineRenderingCreateInfo {
+                    color_attachment_formats: vec![Some(format)],
+                    ..Default::default()
+                },
+                RasterizationConfig::default(),
+                DepthStencilConfig::default(),
+                BlendMode::Opaque,
+                PrimitiveTopology::TriangleList,
+            )
+            .unwrap();
+
+        let size = 4;
+        let image = processor.create_image_or_panic(
+            ImageType::Dim2d,
+            format,
+            [size, size, 1],
+            ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+            MemoryTypeFilter::PREFER_DEVICE,
+        );
+        let buffer = processor.create_iter_buffer(
+            vec![0u8; (size * size * 4) as usize],
+            BufferUsage::TRANSFER_DST,
+            MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+        );
+
+        let command_buffer = processor.create_command_buffer(
+            |builder| {
+                processor.begin_rendering_to_view(
+                    builder,
+                    ImageView::new_default(image.clone()).unwrap(),
+                    Some(ClearValue::Float([0.0; 4])),
+                );
+                builder.bind_pipeline_graphics(pipeline.clone()).unwrap();
+                processor.set_viewport_and_scissor(builder, [size, size]);
+                processor.push_constants(
+                    builder,
+                    pipeline.layout().clone(),
+                    0,
+                    [0.0f32, 1.0, 0.0, 1.0],
+                );
+                builder.draw(3, 1, 0, 0).unwrap().end_rendering().unwrap();
+
+                builder
+                    .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                        image.clone(),
+                        buffer.clone(),
+                    ))
+                    .unwrap();
+            },
+            CommandBufferUsage::OneTimeSubmit,
+        );
+        processor.execute_then_wait(command_buffer);
+
+        for rgba in processor.download_buffer(&buffer).chunks_exact(4) {
+            assert_eq!(rgba, [0, 255, 0, 255]);
+        }
+    }
+
+    #[test]
+    fn copy_buffer_copies_every_element() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let data: Vec<u32> = (0..256).collect();
+        let src = processor.create_iter_buffer(
+            data.clone(),
+            BufferUsage::TRANSFER_SRC,
+            MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+        );
+        let dst = processor.create_iter_buffer(
+            vec![0u32; data.len()],
+            BufferUsage::TRANSFER_DST,
+            MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+        );
+        processor.copy_buffer(&src, &dst);
+
+        assert_eq!(processor.download_buffer(&dst), data);
+    }
+
+    #[test]
+    fn two_draws_in_one_recorded_pass() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let render_pass = processor.create_render_pass(
+            Format::R8G8B8A8_UNORM,
+            SampleCount::Sample1,
+            AttachmentOps::default(),
+        );
+        let pipeline = create_fullscreen_pipeline(&processor, &render_pass, push_color_fs::load);
+        let extent = [4, 4];
+
+        let pixels = render_offscreen(
+            &processor,
+            &render_pass,
+            extent,
+            |builder, framebuffer, clear_values| {
+                processor.record_render_pass(builder, framebuffer, clear_values, |builder| {
+                    builder.bind_pipeline_graphics(pipeline.clone()).unwrap();
+                    processor.set_viewport_and_scissor(builder, extent);
+
+                    // Blue everywhere, then red over the left half.
+                    processor.push_constants(
+                        builder,
+                        pipeline.layout().clone(),
+                        0,
+                        [0.0f32, 0.0, 1.0, 1.0],
+                    );
+                    builder.draw(3, 1, 0, 0).unwrap();
+
+                    let scissor = Scissor {
+                        offset: [0, 0],
+                        extent: [extent[0] / 2, extent[1]],
+                    };
+                    builder
+                        .set_scissor(0, [scissor].into_iter().collect())
+                        .unwrap();
+                    processor.push_constants(
+                        builder,
+                        pipeline.layout().clone(),
+                        0,
+                        [1.0f32, 0.0, 0.0, 1.0],
+                    );
+                    builder.draw(3, 1, 0, 0).unwrap();
+                });
+            },
+        );
+
+        for (index, rgba) in pixels.chunks_exact(4).enumerate() {
+            let expected = if (index as u32 % extent[0]) < extent[0] / 2 {
+                [255, 0, 0, 255]
+            } else {
+                [0, 0, 255, 255]
+            };
+            assert_eq!(rgba, expected, "Pixel {}.", index);
+        }
     }
 }
@@ -0,0 +1,578 @@
+use vulkano::{
+    pipeline::graphics::{
+        color_blend::{
+            AttachmentBlend, BlendFactor, BlendOp, ColorBlendAttachmentState, ColorComponents,
+        },
+        depth_stencil::{CompareOp, DepthState, StencilOpState},
+        rasterization::{CullMode, DepthBiasState, FrontFace, PolygonMode},
+    },
+    render_pass::{AttachmentLoadOp, AttachmentStoreOp},
+};
+
+#[derive(Clone, Copy, Default)]
+pub struct RasterizationConfig {
+    // Requires the `depth_clamp` device feature.
+    pub depth_clamp_enable: bool,
+    // A non-zero `clamp` requires the `depth_bias_clamp` device feature.
+    pub depth_bias: Option<DepthBiasState>,
+    // Defaults to no culling.
+    pub cull_mode: CullMode,
+    // Winding is evaluated in framebuffer space, where Y points down.
+    pub front_face: FrontFace,
+    // Anything other than `Fill` requires the `fill_mode_non_solid` device feature.
+    pub polygon_mode: PolygonMode,
+}
+
+// Both tests are disabled by default, and need a render pass from
+// `create_render_pass_with_depth_stencil` whose format has the matching aspect.
+#[derive(Clone, Copy, Default)]
+pub struct DepthStencilConfig {
+    pub depth: Option<DepthState>,
+    // Applied to front and back faces alike. The reference, compare and write masks are static,
+    // so e.g. a mask pass and a masked pass need a pipeline each.
+    pub stencil: Option<StencilOpState>,
+}
+
+impl DepthStencilConfig {
+    // For the first half of a depth prepass, see `VulkanProcessor::record_depth_prepass`. Keeps
+    // the nearest depth of each pixel.
+    #[allow(dead_code)]
+    pub fn depth_prepass() -> Self {
+        DepthStencilConfig {
+            depth: Some(DepthState {
+                write_enable: true,
+                compare_op: CompareOp::Less,
+            }),
+            stencil: None,
+        }
+    }
+
+    // For the color pass after a depth prepass, which only shades the fragments that ended up
+    // nearest. The depth is already final, so it isn't written again.
+    #[allow(dead_code)]
+    pub fn depth_equal() -> Self {
+        DepthStencilConfig {
+            depth: Some(DepthState {
+                write_enable: false,
+                compare_op: CompareOp::Equal,
+            }),
+            stencil: None,
+        }
+    }
+}
+
+// What `create_render_pass` does with the drawn color attachment's contents at the start and end
+// of the pass. `Load` keeps what the image held before, e.g. to accumulate across passes, which
+// needs a framebuffer clear value of `None` and an image that was already written. `DontCare`
+// for `store` suits transient attachments.
+#[derive(Clone, Copy, Debug)]
+pub struct AttachmentOps {
+    pub load: AttachmentLoadOp,
+    pub store: AttachmentStoreOp,
+}
+
+impl Default for AttachmentOps {
+    fn default() -> Self {
+        AttachmentOps {
+            load: AttachmentLoadOp::Clear,
+            store: AttachmentStoreOp::Store,
+        }
+    }
+}
+
+// Applied to every color attachment. The factors assume straight, not premultiplied, alpha from
+// the fragment shader.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    // Overwrites the attachment.
+    #[default]
+    Opaque,
+    // src * src_alpha + dst * (1 - src_alpha), leaving premultiplied colors in the attachment.
+    #[allow(dead_code)]
+    AlphaBlend,
+    // dst + src * src_alpha, e.g. for particles and glows.
+    #[allow(dead_code)]
+    Additive,
+    // src * dst, for darkening, e.g. shadows or tinting.
+    #[allow(dead_code)]
+    Multiply,
+    // Leaves color attachments untouched, for pipelines that only write depth, e.g. a depth
+    // prepass.
+    #[allow(dead_code)]
+    DepthOnly,
+}
+
+impl BlendMode {
+    pub(super) fn attachment_state(self) -> ColorBlendAttachmentState {
+        let blend = match self {
+            BlendMode::Opaque => None,
+            BlendMode::DepthOnly => {
+                return ColorBlendAttachmentState {
+                    blend: None,
+                    color_write_mask: ColorComponents::empty(),
+                    ..Default::default()
+                };
+            }
+            BlendMode::AlphaBlend => Some(AttachmentBlend {
+                src_color_blend_factor: BlendFactor::SrcAlpha,
+                dst_color_blend_factor: BlendFactor::OneMinusSrcAlpha,
+                color_blend_op: BlendOp::Add,
+                src_alpha_blend_factor: BlendFactor::One,
+                dst_alpha_blend_factor: BlendFactor::OneMinusSrcAlpha,
+                alpha_blend_op: BlendOp::Add,
+            }),
+            BlendMode::Additive => Some(AttachmentBlend {
+                src_color_blend_factor: BlendFactor::SrcAlpha,
+                dst_color_blend_factor: BlendFactor::One,
+                color_blend_op: BlendOp::Add,
+                src_alpha_blend_factor: BlendFactor::Zero,
+                dst_alpha_blend_factor: BlendFactor::One,
+                alpha_blend_op: BlendOp::Add,
+            }),
+            BlendMode::Multiply => Some(AttachmentBlend {
+                src_color_blend_factor: BlendFactor::DstColor,
+                dst_color_blend_factor: BlendFactor::Zero,
+                color_blend_op: BlendOp::Add,
+                src_alpha_blend_factor: BlendFactor::Zero,
+                dst_alpha_blend_factor: BlendFactor::One,
+                alpha_blend_op: BlendOp::Add,
+            }),
+        };
+
+        return ColorBlendAttachmentState {
+            blend,
+            ..Default::default()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vulkano::{
+        buffer::BufferUsage,
+        device::Features,
+        format::Format,
+        image::{ImageUsage, SampleCount},
+        memory::allocator::MemoryTypeFilter,
+        pipeline::graphics::{
+            depth_stencil::{CompareOp, StencilOp, StencilOpState, StencilOps},
+            input_assembly::PrimitiveTopology,
+        },
+        render_pass::Subpass,
+    };
+
+    use super::{
+        AttachmentOps, BlendMode, CullMode, DepthStencilConfig, PolygonMode, RasterizationConfig,
+    };
+    use crate::vulkan::{
+        testing::{draw_offscreen, render_offscreen, test_processor, test_processor_with},
+        vertex::ColorVertex,
+        VulkanProcessor, VulkanProcessorCreateInfo,
+    };
+
+    mod half_alpha_vs {
+        vulkano_shaders::shader! {
+            ty: "vertex",
+            src: r"
+                #version 460
+
+                layout(location = 0) in vec2 position;
+                layout(location = 1) in vec3 color;
+
+                layout(location = 0) out vec3 v_color;
+
+                void main() {
+                    v_color = color;
+                    gl_Position = vec4(position, 0.0, 1.0);
+                    // Only read when drawing points.
+                    gl_PointSize = 1.0;
+                }
+            ",
+        }
+    }
+
+    mod half_alpha_fs {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            src: r"
+                #version 460
+
+                layout(location = 0) in vec3 v_color;
+                layout(location = 0) out vec4 f_color;
+
+                void main() {
+                    f_color = vec4(v_color, 0.5);
+                }
+            ",
+        }
+    }
+
+    mod opaque_fs {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            src: r"
+                #version 460
+
+                layout(location = 0) in vec3 v_color;
+                layout(location = 0) out vec4 f_color;
+
+                void main() {
+                    f_color = vec4(v_color, 1.0);
+                }
+            ",
+        }
+    }
+
+    // Draws `positions` in white over the transparent black clear color and returns the RGBA
+    // texels.
+    fn draw_white(
+        processor: &VulkanProcessor,
+        rasterization: RasterizationConfig,
+        topology: PrimitiveTopology,
+        extent: [u32; 2],
+        positions: &[[f32; 2]],
+    ) -> Vec<u8> {
+        let render_pass = processor.create_render_pass(
+            Format::R8G8B8A8_UNORM,
+            SampleCount::Sample1,
+            AttachmentOps::default(),
+        );
+        let mut modules = processor.load_shader_modules(vec![half_alpha_vs::load]);
+        modules.extend(processor.load_shader_modules(vec![opaque_fs::load]));
+        let stages_layout = processor
+            .create_pipeline_stages_layout_from_modules(&modules)
+            .unwrap();
+        let vertex_input_state =
+            VulkanProcessor::create_vertex_input_state::<ColorVertex>(&stages_layout.0).unwrap();
+        let pipeline = processor
+            .create_graphics_pipeline(
+                stages_layout,
+                vertex_input_state,
+                None,
+                Subpass::from(render_pass.clone(), 0).unwrap(),
+                rasterization,
+                DepthStencilConfig::default(),
+                BlendMode::Opaque,
+                topology,
+            )
+            .unwrap();
+
+        let vertex_buffer = processor.create_iter_buffer(
+            positions
+                .iter()
+                .map(|&position| ColorVertex {
+                    position,
+                    color: [1.0; 3],
+                })
+                .collect(),
+            BufferUsage::VERTEX_BUFFER,
+            MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+        );
+
+        return draw_offscreen(processor, &render_pass, pipeline, extent, |builder| {
+            builder
+                .bind_vertex_buffers(0, vertex_buffer)
+                .unwrap()
+                .draw(positions.len() as u32, 1, 0, 0)
+                .unwrap();
+        });
+    }
+
+    // Two triangles covering the full height between `left` and `right`.
+    fn rectangle(left: f32, right: f32, color: [f32; 3]) -> [ColorVertex; 6] {
+        [
+            [left, -1.0],
+            [right, -1.0],
+            [left, 1.0],
+            [right, -1.0],
+            [right, 1.0],
+            [left, 1.0],
+        ]
+        .map(|position| ColorVertex { position, color })
+    }
+
+    #[test]
+    fn alpha_blended_rectangles_mix_where_they_overlap() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let render_pass = processor.create_render_pass(
+            Format::R8G8B8A8_UNORM,
+            SampleCount::Sample1,
+            AttachmentOps::default(),
+        );
+        let mut modules = processor.load_shader_modules(vec![half_alpha_vs::load]);
+        modules.extend(processor.load_shader_modules(vec![half_alpha_fs::load]));
+        let stages_layout = processor
+            .create_pipeline_stages_layout_from_modules(&modules)
+            .unwrap();
+        let vertex_input_state =
+            VulkanProcessor::create_vertex_input_state::<ColorVertex>(&stages_layout.0).unwrap();
+        let pipeline = processor
+            .create_graphics_pipeline(
+                stages_layout,
+                vertex_input_state,
+                None,
+                Subpass::from(render_pass.clone(), 0).unwrap(),
+                RasterizationConfig::default(),
+                DepthStencilConfig::default(),
+                BlendMode::AlphaBlend,
+                PrimitiveTopology::TriangleList,
+            )
+            .unwrap();
+
+        // Red on the left three quarters, then blue on the right three quarters, in one draw so
+        // primitive order fixes which is blended over which.
+        let mut vertices = rectangle(-1.0, 0.5, [1.0, 0.0, 0.0]).to_vec();
+        vertices.extend(rectangle(-0.5, 1.0, [0.0, 0.0, 1.0]));
+        let vertex_buffer = processor.create_iter_buffer(
+            vertices,
+            BufferUsage::VERTEX_BUFFER,
+            MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+        );
+
+        let size = 16;
+        let pixels = draw_offscreen(
+            &processor,
+            &render_pass,
+            pipeline,
+            [size, size],
+            |builder| {
+                builder
+                    .bind_vertex_buffers(0, vertex_buffer)
+                    .unwrap()
+                    .draw(12, 1, 0, 0)
+                    .unwrap();
+            },
+        );
+        let assert_pixel = |x: u32, expected: [u8; 4]| {
+            let offset = ((size / 2) * size + x) as usize * 4;
+            let actual = &pixels[offset..offset + 4];
+            assert!(
+                actual
+                    .iter()
+                    .zip(expected)
+                    .all(|(&actual, expected)| actual.abs_diff(expected) <= 1),
+                "Pixel {} is {:?}, expected {:?}.",
+                x,
+                actual,
+                expected
+            );
+        };
+
+        // Over the transparent clear color each leaves half its color and an alpha of 0.5. Where
+        // they overlap, blue takes half and leaves half of the red below, and alpha reaches 0.75.
+        assert_pixel(1, [128, 0, 0, 128]);
+        assert_pixel(8, [64, 0, 128, 191]);
+        assert_pixel(14, [0, 0, 128, 128]);
+    }
+
+    #[test]
+    fn back_faces_are_culled() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let rasterization = RasterizationConfig {
+            cull_mode: CullMode::Back,
+            ..Default::default()
+        };
+        // The top left half of the framebuffer. Counter-clockwise on screen, the default front
+        // face, when going down the left edge first.
+        let front = [[-1.0, -1.0], [-1.0, 1.0], [1.0, -1.0]];
+        let back = [front[0], front[2], front[1]];
+
+        let pixels = draw_white(
+            &processor,
+            rasterization,
+            PrimitiveTopology::TriangleList,
+            [4, 4],
+            &back,
+        );
+        assert!(pixels.iter().all(|&byte| byte == 0), "{:?}", pixels);
+
+        let pixels = draw_white(
+            &processor,
+            rasterization,
+            PrimitiveTopology::TriangleList,
+            [4, 4],
+            &front,
+        );
+        assert_eq!(pixels[..4], [255; 4]);
+    }
+
+    #[test]
+    fn wireframe_lights_the_edges_and_leaves_the_interior_clear() {
+        let Some(processor) = test_processor_with(VulkanProcessorCreateInfo {
+            device_features: Features {
+                fill_mode_non_solid: true,
+                ..Features::empty()
+            },
+            ..Default::default()
+        }) else {
+            return;
+        };
+
+        let size = 32;
+        // Corners at pixels (4, 4), (4, 28) and (28, 4), so the long edge runs through x + y = 32.
+        let pixels = draw_white(
+            &processor,
+            RasterizationConfig {
+                polygon_mode: PolygonMode::Line,
+                ..Default::default()
+            },
+            PrimitiveTopology::TriangleList,
+            [size, size],
+            &[[-0.75, -0.75], [-0.75, 0.75], [0.75, -0.75]],
+        );
+        let is_lit = |x: u32, y: u32| pixels[((y * size + x) * 4) as usize] == 255;
+
+        // Which of two pixels a line on a pixel boundary lights is up to the implementation.
+        assert!((2..=5).any(|x| is_lit(x, 16)), "The left edge is missing.");
+        assert!(
+            (14..=17).any(|x| is_lit(x, 16)),
+            "The long edge is missing."
+        );
+        assert!(!is_lit(10, 10), "The interior is filled.");
+        assert!(!is_lit(24, 24), "Outside the triangle is lit.");
+    }
+
+    #[test]
+    fn stencil_mask_restricts_a_later_draw() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+        let Some(depth_stencil_format) = [Format::D24_UNORM_S8_UINT, Format::D32_SFLOAT_S8_UINT]
+            .into_iter()
+            .find(|&format| {
+                processor
+                    .supported_image_usage(format)
+                    .intersects(ImageUsage::DEPTH_STENCIL_ATTACHMENT)
+            })
+        else {
+            println!("Skipping, no depth/stencil format is supported.");
+            return;
+        };
+
+        let render_pass = processor
+            .create_render_pass_with_depth_stencil(Format::R8G8B8A8_UNORM, depth_stencil_format);
+        let create_pipeline = |blend_mode: BlendMode, pass_op: StencilOp, compare_op: CompareOp| {
+            let mut modules = processor.load_shader_modules(vec![half_alpha_vs::load]);
+            modules.extend(processor.load_shader_modules(vec![opaque_fs::load]));
+            let stages_layout = processor
+                .create_pipeline_stages_layout_from_modules(&modules)
+                .unwrap();
+            let vertex_input_state =
+                VulkanProcessor::create_vertex_input_state::<ColorVertex>(&stages_layout.0)
+                    .unwrap();
+
+            processor
+                .create_graphics_pipeline(
+                    stages_layout,
+                    vertex_input_state,
+                    None,
+                    Subpass::from(render_pass.clone(), 0).unwrap(),
+                    RasterizationConfig::default(),
+                    DepthStencilConfig {
+                        depth: None,
+                        stencil: Some(StencilOpState {
+                            ops: StencilOps {
+                                fail_op: StencilOp::Keep,
+                                pass_op,
+                                depth_fail_op: StencilOp::Keep,
+                                compare_op,
+                            },
+                            compare_mask: 0xff,
+                            write_mask: 0xff,
+                            reference: 1,
+                        }),
+                    },
+                    blend_mode,
+                    PrimitiveTopology::TriangleList,
+                )
+                .unwrap()
+        };
+        // Sets the stencil to 1 wherever it draws, without touching the color.
+        let mask_pipeline =
+            create_pipeline(BlendMode::DepthOnly, StencilOp::Replace, CompareOp::Always);
+        // Only draws where the stencil is 1.
+        let masked_pipeline = create_pipeline(BlendMode::Opaque, StencilOp::Keep, CompareOp::Equal);
+
+        // The mask covers the left half, the masked draw everything.
+        let mut vertices = rectangle(-1.0, 0.0, [1.0; 3]).to_vec();
+        vertices.extend(rectangle(-1.0, 1.0, [1.0; 3]));
+        let vertex_buffer = processor.create_iter_buffer(
+            vertices,
+            BufferUsage::VERTEX_BUFFER,
+            MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+        );
+
+        let size = 4;
+        let pixels = render_offscreen(
+            &processor,
+            &render_pass,
+            [size, size],
+            |builder, framebuffer, clear_values| {
+                processor.record_render_pass(builder, framebuffer, clear_values, |builder| {
+                    processor.set_viewport_and_scissor(builder, [size, size]);
+                    builder
+                        .bind_vertex_buffers(0, vertex_buffer)
+                        .unwrap()
+                        .bind_pipeline_graphics(mask_pipeline)
+                        .unwrap()
+                        .draw(6, 1, 0, 0)
+                        .unwrap()
+                        .bind_pipeline_graphics(masked_pipeline)
+                        .unwrap()
+                        .draw(6, 1, 6, 0)
+                        .unwrap();
+                });
+            },
+        );
+
+        for (index, rgba) in pixels.chunks_exact(4).enumerate() {
+            let expected = if (index as u32 % size) < size / 2 {
+                [255; 4]
+            } else {
+                [0; 4]
+            };
+            assert_eq!(rgba, expected, "Pixel {}.", index);
+        }
+    }
+
+    #[test]
+    fn point_list_lights_isolated_pixels() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let size = 8;
+        let lit = [[1, 1], [5, 2], [3, 6]];
+        let pixels = draw_white(
+            &processor,
+            RasterizationConfig::default(),
+            PrimitiveTopology::PointList,
+            [size, size],
+            &lit.map(|pixel| pixel.map(|i| (i as f32 + 0.5) / size as f32 * 2.0 - 1.0)),
+        );
+
+        for y in 0..size {
+            for x in 0..size {
+                let offset = ((y * size + x) * 4) as usize;
+                let expected = if lit.contains(&[x, y]) {
+                    [255; 4]
+                } else {
+                    [0; 4]
+                };
+                assert_eq!(
+                    pixels[offset..offset + 4],
+                    expected,
+                    "Pixel ({}, {}).",
+                    x,
+                    y
+                );
+            }
+        }
+    }
+}
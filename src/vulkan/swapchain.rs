@@ -0,0 +1,220 @@
+use std::{ffi::c_void, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use vulkano::{
+    command_buffer::PrimaryAutoCommandBuffer,
+    format::Format,
+    image::{Image, ImageUsage},
+    swapchain::{
+        self, ColorSpace, Surface, SurfaceInfo, Swapchain, SwapchainCreateInfo,
+        SwapchainPresentInfo,
+    },
+    sync::GpuFuture,
+    Validated, VulkanError,
+};
+
+use super::VulkanProcessor;
+
+// How a `Presenter` paces frames, a subset of the Vulkan present modes.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PresentMode {
+    // Waits for vertical blank, so frames never tear and the frame rate is capped to the
+    // display's. The only mode every device supports.
+    #[default]
+    Vsync,
+    // Never tears, but replaces a queued frame with a newer one instead of waiting.
+    Mailbox,
+    // Presents right away, which may tear.
+    Immediate,
+}
+
+impl PresentMode {
+    fn vulkan_present_mode(self) -> swapchain::PresentMode {
+        match self {
+            PresentMode::Vsync => swapchain::PresentMode::Fifo,
+            PresentMode::Mailbox => swapchain::PresentMode::Mailbox,
+            PresentMode::Immediate => swapchain::PresentMode::Immediate,
+        }
+    }
+}
+
+// Renders into the images of a swapchain on a window surface. Requires a processor created with
+// `wayland_presentation`.
+pub struct Presenter {
+    swapchain: Arc<Swapchain>,
+    images: Vec<Arc<Image>>,
+    // Set when presentation reports the swapchain out of date or suboptimal, it's recreated before
+    // the next frame.
+    needs_recreate: bool,
+}
+
+impl VulkanProcessor {
+    // Safety: `display` and `surface` must be a valid `wl_display` and `wl_surface`, which outlive
+    // the returned surface and every swapchain created on it.
+    pub unsafe fn create_wayland_surface(
+        &self,
+        display: *mut c_void,
+        surface: *mut c_void,
+    ) -> Arc<Surface> {
+        Surface::from_wayland(self.device.instance().clone(), display, surface, None)
+            .expect("Failed to create wayland surface.")
+    }
+
+    // The format and color space pairs the surface can present, e.g. to find an HDR one such as
+    // `(R16G16B16A16_SFLOAT, ExtendedSrgbLinear)`. Color spaces beyond `SrgbNonLinear` need the
+    // `ext_swapchain_colorspace` instance extension to be listed.
+    pub fn supported_surface_formats(&self, surface: &Surface) -> Vec<(Format, ColorSpace)> {
+        self.device
+            .physical_device()
+            .surface_formats(surface, SurfaceInfo::default())
+            .expect("Failed to query surface formats.")
+    }
+
+    // With `SrgbNonLinear`, the compositor expects sRGB-encoded values, so an `_SRGB` format
+    // encodes them on write while a `_UNORM` one needs shaders that output them already encoded.
+    // A `present_mode` the surface doesn't support falls back to `PresentMode::Vsync`.
+    pub fn create_presenter(
+        &self,
+        surface: Arc<Surface>,
+        size: (u32, u32),
+        format: Format,
+        color_space: ColorSpace,
+        present_mode: PresentMode,
+    ) -> Presenter {
+        let physical_device = self.device.physical_device();
+
+        assert!(
+            physical_device
+                .surface_support(self.graphics_queue.queue_family_index(), &surface)
+                .unwrap_or(false),
+            "The graphics queue can't present to this surface."
+        );
+
+        assert!(
+            self.supported_surface_formats(&surface)
+                .contains(&(format, color_space)),
+            "The surface doesn't support {:?} in {:?}.",
+            format,
+            color_space
+        );
+
+        let capabilities = physical_device
+            .surface_capabilities(&surface, SurfaceInfo::default())
+            .expect("Failed to query surface capabilities.");
+
+        let supports_present_mode = physical_device
+            .surface_present_modes(&surface, SurfaceInfo::default())
+            .expect("Failed to query surface present modes.")
+            .any(|supported| supported == present_mode.vulkan_present_mode());
+        let present_mode = if supports_present_mode {
+            present_mode
+        } else {
+            println!(
+                "The surface doesn't support {:?} presentation, using {:?}.",
+                present_mode,
+                PresentMode::Vsync
+            );
+            PresentMode::Vsync
+        };
+
+        let (swapchain, images) = Swapchain::new(
+            self.device.clone(),
+            surface,
+            SwapchainCreateInfo {
+                min_image_count: capabilities.min_image_count.max(2),
+                image_format: format,
+                image_color_space: color_space,
+                image_extent: [size.0, size.1],
+                // Transfers are for blitting a frame rendered at a different resolution.
+                image_usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_DST,
+                composite_alpha: capabilities
+                    .supported_composite_alpha
+                    .into_iter()
+                    .next()
+                    .unwrap(),
+                present_mode: present_mode.vulkan_present_mode(),
+                ..Default::default()
+            },
+        )
+        .expect("Failed to create swapchain.");
+
+        return Presenter {
+            swapchain,
+            images,
+            needs_recreate: false,
+        };
+    }
+}
+
+impl Presenter {
+    // Records a frame into the acquired swapchain image with `record` and queues it for
+    // presentation, which also commits the surface. `frame_index` is from `begin_frame`, and the
+    // frame is ended here once submitted. The swapchain is recreated first when `size` changed or
+    // the last frame found it out of date. Returns false if the frame was dropped because the
+    // swapchain went out of date while acquiring.
+    pub fn present<F>(
+        &mut self,
+        processor: &VulkanProcessor,
+        frame_index: usize,
+        size: (u32, u32),
+        record: F,
+    ) -> bool
+    where
+        F: FnOnce(Arc<Image>) -> Arc<PrimaryAutoCommandBuffer>,
+    {
+        if self.needs_recreate || self.swapchain.image_extent() != [size.0, size.1] {
+            self.recreate(size);
+        }
+
+        let (image_index, suboptimal, acquire_future) =
+            match swapchain::acquire_next_image(self.swapchain.clone(), None)
+                .map_err(Validated::unwrap)
+            {
+                Ok(acquired) => acquired,
+                Err(VulkanError::OutOfDate) => {
+                    self.needs_recreate = true;
+                    return false;
+                }
+                Err(error) => panic!("Failed to acquire swapchain image: {}", error),
+            };
+
+        if suboptimal {
+            self.needs_recreate = true;
+        }
+
+        let command_buffer = record(self.images[image_index as usize].clone());
+
+        let future = acquire_future
+            .then_execute(processor.graphics_queue.clone(), command_buffer)
+            .unwrap()
+            .then_swapchain_present(
+                processor.graphics_queue.clone(),
+                SwapchainPresentInfo::swapchain_image_index(self.swapchain.clone(), image_index),
+            )
+            .boxed_send_sync()
+            .then_signal_fence_and_flush();
+
+        // A frame that went out of date isn't ended, its slot goes to the next one.
+        match future.map_err(Validated::unwrap) {
+            Ok(fence) => processor.end_frame_with_fence(frame_index, fence),
+            Err(VulkanError::OutOfDate) => self.needs_recreate = true,
+            Err(error) => panic!("Failed to present frame: {}", error),
+        }
+
+        return true;
+    }
+
+    fn recreate(&mut self, size: (u32, u32)) {
+        let (swapchain, images) = self
+            .swapchain
+            .recreate(SwapchainCreateInfo {
+                image_extent: [size.0, size.1],
+                ..self.swapchain.create_info()
+            })
+            .expect("Failed to recreate swapchain.");
+
+        self.swapchain = swapchain;
+        self.images = images;
+        self.needs_recreate = false;
+    }
+}
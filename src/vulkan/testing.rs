@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use vulkano::{
+    buffer::BufferUsage,
+    command_buffer::{
+        AutoCommandBufferBuilder, CommandBufferUsage, CopyImageToBufferInfo,
+        PrimaryAutoCommandBuffer,
+    },
+    device::Device,
+    format::ClearValue,
+    image::{view::ImageView, ImageAspects, ImageType, ImageUsage},
+    memory::allocator::MemoryTypeFilter,
+    pipeline::{
+        graphics::{input_assembly::PrimitiveTopology, vertex_input::VertexInputState},
+        GraphicsPipeline,
+    },
+    render_pass::{Framebuffer, RenderPass, Subpass},
+    shader::ShaderModule,
+    Validated, VulkanError, VulkanLibrary,
+};
+
+use super::{
+    pipeline::{BlendMode, DepthStencilConfig, RasterizationConfig},
+    VulkanProcessor, VulkanProcessorCreateInfo,
+};
+
+// Covers the framebuffer with one triangle and no vertex buffers, drawn with `draw(3, 1, 0, 0)`.
+// `uv` runs from (0, 0) at the top left to (1, 1) at the bottom right.
+pub(crate) mod fullscreen_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: r"
+            #version 460
+
+            layout(location = 0) out vec2 uv;
+
+            void main() {
+                uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+                gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+            }
+        ",
+    }
+}
+
+// Tests that need a device return early on `None`, so they pass on machines without one, e.g.
+// CI runners with neither a GPU nor a software implementation installed.
+pub(crate) fn test_processor() -> Option<VulkanProcessor> {
+    test_processor_with(VulkanProcessorCreateInfo::default())
+}
+
+// As `test_processor`, also skipping when no device has the features `create_info` asks for.
+pub(crate) fn test_processor_with(
+    create_info: VulkanProcessorCreateInfo,
+) -> Option<VulkanProcessor> {
+    if VulkanLibrary::new().is_err() {
+        println!("Skipping, no Vulkan library is installed.");
+        return None;
+    }
+
+    match VulkanProcessor::new_with_info(create_info) {
+        Ok(processor) => Some(processor),
+        Err(error) => {
+            println!("Skipping, no usable Vulkan device: {}", error);
+            None
+        }
+    }
+}
+
+// A pipeline for `fullscreen_vs` and the given fragment shader, with a dynamic viewport.
+pub(crate) fn create_fullscreen_pipeline<T>(
+    processor: &VulkanProcessor,
+    render_pass: &Arc<RenderPass>,
+    fragment_load_function: T,
+) -> Arc<GraphicsPipeline>
+where
+    T: Fn(Arc<Device>) -> Result<Arc<ShaderModule>, Validated<VulkanError>>,
+{
+    let mut modules = processor.load_shader_modules(vec![fullscreen_vs::load]);
+    modules.extend(processor.load_shader_modules(vec![fragment_load_function]));
+
+    return processor
+        .create_graphics_pipeline(
+            processor
+                .create_pipeline_stages_layout_from_modules(&modules)
+                .unwrap(),
+            VertexInputState::new(),
+            None,
+            Subpass::from(render_pass.clone(), 0).unwrap(),
+            RasterizationConfig::default(),
+            DepthStencilConfig::default(),
+            BlendMode::Opaque,
+            PrimitiveTopology::TriangleList,
+        )
+        .unwrap();
+}
+
+// Renders into a new image for the first attachment of `render_pass`, cleared to zero, and returns
+// its texels. Any further attachment is a depth/stencil one, cleared to a depth of 1.0.
+// `record_fn` records the render pass itself, from the framebuffer and clear values it's given.
+pub(crate) fn render_offscreen<T>(
+    processor: &VulkanProcessor,
+    render_pass: &Arc<RenderPass>,
+    extent: [u32; 2],
+    record_fn: T,
+) -> Vec<u8>
+where
+    T: FnOnce(
+        &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        Arc<Framebuffer>,
+        Vec<Option<ClearValue>>,
+    ),
+{
+    let attachments = render_pass.attachments();
+    let format = attachments[0].format;
+
+    let image = processor.create_image_or_panic(
+        ImageType::Dim2d,
+        format,
+        [extent[0], extent[1], 1],
+        ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+        MemoryTypeFilter::PREFER_DEVICE,
+    );
+    let mut views = vec![ImageView::new_default(image.clone()).unwrap()];
+    let mut clear_values = vec![Some(ClearValue::Float([0.0; 4]))];
+
+    for attachment in &attachments[1..] {
+        let depth_stencil_image = processor.create_depth_stencil_image(attachment.format, extent);
+        views.push(ImageView::new_default(depth_stencil_image).unwrap());
+        clear_values.push(Some(
+            if attachment
+                .format
+                .aspects()
+                .intersects(ImageAspects::STENCIL)
+            {
+                ClearValue::DepthStencil((1.0, 0))
+            } else {
+                ClearValue::Depth(1.0)
+            },
+        ));
+    }
+
+    let framebuffer = processor
+        .create_framebuffer(render_pass, views, extent)
+        .unwrap();
+    let buffer = processor.create_iter_buffer(
+        vec![0u8; (extent[0] * extent[1]) as usize * format.block_size() as usize],
+        BufferUsage::TRANSFER_DST,
+        MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+    );
+
+    let command_buffer = processor.create_command_buffer(
+        |builder| {
+            record_fn(builder, framebuffer, clear_values);
+
+            builder
+                .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(image, buffer.clone()))
+                .unwrap();
+        },
+        CommandBufferUsage::OneTimeSubmit,
+    );
+    processor.execute_then_wait(command_buffer);
+
+    return processor.download_buffer(&buffer);
+}
+
+// As `render_offscreen`, for a single pipeline bound over the whole framebuffer before `draw_fn`
+// records its draws.
+pub(crate) fn draw_offscreen<T>(
+    processor: &VulkanProcessor,
+    render_pass: &Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    extent: [u32; 2],
+    draw_fn: T,
+) -> Vec<u8>
+where
+    T: FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>),
+{
+    render_offscreen(
+        processor,
+        render_pass,
+        extent,
+        |builder, framebuffer, clear_values| {
+            processor.record_render_pass(builder, framebuffer, clear_values, |builder| {
+                builder.bind_pipeline_graphics(pipeline).unwrap();
+                processor.set_viewport_and_scissor(builder, extent);
+                draw_fn(builder);
+            });
+        },
+    )
+}
@@ -0,0 +1,466 @@
+use std::{fs::File, io, path::Path, sync::Arc};
+
+use vulkano::{
+    buffer::BufferUsage,
+    command_buffer::{
+        AutoCommandBufferBuilder, BlitImageInfo, CommandBufferUsage, CopyBufferToImageInfo,
+        ImageBlit, PrimaryAutoCommandBuffer,
+    },
+    descriptor_set::WriteDescriptorSet,
+    format::{Format, FormatFeatures},
+    image::{
+        sampler::{Filter, Sampler, SamplerCreateInfo},
+        view::ImageView,
+        Image, ImageAspects, ImageSubresourceLayers, ImageType, ImageUsage,
+    },
+    memory::allocator::MemoryTypeFilter,
+    pipeline::{GraphicsPipeline, Pipeline},
+};
+
+use super::{descriptors::DescriptorSet, error::ImageError, VulkanProcessor};
+
+#[allow(dead_code)]
+pub struct Texture {
+    pub view: Arc<ImageView>,
+    pub sampler: Arc<Sampler>,
+}
+
+impl Texture {
+    // For use with `create_graphics_descriptor_set`, bound to a `sampler2D` in the shader.
+    #[allow(dead_code)]
+    pub fn write_descriptor_set(&self, binding: u32) -> WriteDescriptorSet {
+        WriteDescriptorSet::image_view_sampler(binding, self.view.clone(), self.sampler.clone())
+    }
+}
+
+impl VulkanProcessor {
+    // Color textures should use `srgb`, so sampling returns linear values. Textures holding data
+    // rather than color, such as normal maps, should not, as the decode would distort the data.
+    // With `mipmaps`, the full mip chain is generated so minified textures don't alias.
+    #[allow(dead_code)]
+    pub fn create_sampled_image_from_file(
+        &self,
+        path: impl AsRef<Path>,
+        srgb: bool,
+        mipmaps: bool,
+    ) -> io::Result<Texture> {
+        let (pixels, extent) = decode_png_rgba(path.as_ref())?;
+
+        let format = if srgb {
+            Format::R8G8B8A8_SRGB
+        } else {
+            Format::R8G8B8A8_UNORM
+        };
+
+        if mipmaps && !self.supports_mipmap_generation(format) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                ImageError::UnsupportedMipmapGeneration(format),
+            ));
+        }
+
+        let staging_buffer = self.create_iter_buffer(
+            pixels,
+            BufferUsage::TRANSFER_SRC,
+            MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+        );
+
+        let extent = [extent[0], extent[1], 1];
+        let (mip_levels, usage) = if mipmaps {
+            (
+                mip_levels_for_extent(extent),
+                ImageUsage::TRANSFER_SRC | ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+            )
+        } else {
+            (1, ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED)
+        };
+
+        let image = self
+            .create_image_with_mip_levels(
+                ImageType::Dim2d,
+                format,
+                extent,
+                mip_levels,
+                usage,
+                MemoryTypeFilter::PREFER_DEVICE,
+            )
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+
+        // Uploaded on the graphics queue, so the image never needs a queue family ownership
+        // transfer before it is sampled.
+        let command_buffer = self.create_command_buffer(
+            |builder| {
+                builder
+                    .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
+                        staging_buffer,
+                        image.clone(),
+                    ))
+                    .unwrap();
+
+                if mipmaps {
+                    self.generate_mipmaps(builder, image.clone()).unwrap();
+                }
+            },
+            CommandBufferUsage::OneTimeSubmit,
+        );
+        self.execute_then_wait(command_buffer);
+
+        let view = ImageView::new_default(image).expect("Failed to create image view.");
+        let sampler = Sampler::new(
+            self.device.clone(),
+            SamplerCreateInfo::simple_repeat_linear(),
+        )
+        .expect("Failed to create sampler.");
+
+        return Ok(Texture { view, sampler });
+    }
+
+    // For a fixed-size array such as `layout(binding = 0) uniform sampler2D textures[N]` in set 0,
+    // indexed in the shader. There must be exactly N textures, as leaving elements unbound needs
+    // the `descriptor_binding_partially_bound` feature.
+    #[allow(dead_code)]
+    pub fn create_texture_array_descriptor_set(
+        &self,
+        graphics_pipeline: Arc<GraphicsPipeline>,
+        binding: u32,
+        textures: &[Texture],
+    ) -> Arc<DescriptorSet> {
+        let set_layouts = graphics_pipeline.layout().set_layouts();
+        let descriptor_count = set_layouts
+            .first()
+            .and_then(|set_layout| set_layout.bindings().get(&binding))
+            .unwrap_or_else(|| panic!("The shaders declare no binding {} in set 0.", binding))
+            .descriptor_count;
+
+        assert!(
+            textures.len() == descriptor_count as usize,
+            "Binding {} is an array of {} textures, got {}.",
+            binding,
+            descriptor_count,
+            textures.len()
+        );
+
+        let elements = textures
+            .iter()
+            .map(|texture| (texture.view.clone(), texture.sampler.clone()));
+
+        return self.create_graphics_descriptor_set(
+            graphics_pipeline,
+            [WriteDescriptorSet::image_view_sampler_array(
+                binding, 0, elements,
+            )],
+        );
+    }
+
+    #[allow(dead_code)]
+    fn supports_mipmap_generation(&self, format: Format) -> bool {
+        self.device
+            .physical_device()
+            .format_properties(format)
+            .expect("Failed to query format properties.")
+            .optimal_tiling_features
+            .contains(
+                FormatFeatures::BLIT_SRC
+                    | FormatFeatures::BLIT_DST
+                    | FormatFeatures::SAMPLED_IMAGE_FILTER_LINEAR,
+            )
+    }
+
+    // Fills every mip level after the first by blitting each level into the next at half the
+    // size. The first level must already be written, and the image needs both transfer usages.
+    // Layout transitions between the blits are handled by the command buffer builder.
+    #[allow(dead_code)]
+    pub fn generate_mipmaps(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image: Arc<Image>,
+    ) -> Result<(), ImageError> {
+        if !self.supports_mipmap_generation(image.format()) {
+            return Err(ImageError::UnsupportedMipmapGeneration(image.format()));
+        }
+
+        let mut extent = image.extent();
+
+        for mip_level in 1..image.mip_levels() {
+            let next_extent = extent.map(|dimension| (dimension / 2).max(1));
+
+            builder
+                .blit_image(BlitImageInfo {
+                    regions: [ImageBlit {
+                        src_subresource: ImageSubresourceLayers {
+                            aspects: ImageAspects::COLOR,
+                            mip_level: mip_level - 1,
+                            array_layers: 0..image.array_layers(),
+                        },
+                        src_offsets: [[0, 0, 0], extent],
+                        dst_subresource: ImageSubresourceLayers {
+                            aspects: ImageAspects::COLOR,
+                            mip_level,
+                            array_layers: 0..image.array_layers(),
+                        },
+                        dst_offsets: [[0, 0, 0], next_extent],
+                        ..Default::default()
+                    }]
+                    .into(),
+                    filter: Filter::Linear,
+                    ..BlitImageInfo::images(image.clone(), image.clone())
+                })
+                .unwrap();
+
+            extent = next_extent;
+        }
+
+        return Ok(());
+    }
+}
+
+// The number of levels in a full mip chain, down to a single texel.
+#[allow(dead_code)]
+pub fn mip_levels_for_extent(extent: [u32; 3]) -> u32 {
+    let max_dimension = extent.into_iter().max().unwrap_or(1).max(1);
+
+    return u32::BITS - max_dimension.leading_zeros();
+}
+
+// Expands every PNG color type and bit depth to 8-bit RGBA.
+#[allow(dead_code)]
+fn decode_png_rgba(path: &Path) -> io::Result<(Vec<u8>, [u32; 2])> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let mut reader = decoder.read_info()?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data)?;
+    data.truncate(info.buffer_size());
+
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => data,
+        png::ColorType::Rgb => data
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => data
+            .chunks_exact(2)
+            .flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]])
+            .collect(),
+        png::ColorType::Grayscale => data.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        png::ColorType::Indexed => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Indexed PNG was not expanded to RGB.",
+            ))
+        }
+    };
+
+    return Ok((pixels, [info.width, info.height]));
+}
+
+#[cfg(test)]
+mod tests {
+    use vulkano::{
+        format::Format,
+        image::SampleCount,
+        pipeline::{Pipeline, PipelineBindPoint},
+    };
+
+    use super::Texture;
+    use crate::{
+        encode_png,
+        vulkan::{
+            pipeline::AttachmentOps,
+            testing::{create_fullscreen_pipeline, draw_offscreen, test_processor},
+            VulkanProcessor,
+        },
+    };
+
+    mod texture_fs {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            src: r"
+                #version 460
+
+                layout(location = 0) in vec2 uv;
+                layout(location = 0) out vec4 f_color;
+
+                layout(set = 0, binding = 0) uniform sampler2D tex;
+
+                void main() {
+                    f_color = texture(tex, uv);
+                }
+            ",
+        }
+    }
+
+    mod texture_array_fs {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            src: r"
+                #version 460
+
+                layout(location = 0) in vec2 uv;
+                layout(location = 0) out vec4 f_color;
+
+                layout(set = 0, binding = 0) uniform sampler2D textures[3];
+
+                // Indexed with constants, as a dynamic index needs the
+                // `shader_sampled_image_array_dynamic_indexing` device feature.
+                void main() {
+                    int column = int(gl_FragCoord.x);
+                    if (column == 0) {
+                        f_color = texture(textures[0], uv);
+                    } else if (column == 1) {
+                        f_color = texture(textures[1], uv);
+                    } else {
+                        f_color = texture(textures[2], uv);
+                    }
+                }
+            ",
+        }
+    }
+
+    // White where `x + y` is even, black elsewhere, with one texel per square.
+    fn checkerboard(size: u32) -> Vec<u8> {
+        (0..size)
+            .flat_map(|y| (0..size).map(move |x| (x + y) % 2 == 0))
+            .flat_map(|white| if white { [255; 4] } else { [0, 0, 0, 255] })
+            .collect()
+    }
+
+    // Covers an `extent` frame with the whole texture, returned as RGBA.
+    fn sample_texture(processor: &VulkanProcessor, texture: &Texture, extent: [u32; 2]) -> Vec<u8> {
+        let render_pass = processor.create_render_pass(
+            Format::R8G8B8A8_UNORM,
+            SampleCount::Sample1,
+            AttachmentOps::default(),
+        );
+        let pipeline = create_fullscreen_pipeline(processor, &render_pass, texture_fs::load);
+        let descriptor_set = processor
+            .create_graphics_descriptor_set(pipeline.clone(), [texture.write_descriptor_set(0)]);
+
+        return draw_offscreen(
+            processor,
+            &render_pass,
+            pipeline.clone(),
+            extent,
+            |builder| {
+                builder
+                    .bind_descriptor_sets(
+                        PipelineBindPoint::Graphics,
+                        pipeline.layout().clone(),
+                        0,
+                        descriptor_set,
+                    )
+                    .unwrap()
+                    .draw(3, 1, 0, 0)
+                    .unwrap();
+            },
+        );
+    }
+
+    #[test]
+    fn checkerboard_is_sampled_at_texel_centers() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkerboard.png");
+        encode_png(&path, (4, 4), &checkerboard(4));
+
+        // Black and white decode to exactly 0.0 and 1.0 either way. With one pixel per texel,
+        // each pixel samples a texel center and the linear filter has nothing to blend, and a
+        // flipped texture would come out inverted.
+        for srgb in [false, true] {
+            let texture = processor
+                .create_sampled_image_from_file(&path, srgb, false)
+                .unwrap();
+
+            assert_eq!(
+                sample_texture(&processor, &texture, [4, 4]),
+                checkerboard(4)
+            );
+        }
+    }
+
+    #[test]
+    fn mipmaps_average_a_minified_checkerboard_to_gray() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkerboard.png");
+        encode_png(&path, (64, 64), &checkerboard(64));
+
+        // How far the red channel strays from the checkerboard's average of 127.5.
+        let max_deviation = |mipmaps: bool| {
+            let texture = processor
+                .create_sampled_image_from_file(&path, false, mipmaps)
+                .unwrap();
+
+            sample_texture(&processor, &texture, [7, 7])
+                .chunks_exact(4)
+                .map(|rgba| (rgba[0] as f32 - 127.5).abs())
+                .fold(0.0, f32::max)
+        };
+
+        // At 7 pixels across, most pixel centers land near a single texel of the full-size level,
+        // so without mipmaps the result is mostly black or white. Every level below the first is
+        // an even gray.
+        let aliased = max_deviation(false);
+        let mipmapped = max_deviation(true);
+        assert!(aliased > 64.0, "{}", aliased);
+        assert!(mipmapped < 8.0, "{}", mipmapped);
+    }
+
+    #[test]
+    fn texture_array_elements_are_sampled_by_index() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let colors: [[u8; 4]; 3] = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]];
+        let textures: Vec<Texture> = colors
+            .iter()
+            .enumerate()
+            .map(|(index, color)| {
+                let path = dir.path().join(format!("{}.png", index));
+                encode_png(&path, (1, 1), color);
+
+                processor
+                    .create_sampled_image_from_file(&path, false, false)
+                    .unwrap()
+            })
+            .collect();
+
+        let render_pass = processor.create_render_pass(
+            Format::R8G8B8A8_UNORM,
+            SampleCount::Sample1,
+            AttachmentOps::default(),
+        );
+        let pipeline = create_fullscreen_pipeline(&processor, &render_pass, texture_array_fs::load);
+        let descriptor_set =
+            processor.create_texture_array_descriptor_set(pipeline.clone(), 0, &textures);
+
+        let pixels = draw_offscreen(
+            &processor,
+            &render_pass,
+            pipeline.clone(),
+            [3, 1],
+            |builder| {
+                builder
+                    .bind_descriptor_sets(
+                        PipelineBindPoint::Graphics,
+                        pipeline.layout().clone(),
+                        0,
+                        descriptor_set,
+                    )
+                    .unwrap()
+                    .draw(3, 1, 0, 0)
+                    .unwrap();
+            },
+        );
+        assert_eq!(pixels, colors.concat());
+    }
+}
@@ -0,0 +1,154 @@
+use bytemuck::AnyBitPattern;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+
+// Any `#[derive(Vertex)]` type works with `create_vertex_input_state`, this one matches
+// `layout(location = 0) in vec2 position; layout(location = 1) in vec3 color;`.
+#[derive(Vertex, AnyBitPattern, Clone, Copy, Debug)]
+#[repr(C)]
+#[allow(dead_code)]
+pub struct ColorVertex {
+    #[format(R32G32_SFLOAT)]
+    pub position: [f32; 2],
+    #[format(R32G32B32_SFLOAT)]
+    pub color: [f32; 3],
+}
+
+#[cfg(test)]
+mod tests {
+    use vulkano::{
+        buffer::BufferUsage, format::Format, image::SampleCount,
+        memory::allocator::MemoryTypeFilter, pipeline::graphics::input_assembly::PrimitiveTopology,
+        render_pass::Subpass,
+    };
+
+    use super::ColorVertex;
+    use crate::vulkan::{
+        pipeline::{AttachmentOps, BlendMode, DepthStencilConfig, RasterizationConfig},
+        testing::{draw_offscreen, test_processor},
+        VulkanProcessor,
+    };
+
+    mod gouraud_vs {
+        vulkano_shaders::shader! {
+            ty: "vertex",
+            src: r"
+                #version 460
+
+                layout(location = 0) in vec2 position;
+                layout(location = 1) in vec3 color;
+
+                layout(location = 0) out vec3 v_color;
+
+                void main() {
+                    v_color = color;
+                    gl_Position = vec4(position, 0.0, 1.0);
+                }
+            ",
+        }
+    }
+
+    mod gouraud_fs {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            src: r"
+                #version 460
+
+                layout(location = 0) in vec3 v_color;
+                layout(location = 0) out vec4 f_color;
+
+                void main() {
+                    f_color = vec4(v_color, 1.0);
+                }
+            ",
+        }
+    }
+
+    #[test]
+    fn triangle_colors_are_interpolated_between_vertices() {
+        let Some(processor) = test_processor() else {
+            return;
+        };
+
+        let render_pass = processor.create_render_pass(
+            Format::R8G8B8A8_UNORM,
+            SampleCount::Sample1,
+            AttachmentOps::default(),
+        );
+        let mut modules = processor.load_shader_modules(vec![gouraud_vs::load]);
+        modules.extend(processor.load_shader_modules(vec![gouraud_fs::load]));
+        let stages_layout = processor
+            .create_pipeline_stages_layout_from_modules(&modules)
+            .unwrap();
+        let vertex_input_state =
+            VulkanProcessor::create_vertex_input_state::<ColorVertex>(&stages_layout.0).unwrap();
+        let pipeline = processor
+            .create_graphics_pipeline(
+                stages_layout,
+                vertex_input_state,
+                None,
+                Subpass::from(render_pass.clone(), 0).unwrap(),
+                RasterizationConfig::default(),
+                DepthStencilConfig::default(),
+                BlendMode::Opaque,
+                PrimitiveTopology::TriangleList,
+            )
+            .unwrap();
+
+        // Red at the top center, green at the bottom right and blue at the bottom left.
+        let vertices = processor.create_iter_buffer(
+            vec![
+                ColorVertex {
+                    position: [0.0, -1.0],
+                    color: [1.0, 0.0, 0.0],
+                },
+                ColorVertex {
+                    position: [1.0, 1.0],
+                    color: [0.0, 1.0, 0.0],
+                },
+                ColorVertex {
+                    position: [-1.0, 1.0],
+                    color: [0.0, 0.0, 1.0],
+                },
+            ],
+            BufferUsage::VERTEX_BUFFER,
+            MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+        );
+
+        let size = 32;
+        let pixels = draw_offscreen(
+            &processor,
+            &render_pass,
+            pipeline,
+            [size, size],
+            |builder| {
+                builder
+                    .bind_vertex_buffers(0, vertices)
+                    .unwrap()
+                    .draw(3, 1, 0, 0)
+                    .unwrap();
+            },
+        );
+        let pixel = |x: u32, y: u32| {
+            let offset = (y * size + x) as usize * 4;
+            <[u8; 4]>::try_from(&pixels[offset..offset + 4]).unwrap()
+        };
+
+        // Next to each corner its own color dominates, by over 90% of its weight.
+        let [r, g, b, _] = pixel(16, 1);
+        assert!(r > 230 && g < 25 && b < 25, "{:?}", (r, g, b));
+        let [r, g, b, _] = pixel(30, 30);
+        assert!(g > 230 && r < 25 && b < 25, "{:?}", (r, g, b));
+        let [r, g, b, _] = pixel(1, 30);
+        assert!(b > 230 && r < 25 && g < 25, "{:?}", (r, g, b));
+
+        // The centroid is an even mix.
+        let [r, g, b, a] = pixel(16, 21);
+        for channel in [r, g, b] {
+            assert!((70..=100).contains(&channel), "{:?}", (r, g, b));
+        }
+        assert_eq!(a, 255);
+
+        // Outside the triangle the clear color is untouched.
+        assert_eq!(pixel(1, 1), [0, 0, 0, 0]);
+    }
+}
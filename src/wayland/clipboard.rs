@@ -0,0 +1,79 @@
+use std::{fs::File, io::Write, thread};
+
+use wayland_client::{
+    event_created_child,
+    protocol::{
+        wl_data_device::{self, WlDataDevice},
+        wl_data_offer::WlDataOffer,
+        wl_data_source::{self, WlDataSource},
+    },
+    Connection, Dispatch, QueueHandle,
+};
+
+use super::State;
+
+// `UTF8_STRING` is what X11 clients ask for through Xwayland.
+pub(super) const CLIPBOARD_MIME_TYPES: [&str; 2] = ["text/plain;charset=utf-8", "UTF8_STRING"];
+
+// Only ever sets the selection, offers from other clients are destroyed unread.
+impl Dispatch<WlDataDevice, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlDataDevice,
+        event: wl_data_device::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_data_device::Event::Selection { id: Some(offer) } => offer.destroy(),
+            wl_data_device::Event::Enter {
+                id: Some(offer), ..
+            } => offer.destroy(),
+            _ => (),
+        }
+    }
+
+    event_created_child!(State, WlDataDevice, [
+        wl_data_device::EVT_DATA_OFFER_OPCODE => (WlDataOffer, ()),
+    ]);
+}
+
+impl Dispatch<WlDataSource, ()> for State {
+    fn event(
+        state: &mut Self,
+        proxy: &WlDataSource,
+        event: wl_data_source::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_data_source::Event::Send { mime_type, fd } => {
+                let text = match state.clipboard {
+                    Some((ref source, ref text)) if source == proxy => text.clone(),
+                    _ => return,
+                };
+                if !CLIPBOARD_MIME_TYPES.contains(&mime_type.as_str()) {
+                    return;
+                }
+
+                // Written from another thread, as the reader may be this client's own dispatch
+                // loop, or just slow.
+                thread::spawn(move || {
+                    if let Err(error) = File::from(fd).write_all(text.as_bytes()) {
+                        println!("Failed to send the clipboard contents: {}", error);
+                    }
+                });
+            }
+            // Another client took the selection.
+            wl_data_source::Event::Cancelled => {
+                if matches!(state.clipboard, Some((ref source, _)) if source == proxy) {
+                    state.clipboard = None;
+                }
+                proxy.destroy();
+            }
+            _ => (),
+        }
+    }
+}
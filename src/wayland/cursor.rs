@@ -0,0 +1,85 @@
+use wayland_protocols::wp::cursor_shape::v1::client::wp_cursor_shape_device_v1::Shape;
+
+use super::Seat;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum CursorMode {
+    // The compositor's default arrow, or whatever it shows without `wp_cursor_shape_manager_v1`.
+    #[default]
+    Default,
+    #[allow(dead_code)]
+    Hidden,
+    // A CSS cursor name such as "pointer", "text" or "crosshair", which needs
+    // `wp_cursor_shape_manager_v1`.
+    #[allow(dead_code)]
+    Named(String),
+}
+
+// In the order of the protocol's `shape` enum, which starts at 1.
+const SHAPE_NAMES: [&str; 34] = [
+    "default",
+    "context-menu",
+    "help",
+    "pointer",
+    "progress",
+    "wait",
+    "cell",
+    "crosshair",
+    "text",
+    "vertical-text",
+    "alias",
+    "copy",
+    "move",
+    "no-drop",
+    "not-allowed",
+    "grab",
+    "grabbing",
+    "e-resize",
+    "n-resize",
+    "ne-resize",
+    "nw-resize",
+    "s-resize",
+    "se-resize",
+    "sw-resize",
+    "w-resize",
+    "ew-resize",
+    "ns-resize",
+    "nesw-resize",
+    "nwse-resize",
+    "col-resize",
+    "row-resize",
+    "all-scroll",
+    "zoom-in",
+    "zoom-out",
+];
+
+pub(super) fn shape_from_name(name: &str) -> Option<Shape> {
+    let index = SHAPE_NAMES
+        .iter()
+        .position(|&shape_name| shape_name == name)?;
+    Shape::try_from(index as u32 + 1).ok()
+}
+
+impl Seat {
+    // Cursors are only set for the pointer's latest `wl_pointer::enter`, so this has to run again
+    // on every enter.
+    pub(super) fn apply_cursor(&self, cursor_mode: &CursorMode) {
+        let (Some(pointer), Some(serial)) = (self.pointer.as_ref(), self.pointer_enter_serial)
+        else {
+            return;
+        };
+
+        let shape = match cursor_mode {
+            CursorMode::Default => Shape::Default,
+            CursorMode::Hidden => {
+                pointer.set_cursor(serial, None, 0, 0);
+                return;
+            }
+            CursorMode::Named(name) => shape_from_name(name).unwrap_or(Shape::Default),
+        };
+
+        if let Some(ref cursor_shape_device) = self.cursor_shape_device {
+            cursor_shape_device.set_shape(serial, shape);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct FrameContext {
+    #[allow(dead_code)]
+    pub index: u64,
+    // Time since the first frame.
+    #[allow(dead_code)]
+    pub elapsed: Duration,
+    // Time since the previous frame, zero for the first one.
+    pub delta: Duration,
+    // Measured over roughly the last second, zero until the first second has passed.
+    #[allow(dead_code)]
+    pub fps: f32,
+}
+
+// In buffer pixels, with the origin at the top left.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DamageRegion {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+pub(super) struct FrameClock {
+    start: Instant,
+    last: Instant,
+    last_delta: Duration,
+    index: u64,
+    fps: f32,
+    fps_window_start: Instant,
+    fps_window_frames: u32,
+}
+
+const FPS_WINDOW: Duration = Duration::from_secs(1);
+
+impl FrameClock {
+    pub(super) fn new() -> Self {
+        let now = Instant::now();
+
+        return FrameClock {
+            start: now,
+            last: now,
+            last_delta: Duration::ZERO,
+            index: 0,
+            fps: 0.0,
+            fps_window_start: now,
+            fps_window_frames: 0,
+        };
+    }
+
+    pub(super) fn tick(&mut self) -> FrameContext {
+        let now = Instant::now();
+
+        self.fps_window_frames += 1;
+        let window = now - self.fps_window_start;
+        if window >= FPS_WINDOW {
+            self.fps = self.fps_window_frames as f32 / window.as_secs_f32();
+            self.fps_window_start = now;
+            self.fps_window_frames = 0;
+        }
+
+        let context = FrameContext {
+            index: self.index,
+            elapsed: now - self.start,
+            delta: now - self.last,
+            fps: self.fps,
+        };
+
+        self.last = now;
+        self.last_delta = context.delta;
+        self.index += 1;
+
+        return context;
+    }
+
+    pub(super) fn fps(&self) -> f32 {
+        self.fps
+    }
+
+    #[allow(dead_code)]
+    pub(super) fn frame_time(&self) -> Duration {
+        self.last_delta
+    }
+
+    // Sleeps until a frame interval at `max_fps` has passed since the previous frame. Frames that
+    // already took longer than that aren't delayed any further.
+    pub(super) fn throttle(&self, max_fps: u32) {
+        let next_frame = self.last + Duration::from_secs_f64(1.0 / max_fps as f64);
+        let now = Instant::now();
+
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        }
+    }
+}
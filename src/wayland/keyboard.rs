@@ -0,0 +1,91 @@
+use std::os::fd::OwnedFd;
+
+use xkbcommon::xkb::{self, Keycode, Keysym};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct KeyEvent {
+    pub keysym: Keysym,
+    // The text the key produces under the current layout and modifiers, empty for keys like arrows.
+    #[allow(dead_code)]
+    pub utf8: String,
+    pub pressed: bool,
+    #[allow(dead_code)]
+    pub modifiers: Modifiers,
+}
+
+pub(super) struct Keyboard {
+    context: xkb::Context,
+    state: Option<xkb::State>,
+    modifiers: Modifiers,
+}
+
+impl Keyboard {
+    pub(super) fn new() -> Self {
+        Keyboard {
+            context: xkb::Context::new(xkb::CONTEXT_NO_FLAGS),
+            state: None,
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    pub(super) fn load_keymap(&mut self, fd: OwnedFd, size: u32) {
+        // Safety: the fd and size come straight from the compositor's keymap event.
+        let keymap = unsafe {
+            xkb::Keymap::new_from_fd(
+                &self.context,
+                fd,
+                size as usize,
+                xkb::KEYMAP_FORMAT_TEXT_V1,
+                xkb::KEYMAP_COMPILE_NO_FLAGS,
+            )
+        };
+
+        match keymap {
+            Ok(Some(keymap)) => self.state = Some(xkb::State::new(&keymap)),
+            _ => println!("Warning: failed to load the compositor's keymap."),
+        }
+    }
+
+    pub(super) fn update_modifiers(
+        &mut self,
+        depressed: u32,
+        latched: u32,
+        locked: u32,
+        group: u32,
+    ) {
+        let Some(ref mut state) = self.state else {
+            return;
+        };
+
+        state.update_mask(depressed, latched, locked, 0, 0, group);
+
+        let is_active = |name: &str| state.mod_name_is_active(name, xkb::STATE_MODS_EFFECTIVE);
+        self.modifiers = Modifiers {
+            shift: is_active(xkb::MOD_NAME_SHIFT),
+            ctrl: is_active(xkb::MOD_NAME_CTRL),
+            alt: is_active(xkb::MOD_NAME_ALT),
+            logo: is_active(xkb::MOD_NAME_LOGO),
+        };
+    }
+
+    // Wayland sends evdev scancodes, which are offset by 8 from xkb keycodes.
+    pub(super) fn translate(&self, key: u32, pressed: bool) -> Option<KeyEvent> {
+        let state = self.state.as_ref()?;
+        let keycode = Keycode::new(key + 8);
+
+        Some(KeyEvent {
+            keysym: state.key_get_one_sym(keycode),
+            utf8: state.key_get_utf8(keycode),
+            pressed,
+            modifiers: self.modifiers,
+        })
+    }
+}
@@ -1,36 +1,159 @@
-use std::{fs::File, os::fd::AsFd};
+use std::{
+    collections::HashMap,
+    ffi::c_void,
+    fs::File,
+    io::{self, Seek, SeekFrom},
+    thread,
+    time::Duration,
+};
 
-use settings::{NAME, SIZE};
+use clipboard::CLIPBOARD_MIME_TYPES;
+use cursor::shape_from_name;
+use frame::{DamageRegion, FrameClock, FrameContext};
+use keyboard::{KeyEvent, Keyboard};
+use pointer::PointerEvent;
+use settings::{APP_ID, NAME};
 use wayland_client::{
+    backend::{ObjectId, WaylandError},
     delegate_noop,
     protocol::{
-        wl_buffer::WlBuffer,
+        wl_buffer::{self, WlBuffer},
+        wl_callback::{self, WlCallback},
         wl_compositor::WlCompositor,
-        wl_keyboard,
+        wl_data_device::WlDataDevice,
+        wl_data_device_manager::WlDataDeviceManager,
+        wl_data_offer::WlDataOffer,
+        wl_data_source::WlDataSource,
+        wl_keyboard, wl_pointer,
+        wl_region::WlRegion,
         wl_registry::{Event, WlRegistry},
         wl_seat::{self, Capability, WlSeat},
-        wl_shm::{Format, WlShm},
+        wl_shm::WlShm,
         wl_shm_pool::WlShmPool,
         wl_surface::WlSurface,
     },
-    Connection, Dispatch, EventQueue, QueueHandle, WEnum,
+    Connection, Dispatch, DispatchError, EventQueue, Proxy, QueueHandle, WEnum,
 };
-use wayland_protocols::xdg::shell::client::{
-    xdg_surface::{self, XdgSurface},
-    xdg_toplevel::{self, XdgToplevel},
-    xdg_wm_base::{self, XdgWmBase},
+use wayland_protocols::{
+    wp::cursor_shape::v1::client::{
+        wp_cursor_shape_device_v1::WpCursorShapeDeviceV1,
+        wp_cursor_shape_manager_v1::WpCursorShapeManagerV1,
+    },
+    xdg::shell::client::{
+        xdg_surface::{self, XdgSurface},
+        xdg_toplevel::{self, XdgToplevel},
+        xdg_wm_base::{self, XdgWmBase},
+    },
 };
+use window::{ShmBuffer, Window};
+use xkbcommon::xkb::Keysym;
 
+pub use cursor::CursorMode;
+pub use window::WindowId;
+
+mod clipboard;
+mod cursor;
+pub mod frame;
+pub mod keyboard;
+pub mod pointer;
 pub mod settings;
+mod window;
+
+const BUFFER_COUNT: usize = 2;
+
+// The oldest versions of the optional globals the client works with. Requests from later versions,
+// such as `release`, are only sent when the bound version has them.
+const SEAT_VERSION: u32 = 1;
+const DATA_DEVICE_MANAGER_VERSION: u32 = 1;
+const CURSOR_SHAPE_MANAGER_VERSION: u32 = 1;
+
+// Transient dispatch errors are retried after this, doubled on each consecutive failure.
+const DISPATCH_RETRY_DELAY: Duration = Duration::from_millis(10);
+const MAX_DISPATCH_RETRIES: u32 = 5;
+
+struct Seat {
+    seat: WlSeat,
+    keyboard: Option<wl_keyboard::WlKeyboard>,
+    pointer: Option<wl_pointer::WlPointer>,
+    // The serial of the pointer's latest `wl_pointer::enter`, while it's over one of the windows.
+    pointer_enter_serial: Option<u32>,
+    // Created for the pointer when the compositor has `wp_cursor_shape_manager_v1`.
+    cursor_shape_device: Option<WpCursorShapeDeviceV1>,
+    // Created once both the seat and `wl_data_device_manager` are bound.
+    data_device: Option<WlDataDevice>,
+}
+
+impl Seat {
+    // Each of these only has a `release` request from a certain version, older ones can only be
+    // dropped.
+    fn release_keyboard(&mut self) {
+        if let Some(keyboard) = self.keyboard.take() {
+            if keyboard.version() >= 3 {
+                keyboard.release();
+            }
+        }
+    }
+
+    fn release_pointer(&mut self) {
+        self.pointer_enter_serial = None;
+
+        if let Some(cursor_shape_device) = self.cursor_shape_device.take() {
+            cursor_shape_device.destroy();
+        }
+
+        if let Some(pointer) = self.pointer.take() {
+            if pointer.version() >= 3 {
+                pointer.release();
+            }
+        }
+    }
+
+    fn release(mut self) {
+        self.release_keyboard();
+        self.release_pointer();
+
+        if let Some(data_device) = self.data_device.take() {
+            if data_device.version() >= 2 {
+                data_device.release();
+            }
+        }
+
+        if self.seat.version() >= 5 {
+            self.seat.release();
+        }
+    }
+}
 
 struct State {
-    running: bool,
-    base_surface: Option<WlSurface>,
-    buffer_file: File,
-    buffer: Option<WlBuffer>,
+    // Every advertised global with its interface and version, keyed by the registry name, which
+    // `wl_registry::global_remove` refers to.
+    globals: HashMap<u32, (String, u32)>,
+    // Set once the globals from the initial scan are bound.
+    registry_scanned: bool,
+    compositor: Option<WlCompositor>,
+    shm: Option<WlShm>,
+    seats: HashMap<u32, Seat>,
     wm_base: Option<XdgWmBase>,
-    xdg_surface: Option<(XdgSurface, XdgToplevel)>,
-    configured: bool,
+    // Keyed by the id of each window's `wl_surface`, which its other objects carry as user data.
+    windows: HashMap<ObjectId, Window>,
+    keyboard: Keyboard,
+    // The surface holding keyboard focus, which Escape closes.
+    keyboard_focus: Option<ObjectId>,
+    // The registry name of the focused seat and its latest keyboard serial, which setting the
+    // clipboard requires. Cleared when focus leaves.
+    keyboard_serial: Option<(u32, u32)>,
+    data_device_manager: Option<WlDataDeviceManager>,
+    // The source currently offering the clipboard, and its text.
+    clipboard: Option<(WlDataSource, String)>,
+    cursor_shape_manager: Option<WpCursorShapeManagerV1>,
+    // Applied on every `wl_pointer::enter`.
+    cursor_mode: CursorMode,
+    // Drained by `WaylandClient::run` into the key handler after each dispatch.
+    key_events: Vec<KeyEvent>,
+    pointer_inside: bool,
+    pointer_position: (f64, f64),
+    // Drained by `WaylandClient::run` into the pointer handler after each dispatch.
+    pointer_events: Vec<PointerEvent>,
 }
 
 impl Dispatch<WlRegistry, ()> for State {
@@ -48,60 +171,39 @@ impl Dispatch<WlRegistry, ()> for State {
                 interface,
                 version,
             } => {
-                match interface.as_str() {
-                    "wl_compositor" => {
-                        let wl_compositor =
-                            proxy.bind::<WlCompositor, _, _>(name, version, queue_handle, ());
+                state.globals.insert(name, (interface.clone(), version));
 
-                        let surface = wl_compositor.create_surface(queue_handle, ());
-                        state.base_surface = Some(surface);
+                // Globals from the initial scan are bound by `WaylandClient::connect` once all of
+                // them are known, later ones, e.g. a seat that was plugged in, right away.
+                if state.registry_scanned {
+                    state.bind_global(proxy, name, &interface, version, queue_handle);
+                }
+            }
+            Event::GlobalRemove { name } => {
+                let Some((interface, _)) = state.globals.remove(&name) else {
+                    return;
+                };
 
-                        if state.wm_base.is_some() && state.xdg_surface.is_none() {
-                            state.init_xdg_surface(queue_handle);
+                match interface.as_str() {
+                    "wl_seat" => {
+                        if let Some(seat) = state.seats.remove(&name) {
+                            seat.release();
                         }
-                    }
-                    "wl_shm" => {
-                        let wl_shm = proxy.bind::<WlShm, _, _>(name, version, queue_handle, ());
-
-                        let pool = wl_shm.create_pool(
-                            state.buffer_file.as_fd(),
-                            (SIZE.0 * SIZE.1 * 4) as i32,
-                            queue_handle,
-                            (),
-                        );
-                        let buffer = pool.create_buffer(
-                            0,
-                            SIZE.0 as i32,
-                            SIZE.1 as i32,
-                            (SIZE.0 * 4) as i32,
-                            Format::Argb8888,
-                            queue_handle,
-                            (),
-                        );
-                        state.buffer = Some(buffer.clone());
-
-                        if state.configured {
-                            let surface = state.base_surface.as_ref().unwrap();
-                            surface.attach(Some(&buffer), 0, 0);
-                            surface.commit();
+                        if let Some((seat_name, _)) = state.keyboard_serial {
+                            if seat_name == name {
+                                state.keyboard_serial = None;
+                            }
                         }
-                    }
-                    "wl_seat" => {
-                        proxy.bind::<WlSeat, _, _>(name, version, queue_handle, ());
-                    }
-                    "xdg_wm_base" => {
-                        let xdg_wm_base =
-                            proxy.bind::<XdgWmBase, _, _>(name, version, queue_handle, ());
-                        state.wm_base = Some(xdg_wm_base);
 
-                        if state.base_surface.is_some() && state.xdg_surface.is_none() {
-                            state.init_xdg_surface(queue_handle);
-                        }
+                        state.pointer_inside = false;
                     }
+                    // `release` only exists from version 2 on, which isn't in these bindings, so
+                    // `wl_shm` is bound at version 1 and the proxy is just dropped. Buffers already
+                    // created from it stay valid.
+                    "wl_shm" => state.shm = None,
                     _ => (),
-                };
+                }
             }
-            Event::GlobalRemove { name: _ } => (),
             _ => (),
         }
     }
@@ -109,42 +211,128 @@ impl Dispatch<WlRegistry, ()> for State {
 
 delegate_noop!(State: ignore WlCompositor);
 delegate_noop!(State: ignore WlSurface);
+delegate_noop!(State: ignore WlRegion);
 delegate_noop!(State: ignore WlShm);
 delegate_noop!(State: ignore WlShmPool);
-delegate_noop!(State: ignore WlBuffer);
+delegate_noop!(State: ignore WlDataDeviceManager);
+delegate_noop!(State: ignore WlDataOffer);
+delegate_noop!(State: ignore WpCursorShapeManagerV1);
+delegate_noop!(State: ignore WpCursorShapeDeviceV1);
 
-impl Dispatch<XdgSurface, ()> for State {
+// The user data is the window's surface id and the buffer's index in the window.
+impl Dispatch<WlBuffer, (ObjectId, usize)> for State {
+    fn event(
+        state: &mut Self,
+        _: &WlBuffer,
+        event: wl_buffer::Event,
+        (id, index): &(ObjectId, usize),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_buffer::Event::Release = event {
+            if let Some(window) = state.windows.get_mut(id) {
+                window.buffers[*index].busy = false;
+            }
+        }
+    }
+}
+
+// The user data of this and the xdg objects below is the window's surface id.
+impl Dispatch<WlCallback, ObjectId> for State {
+    fn event(
+        state: &mut Self,
+        _: &WlCallback,
+        event: wl_callback::Event,
+        id: &ObjectId,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_callback::Event::Done { .. } = event {
+            if let Some(window) = state.windows.get_mut(id) {
+                window.draw_state.frame_done();
+            }
+        }
+    }
+}
+
+impl Dispatch<XdgSurface, ObjectId> for State {
     fn event(
         state: &mut Self,
         proxy: &XdgSurface,
         event: <XdgSurface as wayland_client::Proxy>::Event,
-        _: &(),
+        id: &ObjectId,
         _: &Connection,
-        _: &QueueHandle<Self>,
+        queue_handle: &QueueHandle<Self>,
     ) {
         if let xdg_surface::Event::Configure { serial, .. } = event {
             proxy.ack_configure(serial);
-            state.configured = true;
-            let surface = state.base_surface.as_ref().unwrap();
-            if let Some(ref buffer) = state.buffer {
-                surface.attach(Some(buffer), 0, 0);
-                surface.commit();
+
+            let Some(window) = state.windows.get_mut(id) else {
+                return;
+            };
+
+            if let Some(size) = window.pending_size.take() {
+                window.size = size;
+                if let Some(ref wl_shm) = state.shm {
+                    window.create_buffers(wl_shm, queue_handle);
+                }
             }
+            window.update_opaque_region(state.compositor.as_ref(), queue_handle);
+
+            window.draw_state.configure();
         }
     }
 }
 
-impl Dispatch<XdgToplevel, ()> for State {
+impl Dispatch<XdgToplevel, ObjectId> for State {
     fn event(
         state: &mut Self,
         _: &XdgToplevel,
         event: <XdgToplevel as wayland_client::Proxy>::Event,
-        _: &(),
+        id: &ObjectId,
         _: &Connection,
         _: &QueueHandle<Self>,
     ) {
-        if let xdg_toplevel::Event::Close {} = event {
-            state.running = false;
+        let Some(window) = state.windows.get_mut(id) else {
+            return;
+        };
+
+        match event {
+            xdg_toplevel::Event::Configure {
+                width,
+                height,
+                states,
+            } => {
+                let states: Vec<u32> = states
+                    .chunks_exact(4)
+                    .map(|bytes| u32::from_ne_bytes(bytes.try_into().unwrap()))
+                    .collect();
+                window.fullscreen = states.contains(&(xdg_toplevel::State::Fullscreen as u32));
+                window.maximized = states.contains(&(xdg_toplevel::State::Maximized as u32));
+                let floating = !window.fullscreen && !window.maximized;
+
+                // A zero dimension leaves the size up to us, which for a floating window is the
+                // size it had before it was made fullscreen or maximized.
+                let size = if width > 0 && height > 0 {
+                    Some((width as u32, height as u32))
+                } else if floating {
+                    Some(window.windowed_size)
+                } else {
+                    None
+                };
+
+                if let Some(size) = size {
+                    if floating {
+                        window.windowed_size = size;
+                    }
+
+                    if size != window.size {
+                        window.pending_size = Some(size);
+                    }
+                }
+            }
+            xdg_toplevel::Event::Close {} => window.close_requested = true,
+            _ => (),
         }
     }
 }
@@ -164,12 +352,13 @@ impl Dispatch<XdgWmBase, ()> for State {
     }
 }
 
-impl Dispatch<WlSeat, ()> for State {
+// The user data is the seat's registry name.
+impl Dispatch<WlSeat, u32> for State {
     fn event(
-        _: &mut Self,
+        state: &mut Self,
         proxy: &WlSeat,
         event: <WlSeat as wayland_client::Proxy>::Event,
-        _: &(),
+        name: &u32,
         _: &Connection,
         queue_handle: &QueueHandle<Self>,
     ) {
@@ -177,79 +366,936 @@ impl Dispatch<WlSeat, ()> for State {
             capabilities: WEnum::Value(capabilities),
         } = event
         {
+            let Some(seat) = state.seats.get_mut(name) else {
+                return;
+            };
+
             if capabilities.contains(Capability::Keyboard) {
-                proxy.get_keyboard(queue_handle, ());
+                if seat.keyboard.is_none() {
+                    seat.keyboard = Some(proxy.get_keyboard(queue_handle, *name));
+                }
+            } else {
+                seat.release_keyboard();
+            }
+
+            if capabilities.contains(Capability::Pointer) {
+                if seat.pointer.is_none() {
+                    seat.pointer = Some(proxy.get_pointer(queue_handle, *name));
+                    state.create_cursor_shape_devices(queue_handle);
+                }
+            } else {
+                seat.release_pointer();
+                state.pointer_inside = false;
             }
         }
     }
 }
 
-impl Dispatch<wl_keyboard::WlKeyboard, ()> for State {
+// The user data is the seat's registry name.
+impl Dispatch<wl_keyboard::WlKeyboard, u32> for State {
     fn event(
         state: &mut Self,
         _: &wl_keyboard::WlKeyboard,
         event: wl_keyboard::Event,
-        _: &(),
+        name: &u32,
         _: &Connection,
         _: &QueueHandle<Self>,
     ) {
-        if let wl_keyboard::Event::Key { key, .. } = event {
-            match key {
-                1 => state.running = false,
-                _ => (),
+        match event {
+            wl_keyboard::Event::Keymap { fd, size, .. } => state.keyboard.load_keymap(fd, size),
+            wl_keyboard::Event::Enter {
+                serial, surface, ..
+            } => {
+                state.keyboard_focus = Some(surface.id());
+                state.keyboard_serial = Some((*name, serial));
             }
+            wl_keyboard::Event::Leave { .. } => {
+                state.keyboard_focus = None;
+                state.keyboard_serial = None;
+            }
+            wl_keyboard::Event::Modifiers {
+                mods_depressed,
+                mods_latched,
+                mods_locked,
+                group,
+                ..
+            } => state
+                .keyboard
+                .update_modifiers(mods_depressed, mods_latched, mods_locked, group),
+            wl_keyboard::Event::Key {
+                serial,
+                key,
+                state: key_state,
+                ..
+            } => {
+                state.keyboard_serial = Some((*name, serial));
+                let pressed = key_state == WEnum::Value(wl_keyboard::KeyState::Pressed);
+
+                if let Some(key_event) = state.keyboard.translate(key, pressed) {
+                    if key_event.pressed && key_event.keysym == Keysym::Escape {
+                        state.close_focused_window();
+                    }
+
+                    state.key_events.push(key_event);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+// The user data is the seat's registry name.
+impl Dispatch<wl_pointer::WlPointer, u32> for State {
+    fn event(
+        state: &mut Self,
+        _: &wl_pointer::WlPointer,
+        event: wl_pointer::Event,
+        name: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_pointer::Event::Enter {
+                serial,
+                surface_x,
+                surface_y,
+                ..
+            } => {
+                if let Some(seat) = state.seats.get_mut(name) {
+                    seat.pointer_enter_serial = Some(serial);
+                    seat.apply_cursor(&state.cursor_mode);
+                }
+
+                state.pointer_inside = true;
+                state.pointer_position = (surface_x, surface_y);
+                state.pointer_events.push(PointerEvent::Enter {
+                    x: surface_x,
+                    y: surface_y,
+                });
+            }
+            wl_pointer::Event::Leave { .. } => {
+                if let Some(seat) = state.seats.get_mut(name) {
+                    seat.pointer_enter_serial = None;
+                }
+
+                state.pointer_inside = false;
+                state.pointer_events.push(PointerEvent::Leave);
+            }
+            wl_pointer::Event::Motion {
+                surface_x,
+                surface_y,
+                ..
+            } => {
+                state.pointer_position = (surface_x, surface_y);
+                state.pointer_events.push(PointerEvent::Motion {
+                    x: surface_x,
+                    y: surface_y,
+                });
+            }
+            wl_pointer::Event::Button {
+                button,
+                state: button_state,
+                ..
+            } if state.pointer_inside => {
+                state.pointer_events.push(PointerEvent::Button {
+                    button,
+                    pressed: button_state == WEnum::Value(wl_pointer::ButtonState::Pressed),
+                    x: state.pointer_position.0,
+                    y: state.pointer_position.1,
+                });
+            }
+            wl_pointer::Event::Axis {
+                axis: WEnum::Value(axis),
+                value,
+                ..
+            } if state.pointer_inside => {
+                let event = match axis {
+                    wl_pointer::Axis::HorizontalScroll => PointerEvent::Scroll {
+                        horizontal: value,
+                        vertical: 0.0,
+                    },
+                    _ => PointerEvent::Scroll {
+                        horizontal: 0.0,
+                        vertical: value,
+                    },
+                };
+                state.pointer_events.push(event);
+            }
+            _ => (),
         }
     }
 }
 
 impl State {
-    fn init_xdg_surface(&mut self, queue_handle: &QueueHandle<State>) {
-        let wm_base = self.wm_base.as_ref().unwrap();
-        let base_surface = self.base_surface.as_ref().unwrap();
+    fn supports_protocol(&self, name: &str, min_version: u32) -> bool {
+        self.globals
+            .values()
+            .any(|(interface, version)| interface == name && *version >= min_version)
+    }
+
+    // Binds every global from the initial registry scan, see `bind_global`.
+    fn bind_globals(&mut self, registry: &WlRegistry, queue_handle: &QueueHandle<State>) {
+        let mut globals: Vec<(u32, String, u32)> = self
+            .globals
+            .iter()
+            .map(|(&name, (interface, version))| (name, interface.clone(), *version))
+            .collect();
+        // In the order the compositor advertised them.
+        globals.sort_by_key(|&(name, _, _)| name);
 
-        let xdg_surface = wm_base.get_xdg_surface(base_surface, queue_handle, ());
-        let toplevel = xdg_surface.get_toplevel(queue_handle, ());
-        toplevel.set_title(NAME.into());
+        for (name, interface, version) in globals {
+            self.bind_global(registry, name, &interface, version, queue_handle);
+        }
+    }
 
-        base_surface.commit();
+    // The optional globals are only bound when `supports_protocol` finds a version the client can
+    // use, so the features built on them are left out otherwise.
+    fn bind_global(
+        &mut self,
+        registry: &WlRegistry,
+        name: u32,
+        interface: &str,
+        version: u32,
+        queue_handle: &QueueHandle<State>,
+    ) {
+        match interface {
+            "wl_compositor" => {
+                let wl_compositor =
+                    registry.bind::<WlCompositor, _, _>(name, version, queue_handle, ());
+                self.compositor = Some(wl_compositor);
+            }
+            "wl_shm" => {
+                // Capped at the newest version these bindings know, so the proxy never has a
+                // destructor the client can't send.
+                let version = version.min(WlShm::interface().version);
+                let wl_shm = registry.bind::<WlShm, _, _>(name, version, queue_handle, ());
 
-        self.xdg_surface = Some((xdg_surface, toplevel));
+                for window in self.windows.values_mut() {
+                    window.create_buffers(&wl_shm, queue_handle);
+                }
+                self.shm = Some(wl_shm);
+            }
+            "wl_seat" if self.supports_protocol("wl_seat", SEAT_VERSION) => {
+                let seat = registry.bind::<WlSeat, _, _>(name, version, queue_handle, name);
+                self.seats.insert(
+                    name,
+                    Seat {
+                        seat,
+                        keyboard: None,
+                        pointer: None,
+                        pointer_enter_serial: None,
+                        cursor_shape_device: None,
+                        data_device: None,
+                    },
+                );
+                self.create_data_devices(queue_handle);
+            }
+            "wl_data_device_manager"
+                if self
+                    .supports_protocol("wl_data_device_manager", DATA_DEVICE_MANAGER_VERSION) =>
+            {
+                // Version 3 only adds drag and drop actions, which aren't used.
+                let data_device_manager = registry.bind::<WlDataDeviceManager, _, _>(
+                    name,
+                    version.min(3),
+                    queue_handle,
+                    (),
+                );
+                self.data_device_manager = Some(data_device_manager);
+                self.create_data_devices(queue_handle);
+            }
+            "wp_cursor_shape_manager_v1"
+                if self.supports_protocol(
+                    "wp_cursor_shape_manager_v1",
+                    CURSOR_SHAPE_MANAGER_VERSION,
+                ) =>
+            {
+                let cursor_shape_manager = registry.bind::<WpCursorShapeManagerV1, _, _>(
+                    name,
+                    CURSOR_SHAPE_MANAGER_VERSION,
+                    queue_handle,
+                    (),
+                );
+                self.cursor_shape_manager = Some(cursor_shape_manager);
+                self.create_cursor_shape_devices(queue_handle);
+            }
+            "xdg_wm_base" => {
+                let xdg_wm_base = registry.bind::<XdgWmBase, _, _>(name, version, queue_handle, ());
+
+                for window in self.windows.values_mut() {
+                    if window.xdg_surface.is_none() {
+                        window.init_xdg_surface(&xdg_wm_base, queue_handle);
+                    }
+                }
+                self.wm_base = Some(xdg_wm_base);
+            }
+            _ => (),
+        }
+    }
+
+    fn create_data_devices(&mut self, queue_handle: &QueueHandle<State>) {
+        let Some(ref data_device_manager) = self.data_device_manager else {
+            return;
+        };
+
+        for seat in self.seats.values_mut() {
+            if seat.data_device.is_none() {
+                seat.data_device =
+                    Some(data_device_manager.get_data_device(&seat.seat, queue_handle, ()));
+            }
+        }
+    }
+
+    fn create_cursor_shape_devices(&mut self, queue_handle: &QueueHandle<State>) {
+        let Some(ref cursor_shape_manager) = self.cursor_shape_manager else {
+            return;
+        };
+
+        for seat in self.seats.values_mut() {
+            if let (Some(pointer), None) = (&seat.pointer, &seat.cursor_shape_device) {
+                seat.cursor_shape_device =
+                    Some(cursor_shape_manager.get_pointer(pointer, queue_handle, ()));
+            }
+        }
     }
+
+    fn add_window(
+        &mut self,
+        size: (u32, u32),
+        buffers: Vec<ShmBuffer>,
+        queue_handle: &QueueHandle<State>,
+    ) -> ObjectId {
+        assert!(
+            size.0 > 0 && size.1 > 0,
+            "Window size must be non-zero, got {}x{}.",
+            size.0,
+            size.1
+        );
+
+        let compositor = self
+            .compositor
+            .as_ref()
+            .expect("The compositor didn't advertise wl_compositor.");
+
+        let mut window = Window::new(
+            compositor,
+            size,
+            NAME.to_owned(),
+            APP_ID.to_owned(),
+            buffers,
+            queue_handle,
+        );
+        if let Some(ref wl_shm) = self.shm {
+            window.create_buffers(wl_shm, queue_handle);
+        }
+        if let Some(ref wm_base) = self.wm_base {
+            window.init_xdg_surface(wm_base, queue_handle);
+        }
+
+        let id = window.id();
+        self.windows.insert(id.clone(), window);
+
+        return id;
+    }
+
+    // Without a focused window, e.g. when the compositor doesn't report focus, all of them close.
+    fn close_focused_window(&mut self) {
+        let focused = self
+            .keyboard_focus
+            .as_ref()
+            .and_then(|id| self.windows.get_mut(id));
+
+        match focused {
+            Some(window) => window.close_requested = true,
+            None => {
+                for window in self.windows.values_mut() {
+                    window.close_requested = true;
+                }
+            }
+        }
+    }
+}
+
+type RenderFn<'a> = Box<dyn FnMut(&mut File, (u32, u32), &FrameContext) -> Vec<DamageRegion> + 'a>;
+type PresentFn<'a> = Box<dyn FnMut((u32, u32), &FrameContext) -> bool + 'a>;
+
+enum FrameOutput<'a> {
+    // Fills an shm buffer, which the client attaches and commits.
+    Shm(RenderFn<'a>),
+    // Presents to the surface itself, e.g. through a Vulkan swapchain, which also commits it.
+    // Nothing is drawn until a handler is set.
+    Present(Option<PresentFn<'a>>),
+}
+
+// What a single window draws with, and its frame pacing.
+struct WindowOutput<'a> {
+    output: FrameOutput<'a>,
+    frame_clock: FrameClock,
 }
 
-pub struct WaylandClient {
+pub struct WaylandClient<'a> {
+    // Declared first so they're dropped first, as a swapchain one owns must not outlive its
+    // surface. Keyed like `State::windows`.
+    outputs: HashMap<ObjectId, WindowOutput<'a>>,
+    connection: Connection,
     event_queue: EventQueue<State>,
     state: State,
+    // The window created with the client, which the per-window methods below act on.
+    main_window: ObjectId,
+    max_fps: Option<u32>,
+    key_handler: Option<Box<dyn FnMut(&KeyEvent) + 'a>>,
+    pointer_handler: Option<Box<dyn FnMut(&PointerEvent) + 'a>>,
 }
 
-impl WaylandClient {
-    pub fn new<T: Fn(&File)>(graphics_function: T) -> Self {
+impl<'a> WaylandClient<'a> {
+    // `render` runs once per frame, paced by `wl_surface::frame` callbacks, with the frame's
+    // timing in the `FrameContext`. It must fill the whole buffer, and returns the regions that
+    // changed since the previous frame, where no regions means the whole surface changed.
+    pub fn new<T: FnMut(&mut File, (u32, u32), &FrameContext) -> Vec<DamageRegion> + 'a>(
+        size: (u32, u32),
+        render: T,
+    ) -> Self {
+        let mut client = Self::connect();
+
+        let id = client.add_window(size, render);
+        client.main_window = id.0;
+
+        return client;
+    }
+
+    // For rendering with Vulkan straight to the surface, see `raw_handles` and
+    // `set_present_handler`. No shm buffers are created.
+    pub fn new_presenting(size: (u32, u32)) -> Self {
+        let mut client = Self::connect();
+
+        let queue_handle = client.event_queue.handle();
+        let id = client.state.add_window(size, Vec::new(), &queue_handle);
+        client.outputs.insert(
+            id.clone(),
+            WindowOutput {
+                output: FrameOutput::Present(None),
+                frame_clock: FrameClock::new(),
+            },
+        );
+        client.main_window = id;
+
+        return client;
+    }
+
+    // Binds the globals, so windows can be created right away.
+    fn connect() -> Self {
         let connection = Connection::connect_to_env().expect("Couldn't connect to wayland server.");
 
-        let event_queue = connection.new_event_queue();
+        let mut event_queue = connection.new_event_queue();
 
-        connection.display().get_registry(&event_queue.handle(), ());
+        let registry = connection.display().get_registry(&event_queue.handle(), ());
 
-        let state = State {
-            running: true,
-            buffer_file: tempfile::tempfile().unwrap(),
-            base_surface: None,
-            buffer: None,
+        let mut state = State {
+            globals: HashMap::new(),
+            registry_scanned: false,
+            compositor: None,
+            shm: None,
+            seats: HashMap::new(),
             wm_base: None,
-            xdg_surface: None,
-            configured: false,
+            windows: HashMap::new(),
+            keyboard: Keyboard::new(),
+            keyboard_focus: None,
+            keyboard_serial: None,
+            data_device_manager: None,
+            clipboard: None,
+            cursor_shape_manager: None,
+            cursor_mode: CursorMode::Default,
+            key_events: Vec::new(),
+            pointer_inside: false,
+            pointer_position: (0.0, 0.0),
+            pointer_events: Vec::new(),
         };
 
-        graphics_function(&state.buffer_file);
+        event_queue
+            .roundtrip(&mut state)
+            .expect("Failed to scan the wayland registry.");
+
+        state.bind_globals(&registry, &event_queue.handle());
+        state.registry_scanned = true;
+
+        return WaylandClient {
+            outputs: HashMap::new(),
+            connection,
+            event_queue,
+            state,
+            main_window: ObjectId::null(),
+            max_fps: None,
+            key_handler: None,
+            pointer_handler: None,
+        };
+    }
+
+    // Opens another shm window on the same connection, rendered like the one from `new`. `run`
+    // keeps going until every window is closed.
+    pub fn add_window<T: FnMut(&mut File, (u32, u32), &FrameContext) -> Vec<DamageRegion> + 'a>(
+        &mut self,
+        size: (u32, u32),
+        mut render: T,
+    ) -> WindowId {
+        let mut buffers: Vec<ShmBuffer> = (0..BUFFER_COUNT).map(|_| ShmBuffer::new(size)).collect();
+
+        // Populate the first buffer before the pools are created, so the compositor never maps an
+        // uninitialized file.
+        let mut frame_clock = FrameClock::new();
+        render(&mut buffers[0].file, size, &frame_clock.tick());
+
+        let queue_handle = self.event_queue.handle();
+        let id = self.state.add_window(size, buffers, &queue_handle);
+        self.outputs.insert(
+            id.clone(),
+            WindowOutput {
+                output: FrameOutput::Shm(Box::new(render)),
+                frame_clock,
+            },
+        );
+
+        return WindowId(id);
+    }
+
+    // Whether the compositor advertises the global `name` at `min_version` or later, e.g. to check
+    // for a protocol before relying on a feature built on it.
+    #[allow(dead_code)]
+    pub fn supports_protocol(&self, name: &str, min_version: u32) -> bool {
+        self.state.supports_protocol(name, min_version)
+    }
+
+    pub fn main_window(&self) -> WindowId {
+        WindowId(self.main_window.clone())
+    }
+
+    #[allow(dead_code)]
+    pub fn window_count(&self) -> usize {
+        self.state.windows.len()
+    }
 
-        return WaylandClient { event_queue, state };
+    fn window(&self, id: &WindowId) -> Option<&Window> {
+        self.state.windows.get(&id.0)
+    }
+
+    #[allow(dead_code)]
+    fn window_mut(&mut self, id: &WindowId) -> Option<&mut Window> {
+        self.state.windows.get_mut(&id.0)
+    }
+
+    // The `wl_display` and `wl_surface` pointers for `VulkanProcessor::create_wayland_surface`,
+    // valid for as long as the main window is open.
+    pub fn raw_handles(&self) -> (*mut c_void, *mut c_void) {
+        let window = self
+            .window(&self.main_window())
+            .expect("The main window is closed.");
+
+        let display = self.connection.backend().display_ptr() as *mut c_void;
+        let surface = window.surface.id().as_ptr() as *mut c_void;
+
+        return (display, surface);
+    }
+
+    // Only used by clients created with `new_presenting`. The handler must present a frame of the
+    // given size to the surface, committing it, and returns false if it couldn't, in which case
+    // the frame is retried after the next event.
+    pub fn set_present_handler<T: FnMut((u32, u32), &FrameContext) -> bool + 'a>(
+        &mut self,
+        present_handler: T,
+    ) {
+        let Some(window_output) = self.outputs.get_mut(&self.main_window) else {
+            return;
+        };
+
+        assert!(
+            matches!(window_output.output, FrameOutput::Present(_)),
+            "set_present_handler requires a client created with new_presenting."
+        );
+
+        window_output.output = FrameOutput::Present(Some(Box::new(present_handler)));
+    }
+
+    // The setters below act on the main window, use the `_of` variants for the others. Windows
+    // that were already closed are ignored.
+    #[allow(dead_code)]
+    pub fn set_title(&mut self, title: &str) {
+        self.set_title_of(&self.main_window(), title);
+    }
+
+    #[allow(dead_code)]
+    pub fn set_title_of(&mut self, id: &WindowId, title: &str) {
+        let Some(window) = self.window_mut(id) else {
+            return;
+        };
+
+        window.title = title.to_owned();
+
+        if let Some((_, toplevel)) = &window.xdg_surface {
+            toplevel.set_title(window.title.clone());
+            window.surface.commit();
+        }
     }
 
-    pub fn run(&mut self) {
+    // Should match the basename of the application's .desktop file for desktop integration.
+    #[allow(dead_code)]
+    pub fn set_app_id(&mut self, app_id: &str) {
+        self.set_app_id_of(&self.main_window(), app_id);
+    }
+
+    #[allow(dead_code)]
+    pub fn set_app_id_of(&mut self, id: &WindowId, app_id: &str) {
+        let Some(window) = self.window_mut(id) else {
+            return;
+        };
+
+        window.app_id = app_id.to_owned();
+
+        if let Some((_, toplevel)) = &window.xdg_surface {
+            toplevel.set_app_id(window.app_id.clone());
+            window.surface.commit();
+        }
+    }
+
+    // The compositor picks the output. The resulting configure resizes the buffers, and leaving
+    // fullscreen restores the previous floating size.
+    #[allow(dead_code)]
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.set_fullscreen_of(&self.main_window(), fullscreen);
+    }
+
+    #[allow(dead_code)]
+    pub fn set_fullscreen_of(&mut self, id: &WindowId, fullscreen: bool) {
+        let Some(window) = self.window_mut(id) else {
+            return;
+        };
+
+        window.fullscreen_requested = fullscreen;
+
+        if let Some((_, toplevel)) = &window.xdg_surface {
+            if fullscreen {
+                toplevel.set_fullscreen(None);
+            } else {
+                toplevel.unset_fullscreen();
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn set_maximized(&mut self, maximized: bool) {
+        self.set_maximized_of(&self.main_window(), maximized);
+    }
+
+    #[allow(dead_code)]
+    pub fn set_maximized_of(&mut self, id: &WindowId, maximized: bool) {
+        let Some(window) = self.window_mut(id) else {
+            return;
+        };
+
+        window.maximized_requested = maximized;
+
+        if let Some((_, toplevel)) = &window.xdg_surface {
+            if maximized {
+                toplevel.set_maximized();
+            } else {
+                toplevel.unset_maximized();
+            }
+        }
+    }
+
+    // Opaque by default, which is hinted to the compositor with an opaque region. The buffer is
+    // `Argb8888` with premultiplied alpha, so for a translucent window the renderer must write RGB
+    // already multiplied by alpha. Takes effect with the next frame.
+    pub fn set_opaque(&mut self, opaque: bool) {
+        self.set_opaque_of(&self.main_window(), opaque);
+    }
+
+    pub fn set_opaque_of(&mut self, id: &WindowId, opaque: bool) {
+        let queue_handle = self.event_queue.handle();
+        let Some(window) = self.state.windows.get_mut(&id.0) else {
+            return;
+        };
+
+        window.opaque = opaque;
+        window.update_opaque_region(self.state.compositor.as_ref(), &queue_handle);
+    }
+
+    // As last configured by the compositor, which may differ from what was requested.
+    #[allow(dead_code)]
+    pub fn is_fullscreen(&self) -> bool {
+        self.window(&self.main_window())
+            .map_or(false, |window| window.fullscreen)
+    }
+
+    #[allow(dead_code)]
+    pub fn is_maximized(&self) -> bool {
+        self.window(&self.main_window())
+            .map_or(false, |window| window.maximized)
+    }
+
+    pub fn fps(&self) -> f32 {
+        self.outputs
+            .get(&self.main_window)
+            .map_or(0.0, |window_output| window_output.frame_clock.fps())
+    }
+
+    // Caps the frame rate of every window below what frame callbacks allow, e.g. to save power, by
+    // sleeping before a frame that would come too early. `None` renders at the compositor's pace.
+    pub fn set_max_fps(&mut self, max_fps: Option<u32>) {
+        assert!(max_fps != Some(0), "The frame rate cap must be non-zero.");
+
+        self.max_fps = max_fps;
+    }
+
+    // The time between the main window's last two frames, including any throttling.
+    #[allow(dead_code)]
+    pub fn frame_time(&self) -> Duration {
+        self.outputs
+            .get(&self.main_window)
+            .map_or(Duration::ZERO, |window_output| {
+                window_output.frame_clock.frame_time()
+            })
+    }
+
+    // Escape always closes the focused window, before the handler sees it. Events from every
+    // window go to the same handler.
+    pub fn set_key_handler<T: FnMut(&KeyEvent) + 'a>(&mut self, key_handler: T) {
+        self.key_handler = Some(Box::new(key_handler));
+    }
+
+    // Coordinates are surface-local. Buttons and scrolling outside the surface are not reported.
+    #[allow(dead_code)]
+    pub fn set_pointer_handler<T: FnMut(&PointerEvent) + 'a>(&mut self, pointer_handler: T) {
+        self.pointer_handler = Some(Box::new(pointer_handler));
+    }
+
+    // Applies to every window, right away if the pointer is over one, and again whenever it
+    // enters one. Named cursors fall back to the default for names the protocol doesn't have, and
+    // are left to the compositor without `wp_cursor_shape_manager_v1`.
+    #[allow(dead_code)]
+    pub fn set_cursor(&mut self, cursor_mode: CursorMode) {
+        if let CursorMode::Named(ref name) = cursor_mode {
+            if self.state.cursor_shape_manager.is_none() {
+                println!(
+                    "Warning: the compositor has no wp_cursor_shape_manager_v1, can't show the \
+                     '{}' cursor.",
+                    name
+                );
+            } else if shape_from_name(name).is_none() {
+                println!("Warning: unknown cursor '{}', using the default.", name);
+            }
+        }
+
+        self.state.cursor_mode = cursor_mode;
+
+        for seat in self.state.seats.values() {
+            seat.apply_cursor(&self.state.cursor_mode);
+        }
+
+        let _ = self.connection.flush();
+    }
+
+    // Offers `text` as the clipboard contents until another client sets it. Setting the
+    // clipboard needs a recent input serial, so this returns false unless one of the windows has
+    // keyboard focus, or if the compositor has no `wl_data_device_manager`.
+    #[allow(dead_code)]
+    pub fn set_clipboard_text(&mut self, text: &str) -> bool {
+        let Some((seat_name, serial)) = self.state.keyboard_serial else {
+            return false;
+        };
+
+        let data_device = self
+            .state
+            .seats
+            .get(&seat_name)
+            .and_then(|seat| seat.data_device.as_ref());
+        let (Some(data_device_manager), Some(data_device)) =
+            (self.state.data_device_manager.as_ref(), data_device)
+        else {
+            return false;
+        };
+
+        let source = data_device_manager.create_data_source(&self.event_queue.handle(), ());
+        for mime_type in CLIPBOARD_MIME_TYPES {
+            source.offer(mime_type.to_owned());
+        }
+        data_device.set_selection(Some(&source), serial);
+
+        if let Some((previous_source, _)) = self.state.clipboard.replace((source, text.to_owned()))
+        {
+            previous_source.destroy();
+        }
+
+        let _ = self.connection.flush();
+
+        return true;
+    }
+
+    // Returns once every window is closed, or with the error when the connection to the
+    // compositor is lost, e.g. because it exited.
+    pub fn run(&mut self) -> Result<(), DispatchError> {
         println!("Start: \n");
-        while self.state.running {
-            let _ = self.event_queue.blocking_dispatch(&mut self.state);
+        let mut dispatch_retries = 0;
+        while !self.state.windows.is_empty() {
+            let ready: Vec<ObjectId> = self
+                .state
+                .windows
+                .iter()
+                .filter(|(_, window)| window.ready_to_draw())
+                .map(|(id, _)| id.clone())
+                .collect();
+            for id in ready {
+                self.draw_frame(&id);
+            }
+
+            match self.event_queue.blocking_dispatch(&mut self.state) {
+                Ok(_) => dispatch_retries = 0,
+                Err(error) if is_transient(&error) && dispatch_retries < MAX_DISPATCH_RETRIES => {
+                    println!("Retrying after a wayland dispatch error: {}", error);
+                    thread::sleep(DISPATCH_RETRY_DELAY * 2u32.pow(dispatch_retries));
+                    dispatch_retries += 1;
+                }
+                Err(error) => return Err(error),
+            }
+
+            self.close_requested_windows();
+
+            for key_event in self.state.key_events.drain(..) {
+                if let Some(ref mut key_handler) = self.key_handler {
+                    key_handler(&key_event);
+                }
+            }
+
+            for pointer_event in self.state.pointer_events.drain(..) {
+                if let Some(ref mut pointer_handler) = self.pointer_handler {
+                    pointer_handler(&pointer_event);
+                }
+            }
         }
         println!("End. \n");
+
+        return Ok(());
+    }
+
+    fn close_requested_windows(&mut self) {
+        let closed: Vec<ObjectId> = self
+            .state
+            .windows
+            .iter()
+            .filter(|(_, window)| window.close_requested)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for id in closed {
+            // A swapchain presenting to the surface has to go before the surface does.
+            self.outputs.remove(&id);
+
+            if let Some(window) = self.state.windows.remove(&id) {
+                window.destroy();
+            }
+
+            if self.state.keyboard_focus.as_ref() == Some(&id) {
+                self.state.keyboard_focus = None;
+            }
+        }
+    }
+
+    // Asks for a frame callback to pace the next frame, then renders into whichever buffer the
+    // compositor isn't holding and presents it, or leaves presenting to the present handler.
+    fn draw_frame(&mut self, id: &ObjectId) {
+        let (Some(window), Some(window_output)) =
+            (self.state.windows.get_mut(id), self.outputs.get_mut(id))
+        else {
+            return;
+        };
+
+        let surface = window.surface.clone();
+
+        if let Some(max_fps) = self.max_fps {
+            window_output.frame_clock.throttle(max_fps);
+        }
+
+        let render = match window_output.output {
+            FrameOutput::Shm(ref mut render) => render,
+            FrameOutput::Present(ref mut present_handler) => {
+                let Some(present_handler) = present_handler else {
+                    return;
+                };
+
+                let frame_context = window_output.frame_clock.tick();
+                surface.frame(&self.event_queue.handle(), id.clone());
+
+                if present_handler(window.size, &frame_context) {
+                    window.draw_state.frame_committed();
+                }
+                return;
+            }
+        };
+
+        let Some(shm_buffer) = window
+            .buffers
+            .iter_mut()
+            .find(|shm_buffer| shm_buffer.buffer.is_some() && !shm_buffer.busy)
+        else {
+            return;
+        };
+
+        shm_buffer.file.seek(SeekFrom::Start(0)).unwrap();
+        let frame_context = window_output.frame_clock.tick();
+        let damage = render(&mut shm_buffer.file, window.size, &frame_context);
+
+        surface.attach(shm_buffer.buffer.as_ref(), 0, 0);
+        if damage.is_empty() {
+            surface.damage_buffer(0, 0, window.size.0 as i32, window.size.1 as i32);
+        } else {
+            for region in damage {
+                surface.damage_buffer(region.x, region.y, region.w, region.h);
+            }
+        }
+        surface.frame(&self.event_queue.handle(), id.clone());
+        surface.commit();
+
+        shm_buffer.busy = true;
+        window.draw_state.frame_committed();
+    }
+}
+
+// Interrupted reads are harmless. Anything else, such as a protocol error or a closed socket,
+// leaves the connection unusable.
+fn is_transient(error: &DispatchError) -> bool {
+    match error {
+        DispatchError::Backend(WaylandError::Io(error)) => matches!(
+            error.kind(),
+            io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+        ),
+        _ => false,
+    }
+}
+
+// Destroys every object child first, so compositors don't log them as leaked.
+impl<'a> Drop for WaylandClient<'a> {
+    fn drop(&mut self) {
+        // A swapchain presenting to a surface has to go before the surface does.
+        self.outputs.clear();
+
+        for (_, window) in self.state.windows.drain() {
+            window.destroy();
+        }
+
+        if let Some(wm_base) = self.state.wm_base.take() {
+            wm_base.destroy();
+        }
+
+        if let Some((source, _)) = self.state.clipboard.take() {
+            source.destroy();
+        }
+
+        for (_, seat) in self.state.seats.drain() {
+            seat.release();
+        }
+
+        if let Some(cursor_shape_manager) = self.state.cursor_shape_manager.take() {
+            cursor_shape_manager.destroy();
+        }
+
+        let _ = self.connection.flush();
     }
 }
@@ -0,0 +1,23 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PointerEvent {
+    Enter {
+        x: f64,
+        y: f64,
+    },
+    Leave,
+    Motion {
+        x: f64,
+        y: f64,
+    },
+    // Linux input button codes, e.g. 0x110 for the left button.
+    Button {
+        button: u32,
+        pressed: bool,
+        x: f64,
+        y: f64,
+    },
+    Scroll {
+        horizontal: f64,
+        vertical: f64,
+    },
+}
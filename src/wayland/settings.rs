@@ -1,2 +1,3 @@
 pub const NAME: &str = "Vase";
+pub const APP_ID: &str = "vase";
 pub const SIZE: (u32, u32) = (1280, 800);
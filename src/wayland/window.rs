@@ -0,0 +1,327 @@
+use std::{fs::File, os::fd::AsFd};
+
+use wayland_client::{
+    backend::ObjectId,
+    protocol::{
+        wl_buffer::WlBuffer,
+        wl_compositor::WlCompositor,
+        wl_shm::{Format, WlShm},
+        wl_shm_pool::WlShmPool,
+        wl_surface::WlSurface,
+    },
+    Proxy, QueueHandle,
+};
+use wayland_protocols::xdg::shell::client::{
+    xdg_surface::XdgSurface, xdg_toplevel::XdgToplevel, xdg_wm_base::XdgWmBase,
+};
+
+use super::State;
+
+pub(super) struct ShmBuffer {
+    pub(super) file: File,
+    // Kept for the buffer's lifetime so it can be grown on resize, rather than a new file and
+    // pool being made each time.
+    pub(super) pool: Option<(WlShmPool, i32)>,
+    pub(super) buffer: Option<WlBuffer>,
+    // Held by the compositor until it sends `wl_buffer::release`.
+    pub(super) busy: bool,
+}
+
+impl ShmBuffer {
+    pub(super) fn new(size: (u32, u32)) -> Self {
+        let file = tempfile::tempfile().unwrap();
+        file.set_len((size.0 * size.1 * 4) as u64).unwrap();
+
+        ShmBuffer {
+            file,
+            pool: None,
+            buffer: None,
+            busy: false,
+        }
+    }
+}
+
+// When a window may draw its next frame. Kept apart from the protocol objects, so the order
+// events arrive in can be tested without a compositor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct DrawState {
+    configured: bool,
+    // Until the shm buffers exist, which needs `wl_shm`. Presenting windows have none to wait for.
+    awaiting_buffers: bool,
+    frame_pending: bool,
+    needs_redraw: bool,
+}
+
+impl DrawState {
+    pub(super) fn new(uses_shm: bool) -> Self {
+        DrawState {
+            configured: false,
+            awaiting_buffers: uses_shm,
+            frame_pending: false,
+            needs_redraw: false,
+        }
+    }
+
+    // On `xdg_surface::configure`.
+    pub(super) fn configure(&mut self) {
+        self.configured = true;
+        self.needs_redraw = true;
+    }
+
+    pub(super) fn buffers_created(&mut self) {
+        self.awaiting_buffers = false;
+        self.needs_redraw = true;
+    }
+
+    // On the `wl_surface::frame` callback's `done`.
+    pub(super) fn frame_done(&mut self) {
+        self.frame_pending = false;
+        self.needs_redraw = true;
+    }
+
+    pub(super) fn frame_committed(&mut self) {
+        self.frame_pending = true;
+        self.needs_redraw = false;
+    }
+
+    // The first configure and, for shm windows, `wl_shm` may arrive in either order, so the first
+    // frame is drawn once both are there. After that, one frame per frame callback.
+    pub(super) fn ready_to_draw(&self) -> bool {
+        self.configured && !self.awaiting_buffers && self.needs_redraw && !self.frame_pending
+    }
+}
+
+// Identifies one of a client's windows, by its `wl_surface`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct WindowId(pub(super) ObjectId);
+
+pub(super) struct Window {
+    pub(super) surface: WlSurface,
+    pub(super) buffers: Vec<ShmBuffer>,
+    pub(super) size: (u32, u32),
+    // Set by `xdg_toplevel::configure`, applied on the following `xdg_surface::configure`.
+    pub(super) pending_size: Option<(u32, u32)>,
+    // The last floating size, restored when leaving fullscreen or maximized.
+    pub(super) windowed_size: (u32, u32),
+    pub(super) fullscreen: bool,
+    pub(super) maximized: bool,
+    pub(super) xdg_surface: Option<(XdgSurface, XdgToplevel)>,
+    // Applied in `init_xdg_surface` when set before the toplevel exists.
+    pub(super) title: String,
+    pub(super) app_id: String,
+    pub(super) fullscreen_requested: bool,
+    pub(super) maximized_requested: bool,
+    // Whether every pixel rendered has full alpha.
+    pub(super) opaque: bool,
+    pub(super) draw_state: DrawState,
+    // Set by `xdg_toplevel::close` or Escape, the window is destroyed after the current dispatch.
+    pub(super) close_requested: bool,
+}
+
+impl Window {
+    pub(super) fn new(
+        compositor: &WlCompositor,
+        size: (u32, u32),
+        title: String,
+        app_id: String,
+        buffers: Vec<ShmBuffer>,
+        queue_handle: &QueueHandle<State>,
+    ) -> Self {
+        // Presenting windows are created without shm buffers.
+        let draw_state = DrawState::new(!buffers.is_empty());
+
+        Window {
+            surface: compositor.create_surface(queue_handle, ()),
+            buffers,
+            size,
+            pending_size: None,
+            windowed_size: size,
+            fullscreen: false,
+            maximized: false,
+            xdg_surface: None,
+            title,
+            app_id,
+            fullscreen_requested: false,
+            maximized_requested: false,
+            opaque: true,
+            draw_state,
+            close_requested: false,
+        }
+    }
+
+    pub(super) fn id(&self) -> ObjectId {
+        self.surface.id()
+    }
+
+    // The single check for whether `WaylandClient::draw_frame` should run, see `DrawState`. A
+    // window whose buffers are all still held by the compositor stays ready and is retried after
+    // the next event.
+    pub(super) fn ready_to_draw(&self) -> bool {
+        self.draw_state.ready_to_draw()
+    }
+
+    // (Re)creates every shm buffer at the current size. Old buffers are destroyed, the compositor
+    // keeps whatever contents it is still showing. Pools only ever grow, with
+    // `wl_shm_pool::resize`, on which the compositor remaps the file. Shrinking keeps the larger
+    // allocation for the next time the window grows. The render function maps the file afresh
+    // for each frame, if at all, so there is nothing to remap on this side.
+    pub(super) fn create_buffers(&mut self, wl_shm: &WlShm, queue_handle: &QueueHandle<State>) {
+        let (width, height) = self.size;
+        let length = (width * height * 4) as i32;
+        let id = self.id();
+
+        for (index, shm_buffer) in self.buffers.iter_mut().enumerate() {
+            if let Some(buffer) = shm_buffer.buffer.take() {
+                buffer.destroy();
+            }
+
+            match shm_buffer.pool {
+                Some((ref pool, ref mut pool_length)) => {
+                    if length > *pool_length {
+                        shm_buffer.file.set_len(length as u64).unwrap();
+                        pool.resize(length);
+                        *pool_length = length;
+                    }
+                }
+                None => {
+                    shm_buffer.file.set_len(length as u64).unwrap();
+                    let pool =
+                        wl_shm.create_pool(shm_buffer.file.as_fd(), length, queue_handle, ());
+                    shm_buffer.pool = Some((pool, length));
+                }
+            }
+
+            let (pool, _) = shm_buffer.pool.as_ref().unwrap();
+            let buffer = pool.create_buffer(
+                0,
+                width as i32,
+                height as i32,
+                (width * 4) as i32,
+                Format::Argb8888,
+                queue_handle,
+                (id.clone(), index),
+            );
+
+            shm_buffer.buffer = Some(buffer);
+            shm_buffer.busy = false;
+        }
+
+        self.draw_state.buffers_created();
+    }
+
+    // Lets the compositor skip blending the surface with what's below it while it's opaque. The
+    // region is in surface coordinates, so it's recomputed whenever the size changes.
+    pub(super) fn update_opaque_region(
+        &self,
+        compositor: Option<&WlCompositor>,
+        queue_handle: &QueueHandle<State>,
+    ) {
+        if !self.opaque {
+            self.surface.set_opaque_region(None);
+            return;
+        }
+
+        let Some(compositor) = compositor else {
+            return;
+        };
+
+        let region = compositor.create_region(queue_handle, ());
+        region.add(0, 0, self.size.0 as i32, self.size.1 as i32);
+        self.surface.set_opaque_region(Some(&region));
+        region.destroy();
+    }
+
+    pub(super) fn init_xdg_surface(
+        &mut self,
+        wm_base: &XdgWmBase,
+        queue_handle: &QueueHandle<State>,
+    ) {
+        let xdg_surface = wm_base.get_xdg_surface(&self.surface, queue_handle, self.id());
+        let toplevel = xdg_surface.get_toplevel(queue_handle, self.id());
+        toplevel.set_title(self.title.clone());
+        toplevel.set_app_id(self.app_id.clone());
+        if self.fullscreen_requested {
+            toplevel.set_fullscreen(None);
+        }
+        if self.maximized_requested {
+            toplevel.set_maximized();
+        }
+
+        self.surface.commit();
+
+        self.xdg_surface = Some((xdg_surface, toplevel));
+    }
+
+    // Destroys the window's objects child first, so compositors don't log them as leaked.
+    pub(super) fn destroy(mut self) {
+        if let Some((xdg_surface, toplevel)) = self.xdg_surface.take() {
+            toplevel.destroy();
+            xdg_surface.destroy();
+        }
+
+        for shm_buffer in self.buffers.iter_mut() {
+            if let Some(buffer) = shm_buffer.buffer.take() {
+                buffer.destroy();
+            }
+            if let Some((pool, _)) = shm_buffer.pool.take() {
+                pool.destroy();
+            }
+        }
+
+        self.surface.destroy();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shm_bound_before_the_first_configure() {
+        let mut draw_state = DrawState::new(true);
+
+        draw_state.buffers_created();
+        assert!(!draw_state.ready_to_draw());
+
+        draw_state.configure();
+        assert!(draw_state.ready_to_draw());
+    }
+
+    #[test]
+    fn shm_bound_after_the_first_configure() {
+        let mut draw_state = DrawState::new(true);
+
+        draw_state.configure();
+        assert!(!draw_state.ready_to_draw());
+
+        draw_state.buffers_created();
+        assert!(draw_state.ready_to_draw());
+    }
+
+    #[test]
+    fn presenting_windows_only_wait_for_the_configure() {
+        let mut draw_state = DrawState::new(false);
+        assert!(!draw_state.ready_to_draw());
+
+        draw_state.configure();
+        assert!(draw_state.ready_to_draw());
+    }
+
+    #[test]
+    fn one_frame_per_frame_callback() {
+        let mut draw_state = DrawState::new(true);
+        draw_state.configure();
+        draw_state.buffers_created();
+
+        draw_state.frame_committed();
+        assert!(!draw_state.ready_to_draw());
+
+        // A resize while the frame is pending waits for the callback too.
+        draw_state.configure();
+        draw_state.buffers_created();
+        assert!(!draw_state.ready_to_draw());
+
+        draw_state.frame_done();
+        assert!(draw_state.ready_to_draw());
+    }
+}